@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use kinematics::model::KinematicState;
+use kinematics::motion::profile::JointSample;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+use crate::{Servo, ServoTarget};
+
+#[derive(Debug, Error)]
+pub enum CalibrationError {
+    #[error("Servo {servo:?} can not reach angle {angle} (degrees)")]
+    OutOfRange { servo: Servo, angle: f64 },
+    #[error("Servo {servo:?} can not move at speed {speed} (degrees per second)")]
+    TooFast { servo: Servo, speed: f64 },
+    #[error("Sample {0} does not come after the previous one")]
+    NonIncreasingTimes(usize),
+    #[error("The time of sample {0} is not finite")]
+    InvalidTime(usize),
+    #[error("No angle was reported for servo {0:?}")]
+    MissingAngle(Servo),
+    #[error("Invalid calibration of servo {servo:?}, {reason}")]
+    InvalidCalibration { servo: Servo, reason: &'static str },
+    #[error("Failed to access calibration file, error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to (de)serialize calibration, error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// The direction in which a servo turns when its joint angle increases.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServoDirection {
+    Normal,
+    Reversed,
+}
+
+impl ServoDirection {
+    fn sign(&self) -> f64 {
+        match self {
+            ServoDirection::Normal => 1_f64,
+            ServoDirection::Reversed => -1_f64,
+        }
+    }
+}
+
+/// The mapping between the angle of a joint (in radians) and the angle of the servo driving it
+///  (in degrees).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointCalibration {
+    /// The angle of the servo when the joint is at zero.
+    pub zero_offset: f64,
+    pub direction: ServoDirection,
+    /// The number of servo turns per turn of the joint.
+    pub gear_ratio: f64,
+    /// The lowest angle the servo can be commanded to.
+    pub min_angle: i16,
+    /// The highest angle the servo can be commanded to.
+    pub max_angle: i16,
+    /// The highest speed the servo can be commanded to, in degrees per second.
+    pub max_speed: i16,
+}
+
+impl Default for JointCalibration {
+    fn default() -> Self {
+        Self {
+            zero_offset: 90_f64,
+            direction: ServoDirection::Normal,
+            gear_ratio: 1_f64,
+            min_angle: 0_i16,
+            max_angle: 180_i16,
+            max_speed: i16::MAX,
+        }
+    }
+}
+
+impl JointCalibration {
+    pub fn new(
+        zero_offset: f64,
+        direction: ServoDirection,
+        gear_ratio: f64,
+        min_angle: i16,
+        max_angle: i16,
+    ) -> Self {
+        Self {
+            zero_offset,
+            direction,
+            gear_ratio,
+            min_angle,
+            max_angle,
+            ..Self::default()
+        }
+    }
+
+    /// Convert the given joint angle to the (unrounded) servo angle.
+    pub fn servo_angle(&self, theta: f64) -> f64 {
+        self.zero_offset + self.direction.sign() * self.gear_ratio * theta.to_degrees()
+    }
+
+    /// Convert the given servo angle back to the joint angle.
+    pub fn joint_angle(&self, angle: i16) -> f64 {
+        ((angle as f64 - self.zero_offset) / (self.direction.sign() * self.gear_ratio)).to_radians()
+    }
+
+    /// Convert the given joint angle to the nearest servo angle, fails when it lies outside of
+    ///  the limits of the servo.
+    fn command_angle(&self, servo: Servo, theta: f64) -> Result<i16, CalibrationError> {
+        let angle: f64 = self.servo_angle(theta).round();
+
+        // Written as a containment check so that NaN angles are rejected as well.
+        if !(self.min_angle as f64..=self.max_angle as f64).contains(&angle) {
+            return Err(CalibrationError::OutOfRange { servo, angle });
+        }
+
+        Ok(angle as i16)
+    }
+
+    /// Check that the calibration can be converted in both directions.
+    fn validate(&self, servo: Servo) -> Result<(), CalibrationError> {
+        let reason: Option<&'static str> = if !self.zero_offset.is_finite() {
+            Some("the zero offset must be finite")
+        } else if !self.gear_ratio.is_finite() || self.gear_ratio == 0_f64 {
+            Some("the gear ratio must be finite and non-zero")
+        } else if self.min_angle > self.max_angle {
+            Some("the minimum angle lies above the maximum angle")
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(CalibrationError::InvalidCalibration { servo, reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The calibrations of all the servos of the arm, which convert kinematic states into servo
+///  targets and reported servo angles back into kinematic states.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ServoCalibration {
+    /// The calibrations indexed by the identifiers of the servos.
+    pub joints: [JointCalibration; 5],
+}
+
+impl ServoCalibration {
+    /// Create a calibration from the calibrations of the servos, fails when one of them has a
+    ///  zero or non-finite gear ratio, a non-finite zero offset or inverted limits.
+    pub fn new(joints: [JointCalibration; 5]) -> Result<Self, CalibrationError> {
+        let calibration: Self = Self { joints };
+
+        calibration.validate()?;
+
+        Ok(calibration)
+    }
+
+    /// Load a calibration from the given (JSON) file, which is validated like in [`Self::new`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CalibrationError> {
+        let calibration: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        calibration.validate()?;
+
+        Ok(calibration)
+    }
+
+    /// Save the calibration to the given (JSON) file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CalibrationError> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    /// Check the calibrations of all the servos.
+    fn validate(&self) -> Result<(), CalibrationError> {
+        Servo::iter().try_for_each(|servo| self.joint(servo).validate(servo))
+    }
+
+    /// Get the calibration of the given servo.
+    pub fn joint(&self, servo: Servo) -> &JointCalibration {
+        &self.joints[servo.identifier() as usize]
+    }
+
+    /// Convert the given state to the angles of all the servos.
+    pub fn servo_angles(
+        &self,
+        state: &KinematicState,
+    ) -> Result<HashMap<Servo, i16>, CalibrationError> {
+        Servo::iter()
+            .map(|servo| {
+                Ok((
+                    servo,
+                    self.joint(servo)
+                        .command_angle(servo, joint_theta(state, servo))?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Convert the given state to a target for every servo, which all move at the given speed.
+    pub fn state_targets(
+        &self,
+        state: &KinematicState,
+        speed: i16,
+    ) -> Result<HashMap<Servo, ServoTarget>, CalibrationError> {
+        Ok(self
+            .servo_angles(state)?
+            .into_iter()
+            .map(|(servo, angle)| (servo, ServoTarget::new(angle, speed)))
+            .collect())
+    }
+
+    /// Convert the given joint-space trajectory to a sequence of targets for every servo.
+    ///
+    /// The first sample is taken to be the current state of the arm, every following sample
+    ///  becomes a target whose speed lets the servo arrive at the time of the sample. Samples in
+    ///  which a servo does not move are merged into its next move, so the time spent standing
+    ///  still is covered by the speed of that move instead.
+    ///
+    /// Fails when a sample time is not finite, or not later than the one of the previous sample.
+    pub fn trajectory_targets(
+        &self,
+        samples: &[JointSample],
+    ) -> Result<HashMap<Servo, Vec<ServoTarget>>, CalibrationError> {
+        let mut targets: HashMap<Servo, Vec<ServoTarget>> =
+            Servo::iter().map(|servo| (servo, Vec::new())).collect();
+
+        if let Some(i) = samples.iter().position(|sample| !sample.time.is_finite()) {
+            return Err(CalibrationError::InvalidTime(i));
+        }
+
+        let Some(first) = samples.first() else {
+            return Ok(targets);
+        };
+
+        // The angle and time of the last target of every servo.
+        let mut last: HashMap<Servo, (i16, f64)> = self
+            .servo_angles(&first.state)?
+            .into_iter()
+            .map(|(servo, angle)| (servo, (angle, first.time)))
+            .collect();
+
+        for (i, pair) in samples.windows(2_usize).enumerate() {
+            if pair[1].time <= pair[0].time {
+                return Err(CalibrationError::NonIncreasingTimes(i + 1_usize));
+            }
+
+            for (servo, angle) in self.servo_angles(&pair[1].state)? {
+                let (last_angle, last_time) = last[&servo];
+
+                if angle == last_angle {
+                    continue;
+                }
+
+                // Round the speed up, so the servo never arrives late.
+                let speed: f64 =
+                    ((angle - last_angle).abs() as f64 / (pair[1].time - last_time)).ceil();
+
+                if speed > self.joint(servo).max_speed as f64 {
+                    return Err(CalibrationError::TooFast { servo, speed });
+                }
+
+                targets
+                    .get_mut(&servo)
+                    .unwrap()
+                    .push(ServoTarget::new(angle, speed as i16));
+                last.insert(servo, (angle, pair[1].time));
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// Convert the angles reported by the servos back to a kinematic state.
+    pub fn state_from_angles(
+        &self,
+        angles: &HashMap<Servo, i16>,
+    ) -> Result<KinematicState, CalibrationError> {
+        let theta = |servo: Servo| -> Result<f64, CalibrationError> {
+            let angle: i16 = *angles
+                .get(&servo)
+                .ok_or(CalibrationError::MissingAngle(servo))?;
+
+            Ok(self.joint(servo).joint_angle(angle))
+        };
+
+        Ok(KinematicState {
+            theta_0: theta(Servo::Joint0)?,
+            theta_1: theta(Servo::Joint1)?,
+            theta_2: theta(Servo::Joint2)?,
+            theta_3: theta(Servo::Joint3)?,
+            theta_4: theta(Servo::Joint4)?,
+        })
+    }
+}
+
+/// Get the angle of the joint that is driven by the given servo.
+fn joint_theta(state: &KinematicState, servo: Servo) -> f64 {
+    match servo {
+        Servo::Joint0 => state.theta_0,
+        Servo::Joint1 => state.theta_1,
+        Servo::Joint2 => state.theta_2,
+        Servo::Joint3 => state.theta_3,
+        Servo::Joint4 => state.theta_4,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use kinematics::model::KinematicState;
+    use kinematics::motion::profile::{
+        JointDynamicsLimits, JointProfile, JointSample, ProfileShape,
+    };
+
+    use crate::calibration::{
+        CalibrationError, JointCalibration, ServoCalibration, ServoDirection,
+    };
+    use crate::{Servo, ServoTarget};
+
+    fn calibration() -> ServoCalibration {
+        ServoCalibration::new([
+            JointCalibration::default(),
+            JointCalibration::new(90_f64, ServoDirection::Reversed, 1_f64, 0_i16, 180_i16),
+            JointCalibration::new(10_f64, ServoDirection::Normal, 2_f64, 0_i16, 180_i16),
+            JointCalibration::default(),
+            JointCalibration::new(90_f64, ServoDirection::Normal, 1_f64, 45_i16, 135_i16),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    pub fn converts_states() {
+        let calibration: ServoCalibration = calibration();
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            theta_1: 0.5_f64,
+            theta_2: 0.5_f64,
+            ..KinematicState::default()
+        };
+
+        let targets: HashMap<Servo, ServoTarget> =
+            calibration.state_targets(&state, 50_i16).unwrap();
+
+        assert_eq!(targets[&Servo::Joint0], ServoTarget::new(119_i16, 50_i16));
+        assert_eq!(targets[&Servo::Joint1].angle(), 61_i16);
+        assert_eq!(targets[&Servo::Joint2].angle(), 67_i16);
+        assert_eq!(targets[&Servo::Joint3].angle(), 90_i16);
+
+        // Going back loses no more than the rounding to whole (servo) degrees.
+        let angles: HashMap<Servo, i16> = targets
+            .iter()
+            .map(|(servo, target)| (*servo, target.angle()))
+            .collect();
+        let reported: KinematicState = calibration.state_from_angles(&angles).unwrap();
+
+        assert!((reported.theta_0 - state.theta_0).abs() <= 0.5_f64.to_radians());
+        assert!((reported.theta_1 - state.theta_1).abs() <= 0.5_f64.to_radians());
+        assert!((reported.theta_2 - state.theta_2).abs() <= 0.25_f64.to_radians());
+        assert_eq!(reported.theta_3, 0_f64);
+
+        // The limits of the servos are enforced, and every servo has to report its angle.
+        assert!(matches!(
+            calibration.servo_angles(&KinematicState {
+                theta_4: 1_f64,
+                ..KinematicState::default()
+            }),
+            Err(CalibrationError::OutOfRange {
+                servo: Servo::Joint4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            calibration.state_from_angles(&HashMap::from([(Servo::Joint0, 90_i16)])),
+            Err(CalibrationError::MissingAngle(Servo::Joint1))
+        ));
+    }
+
+    #[test]
+    pub fn converts_trajectories() {
+        let calibration: ServoCalibration = calibration();
+        let profile: JointProfile = JointProfile::new(
+            &KinematicState::default(),
+            &KinematicState {
+                theta_0: 1_f64,
+                ..KinematicState::default()
+            },
+            &JointDynamicsLimits::uniform(1_f64, 2_f64, 10_f64),
+            ProfileShape::Trapezoidal,
+        )
+        .unwrap();
+        let samples: Vec<JointSample> = profile.sample(0.1_f64).unwrap();
+
+        let targets: HashMap<Servo, Vec<ServoTarget>> =
+            calibration.trajectory_targets(&samples).unwrap();
+
+        // Only the first servo moves, and it arrives at the end of the move.
+        assert!(targets[&Servo::Joint1].is_empty());
+        assert_eq!(
+            targets[&Servo::Joint0].last().unwrap().angle(),
+            (90_f64 + 1_f64.to_degrees()).round() as i16
+        );
+
+        // The speeds follow the profile, they rise to (at least) the velocity limit and fall
+        //  again.
+        let speeds: Vec<i16> = targets[&Servo::Joint0]
+            .iter()
+            .map(|target| target.speed())
+            .collect();
+        let peak: i16 = *speeds.iter().max().unwrap();
+
+        assert!(peak as f64 >= 1_f64.to_degrees());
+        assert!(speeds[0] < peak && *speeds.last().unwrap() < peak);
+
+        // A servo that can not keep up with the profile is refused.
+        let mut slow: ServoCalibration = calibration.clone();
+        slow.joints[0].max_speed = 30_i16;
+
+        assert!(matches!(
+            slow.trajectory_targets(&samples),
+            Err(CalibrationError::TooFast {
+                servo: Servo::Joint0,
+                ..
+            })
+        ));
+
+        // A sample at a time that is not finite would turn into a target at speed zero.
+        for (i, time) in [
+            (0_usize, f64::NEG_INFINITY),
+            (5_usize, f64::NAN),
+            (9_usize, f64::INFINITY),
+        ] {
+            let mut invalid: Vec<JointSample> = samples.clone();
+            invalid[i].time = time;
+
+            assert!(matches!(
+                calibration.trajectory_targets(&invalid),
+                Err(CalibrationError::InvalidTime(index)) if index == i
+            ));
+        }
+    }
+
+    #[test]
+    pub fn refuses_invalid_input() {
+        let calibration: ServoCalibration = calibration();
+
+        // A NaN joint angle must never turn into a servo command.
+        assert!(matches!(
+            calibration.servo_angles(&KinematicState {
+                theta_2: f64::NAN,
+                ..KinematicState::default()
+            }),
+            Err(CalibrationError::OutOfRange {
+                servo: Servo::Joint2,
+                ..
+            })
+        ));
+
+        // Calibrations that can not be inverted are refused, both when built and when loaded.
+        for (zero_offset, gear_ratio) in
+            [(90_f64, 0_f64), (f64::NAN, 1_f64), (90_f64, f64::INFINITY)]
+        {
+            let mut joints: [JointCalibration; 5] = calibration.joints.clone();
+            joints[3].zero_offset = zero_offset;
+            joints[3].gear_ratio = gear_ratio;
+
+            assert!(matches!(
+                ServoCalibration::new(joints),
+                Err(CalibrationError::InvalidCalibration {
+                    servo: Servo::Joint3,
+                    ..
+                })
+            ));
+        }
+
+        let mut broken: ServoCalibration = calibration.clone();
+        broken.joints[1].gear_ratio = 0_f64;
+
+        let path: PathBuf = std::env::temp_dir().join("rustydog_servo_calibration.json");
+        broken.save(&path).unwrap();
+        let loaded: Result<ServoCalibration, CalibrationError> = ServoCalibration::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            loaded,
+            Err(CalibrationError::InvalidCalibration {
+                servo: Servo::Joint1,
+                ..
+            })
+        ));
+    }
+}
@@ -20,6 +20,8 @@ use tokio::time::{sleep, timeout};
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
 
+pub mod calibration;
+
 #[derive(Copy, Clone, EnumIter, PartialEq, Eq, Debug, Hash)]
 pub enum Servo {
     Joint0,
@@ -41,7 +43,7 @@ impl Servo {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
 pub struct ServoTarget {
     angle: i16,
     speed: i16,
@@ -51,6 +53,16 @@ impl ServoTarget {
     pub fn new(angle: i16, speed: i16) -> Self {
         Self { angle, speed }
     }
+
+    /// Get the angle of the target in degrees.
+    pub fn angle(&self) -> i16 {
+        self.angle
+    }
+
+    /// Get the speed at which the servo moves to the target.
+    pub fn speed(&self) -> i16 {
+        self.speed
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,326 @@
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+use crate::model::{KinematicParameters, KinematicState};
+
+pub mod scene;
+
+/// The squared length below which a segment is treated as a single point.
+const DEGENERATE_EPS: f64 = 1e-12_f64;
+
+/// A capsule, the set of points within the radius of a line segment.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Capsule {
+    pub start: Vector3<f64>,
+    pub end: Vector3<f64>,
+    pub radius: f64,
+}
+
+impl Capsule {
+    pub fn new(start: Vector3<f64>, end: Vector3<f64>, radius: f64) -> Self {
+        Self { start, end, radius }
+    }
+
+    /// Compute the distance between the surfaces of both capsules, which is negative when they
+    ///  overlap (by the depth of the overlap).
+    pub fn distance(&self, other: &Capsule) -> f64 {
+        let (a, b) = closest_segment_points(&self.start, &self.end, &other.start, &other.end);
+
+        (a - b).magnitude() - self.radius - other.radius
+    }
+}
+
+/// Compute the closest points between the segments `p0-p1` and `q0-q1`, the first point lies on
+///  the first segment and the second one on the second segment.
+pub fn closest_segment_points(
+    p0: &Vector3<f64>,
+    p1: &Vector3<f64>,
+    q0: &Vector3<f64>,
+    q1: &Vector3<f64>,
+) -> (Vector3<f64>, Vector3<f64>) {
+    let d1: Vector3<f64> = p1 - p0;
+    let d2: Vector3<f64> = q1 - q0;
+    let r: Vector3<f64> = p0 - q0;
+    let a: f64 = d1.magnitude_squared();
+    let e: f64 = d2.magnitude_squared();
+    let f: f64 = d2.dot(&r);
+
+    // Solve for the parameters along both segments, treating degenerate segments as points.
+    let (s, t) = if a <= DEGENERATE_EPS && e <= DEGENERATE_EPS {
+        (0_f64, 0_f64)
+    } else if a <= DEGENERATE_EPS {
+        (0_f64, (f / e).clamp(0_f64, 1_f64))
+    } else {
+        let c: f64 = d1.dot(&r);
+
+        if e <= DEGENERATE_EPS {
+            ((-c / a).clamp(0_f64, 1_f64), 0_f64)
+        } else {
+            let b: f64 = d1.dot(&d2);
+            let denominator: f64 = a * e - b * b;
+
+            // Parallel segments have no unique closest points, so any point of the first one
+            //  will do.
+            let s: f64 = if denominator > DEGENERATE_EPS {
+                ((b * f - c * e) / denominator).clamp(0_f64, 1_f64)
+            } else {
+                0_f64
+            };
+
+            // Compute the closest point on the second segment, and clamp it back onto the segment
+            //  (recomputing the point on the first one when it was clamped).
+            let t: f64 = (b * s + f) / e;
+
+            if t < 0_f64 {
+                ((-c / a).clamp(0_f64, 1_f64), 0_f64)
+            } else if t > 1_f64 {
+                (((b - c) / a).clamp(0_f64, 1_f64), 1_f64)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p0 + d1 * s, q0 + d2 * t)
+}
+
+/// Compute the capsules of all the links, the first link starts in the origin of the base frame
+///  and every other link starts at the end of the previous one.
+pub fn link_capsules(
+    fk: &dyn ForwardKinematicAlgorithm,
+    params: &KinematicParameters,
+    state: &KinematicState,
+) -> [Capsule; 5] {
+    let radii: [f64; 5] = params.radii.as_array();
+    let mut start: Vector3<f64> = Vector3::<f64>::zeros();
+
+    Limb::ALL.map(|limb| {
+        let end: Vector3<f64> = fk.limb_position_vector(params, state, limb);
+        let capsule: Capsule = Capsule::new(start, end, radii[limb.index()]);
+        start = end;
+
+        capsule
+    })
+}
+
+/// The distance between the capsules of two links.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkDistance {
+    pub links: (Limb, Limb),
+    /// The distance between the surfaces of the capsules, negative when they overlap.
+    pub distance: f64,
+}
+
+/// Checks if the links of the arm collide with each other, by wrapping every link in a capsule
+///  around the segment between the joints at its ends.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfCollisionChecker {
+    /// The distance below which two links are considered to collide.
+    pub margin: f64,
+    /// The pairs of links that are checked.
+    pub pairs: Vec<(Limb, Limb)>,
+}
+
+impl Default for SelfCollisionChecker {
+    /// Check every pair of links that do not share a joint, since links that do always touch.
+    fn default() -> Self {
+        let mut pairs: Vec<(Limb, Limb)> = Vec::new();
+
+        for a in Limb::ALL {
+            for b in Limb::ALL {
+                if b.index() > a.index() + 1_usize {
+                    pairs.push((a, b));
+                }
+            }
+        }
+
+        Self {
+            margin: 0_f64,
+            pairs,
+        }
+    }
+}
+
+impl SelfCollisionChecker {
+    pub fn new(margin: f64, pairs: Vec<(Limb, Limb)>) -> Self {
+        Self { margin, pairs }
+    }
+
+    /// Compute the capsules of all the links, see `link_capsules`.
+    pub fn link_capsules(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> [Capsule; 5] {
+        link_capsules(fk, params, state)
+    }
+
+    /// Compute the distances between all the checked pairs of links.
+    pub fn distances(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vec<LinkDistance> {
+        let capsules: [Capsule; 5] = self.link_capsules(fk, params, state);
+
+        self.pairs
+            .iter()
+            .map(|(a, b)| LinkDistance {
+                links: (*a, *b),
+                distance: capsules[a.index()].distance(&capsules[b.index()]),
+            })
+            .collect()
+    }
+
+    /// Compute the pairs of links that collide, ordered from the deepest overlap to the
+    ///  shallowest.
+    pub fn collisions(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vec<LinkDistance> {
+        let mut collisions: Vec<LinkDistance> = self
+            .distances(fk, params, state)
+            .into_iter()
+            .filter(|distance| distance.distance < self.margin)
+            .collect();
+
+        collisions.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        collisions
+    }
+
+    /// Check if any of the checked pairs of links collide.
+    pub fn is_colliding(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> bool {
+        !self.collisions(fk, params, state).is_empty()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+
+    use crate::collision::{closest_segment_points, LinkDistance, SelfCollisionChecker};
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::Limb;
+    use crate::model::{KinematicParameters, KinematicState};
+
+    #[test]
+    pub fn segment_distances() {
+        let distance = |p0: [f64; 3], p1: [f64; 3], q0: [f64; 3], q1: [f64; 3]| -> f64 {
+            let (a, b) = closest_segment_points(
+                &Vector3::<f64>::from(p0),
+                &Vector3::<f64>::from(p1),
+                &Vector3::<f64>::from(q0),
+                &Vector3::<f64>::from(q1),
+            );
+
+            (a - b).magnitude()
+        };
+
+        // Crossing segments, at a vertical offset.
+        assert!(
+            (distance(
+                [-1_f64, 0_f64, 0_f64],
+                [1_f64, 0_f64, 0_f64],
+                [0_f64, 2_f64, -1_f64],
+                [0_f64, 2_f64, 1_f64]
+            ) - 2_f64)
+                .abs()
+                < 10_f64.powf(-12_f64)
+        );
+
+        // Parallel segments, overlapping along their length.
+        assert!(
+            (distance(
+                [0_f64, 0_f64, 0_f64],
+                [2_f64, 0_f64, 0_f64],
+                [1_f64, 3_f64, 0_f64],
+                [4_f64, 3_f64, 0_f64]
+            ) - 3_f64)
+                .abs()
+                < 10_f64.powf(-12_f64)
+        );
+
+        // Segments that are closest in their end points, and a segment that is a point.
+        assert!(
+            (distance(
+                [0_f64, 0_f64, 0_f64],
+                [1_f64, 0_f64, 0_f64],
+                [4_f64, 4_f64, 0_f64],
+                [4_f64, 8_f64, 0_f64]
+            ) - 5_f64)
+                .abs()
+                < 10_f64.powf(-12_f64)
+        );
+        assert!(
+            (distance(
+                [0_f64, 0_f64, 0_f64],
+                [0_f64, 0_f64, 0_f64],
+                [-1_f64, 1_f64, 0_f64],
+                [1_f64, 1_f64, 0_f64]
+            ) - 1_f64)
+                .abs()
+                < 10_f64.powf(-12_f64)
+        );
+    }
+
+    #[test]
+    pub fn detects_folded_arm() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let checker: SelfCollisionChecker = SelfCollisionChecker::default();
+
+        // The upright arm only touches itself in its joints.
+        assert!(!checker.is_colliding(&fk_solver, &params, &KinematicState::default()));
+
+        // Folding the elbow fully makes the last links run down along the base.
+        let folded: KinematicState = KinematicState {
+            theta_2: PI,
+            ..KinematicState::default()
+        };
+        let collisions: Vec<LinkDistance> = checker.collisions(&fk_solver, &params, &folded);
+
+        // The last link hangs below the base, clear of the links of the pitch chain.
+        let mut links: Vec<(Limb, Limb)> =
+            collisions.iter().map(|collision| collision.links).collect();
+        links.sort();
+
+        assert_eq!(
+            links,
+            vec![
+                (Limb::Limb0, Limb::Limb2),
+                (Limb::Limb0, Limb::Limb3),
+                (Limb::Limb0, Limb::Limb4),
+                (Limb::Limb1, Limb::Limb3),
+            ]
+        );
+        assert!(collisions
+            .iter()
+            .all(|collision| (collision.distance + 2_f64).abs() < 10_f64.powf(-9_f64)));
+
+        // A bent arm keeps its distance, and reports it.
+        let bent: KinematicState = KinematicState {
+            theta_1: 0.5_f64,
+            theta_2: 1_f64,
+            ..KinematicState::default()
+        };
+        let distances: Vec<LinkDistance> = checker.distances(&fk_solver, &params, &bent);
+
+        assert_eq!(distances.len(), 6_usize);
+        assert!(distances.iter().all(|distance| distance.distance > 0_f64));
+        assert!(!checker.is_colliding(&fk_solver, &params, &bent));
+    }
+}
@@ -0,0 +1,416 @@
+use std::path::Path;
+
+use nalgebra::{Matrix3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::collision::{link_capsules, Capsule};
+use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+use crate::model::{KinematicParameters, KinematicState};
+
+/// The number of golden-section iterations used to find the point of a segment closest to an
+///  obstacle, which shrinks the search interval below a billionth of the segment.
+const SEGMENT_SEARCH_ITERATIONS: usize = 48_usize;
+
+#[derive(Debug, Error)]
+pub enum SceneError {
+    #[error("Failed to access scene file, error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to (de)serialize scene, error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Obstacle {obstacle} is degenerate, {reason}")]
+    InvalidObstacle {
+        obstacle: usize,
+        reason: &'static str,
+    },
+}
+
+/// A solid obstacle in the environment of the arm, expressed in the base frame.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Obstacle {
+    /// A box with the given half extents along the axes of its orientation (the columns of the
+    ///  rotation matrix).
+    Box {
+        center: Vector3<f64>,
+        half_extents: Vector3<f64>,
+        orientation: Matrix3<f64>,
+    },
+    Sphere {
+        center: Vector3<f64>,
+        radius: f64,
+    },
+    /// A cylinder around the segment between the centers of its end caps.
+    Cylinder {
+        start: Vector3<f64>,
+        end: Vector3<f64>,
+        radius: f64,
+    },
+    /// The half-space below the plane through the point, the normal points out of the solid.
+    Plane {
+        point: Vector3<f64>,
+        normal: Vector3<f64>,
+    },
+}
+
+impl Obstacle {
+    /// Create a box that is aligned with the axes of the base frame.
+    pub fn aligned_box(center: Vector3<f64>, half_extents: Vector3<f64>) -> Self {
+        Self::Box {
+            center,
+            half_extents,
+            orientation: Matrix3::<f64>::identity(),
+        }
+    }
+
+    /// Create the half-space below the horizontal plane at the given height, like a table top.
+    pub fn floor(height: f64) -> Self {
+        Self::Plane {
+            point: Vector3::<f64>::new(0_f64, height, 0_f64),
+            normal: Vector3::<f64>::y(),
+        }
+    }
+
+    /// Check that the distance to the obstacle is well defined, which fails for a negative or
+    ///  non-finite size, a cylinder without an axis and a plane without a normal.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            Obstacle::Box { half_extents, .. } if !half_extents.iter().all(is_valid_size) => {
+                Err("the half extents of the box are negative or not finite")
+            }
+            Obstacle::Sphere { radius, .. } | Obstacle::Cylinder { radius, .. }
+                if !is_valid_size(radius) =>
+            {
+                Err("the radius is negative or not finite")
+            }
+            Obstacle::Cylinder { start, end, .. } if is_degenerate(&(end - start)) => {
+                Err("the start and end of the cylinder coincide")
+            }
+            Obstacle::Plane { normal, .. } if is_degenerate(normal) => {
+                Err("the normal of the plane is zero")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Compute the signed distance from the given point to the surface of the obstacle, which is
+    ///  negative inside of it.
+    pub fn signed_distance(&self, point: &Vector3<f64>) -> f64 {
+        match self {
+            Obstacle::Box {
+                center,
+                half_extents,
+                orientation,
+            } => {
+                let local: Vector3<f64> = orientation.transpose() * (point - center);
+                let q: Vector3<f64> = local.abs() - half_extents;
+
+                q.map(|x| x.max(0_f64)).magnitude() + q.max().min(0_f64)
+            }
+            Obstacle::Sphere { center, radius } => (point - center).magnitude() - radius,
+            Obstacle::Cylinder { start, end, radius } => {
+                let axis: Vector3<f64> = end - start;
+                let length: f64 = axis.magnitude();
+                let axis: Vector3<f64> = axis / length;
+
+                // Measure the point radially from the axis and along the axis from the middle.
+                let offset: Vector3<f64> = point - (start + end) / 2_f64;
+                let along: f64 = offset.dot(&axis);
+                let q: Vector2<f64> = Vector2::<f64>::new(
+                    (offset - axis * along).magnitude() - radius,
+                    along.abs() - length / 2_f64,
+                );
+
+                q.map(|x| x.max(0_f64)).magnitude() + q.max().min(0_f64)
+            }
+            Obstacle::Plane {
+                point: origin,
+                normal,
+            } => (point - origin).dot(&normal.normalize()),
+        }
+    }
+
+    /// Compute the distance between the surface of the capsule and the obstacle, which is
+    ///  negative when they overlap.
+    ///
+    /// The signed distance to a convex obstacle is convex along the segment of the capsule, so
+    ///  its minimum is found with a golden-section search.
+    pub fn capsule_distance(&self, capsule: &Capsule) -> f64 {
+        let distance = |t: f64| -> f64 {
+            self.signed_distance(&(capsule.start + (capsule.end - capsule.start) * t))
+        };
+
+        let ratio: f64 = (5_f64.sqrt() - 1_f64) / 2_f64;
+        let (mut low, mut high) = (0_f64, 1_f64);
+
+        for _ in 0..SEGMENT_SEARCH_ITERATIONS {
+            let left: f64 = high - ratio * (high - low);
+            let right: f64 = low + ratio * (high - low);
+
+            if distance(left) < distance(right) {
+                high = right;
+            } else {
+                low = left;
+            }
+        }
+
+        distance((low + high) / 2_f64)
+            .min(distance(0_f64))
+            .min(distance(1_f64))
+            - capsule.radius
+    }
+}
+
+/// Check if the given size (a radius or half extent) is finite and not negative.
+fn is_valid_size(size: &f64) -> bool {
+    size.is_finite() && *size >= 0_f64
+}
+
+/// Check if the given direction is zero (or not a number), so it can not be normalized.
+fn is_degenerate(direction: &Vector3<f64>) -> bool {
+    let length: f64 = direction.magnitude();
+
+    length.is_nan() || length == 0_f64
+}
+
+/// The distance between a link of the arm and an obstacle of the scene.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObstacleDistance {
+    pub link: Limb,
+    /// The index of the obstacle in the scene.
+    pub obstacle: usize,
+    /// The distance between the surfaces of the link capsule and the obstacle, negative when they
+    ///  overlap.
+    pub distance: f64,
+}
+
+/// The obstacles in the environment of the arm.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Scene {
+    pub obstacles: Vec<Obstacle>,
+    /// The distance below which a link is considered to collide with an obstacle.
+    #[serde(default)]
+    pub margin: f64,
+}
+
+impl Scene {
+    /// Create a scene from the given obstacles, fails when one of them is degenerate (see
+    ///  `Obstacle::validate`).
+    pub fn new(obstacles: Vec<Obstacle>, margin: f64) -> Result<Self, SceneError> {
+        let scene: Self = Self { obstacles, margin };
+
+        scene.validate()?;
+
+        Ok(scene)
+    }
+
+    /// Load a scene from the given (JSON) file, which is validated like in `new`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let scene: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        scene.validate()?;
+
+        Ok(scene)
+    }
+
+    /// Save the scene to the given (JSON) file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    /// Check that none of the obstacles is degenerate.
+    fn validate(&self) -> Result<(), SceneError> {
+        self.obstacles
+            .iter()
+            .enumerate()
+            .try_for_each(|(obstacle, shape)| {
+                shape
+                    .validate()
+                    .map_err(|reason| SceneError::InvalidObstacle { obstacle, reason })
+            })
+    }
+
+    /// Compute the distances between the moving links of the arm and all the obstacles.
+    ///
+    /// The first link only turns around its own axis, so it never moves into an obstacle and is
+    ///  not checked (which allows the arm to be mounted on an obstacle, like a table).
+    pub fn distances(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vec<ObstacleDistance> {
+        let capsules: [Capsule; 5] = link_capsules(fk, params, state);
+
+        Limb::ALL[1..]
+            .iter()
+            .flat_map(|link| {
+                self.obstacles
+                    .iter()
+                    .enumerate()
+                    .map(|(obstacle, shape)| ObstacleDistance {
+                        link: *link,
+                        obstacle,
+                        distance: shape.capsule_distance(&capsules[link.index()]),
+                    })
+            })
+            .collect()
+    }
+
+    /// Compute the links that collide with an obstacle, ordered from the deepest overlap to the
+    ///  shallowest.
+    pub fn collisions(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vec<ObstacleDistance> {
+        let mut collisions: Vec<ObstacleDistance> = self
+            .distances(fk, params, state)
+            .into_iter()
+            .filter(|distance| distance.distance < self.margin)
+            .collect();
+
+        collisions.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        collisions
+    }
+
+    /// Check if any of the links collides with an obstacle.
+    pub fn is_colliding(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> bool {
+        let capsules: [Capsule; 5] = link_capsules(fk, params, state);
+
+        Limb::ALL[1..].iter().any(|link| {
+            self.obstacles
+                .iter()
+                .any(|shape| shape.capsule_distance(&capsules[link.index()]) < self.margin)
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{Rotation3, Vector3};
+
+    use crate::collision::scene::{Obstacle, Scene, SceneError};
+    use crate::collision::Capsule;
+
+    #[test]
+    pub fn obstacle_distances() {
+        let close = |a: f64, b: f64| -> bool { (a - b).abs() < 10_f64.powf(-6_f64) };
+        let capsule: Capsule = Capsule::new(
+            Vector3::<f64>::new(-5_f64, 4_f64, 0_f64),
+            Vector3::<f64>::new(5_f64, 4_f64, 0_f64),
+            1_f64,
+        );
+
+        // The closest point of the capsule lies in the middle of its segment.
+        let sphere: Obstacle = Obstacle::Sphere {
+            center: Vector3::<f64>::zeros(),
+            radius: 2_f64,
+        };
+        assert!(close(sphere.capsule_distance(&capsule), 1_f64));
+
+        // A rotated box reaches up to the capsule with its edge.
+        let rotated: Obstacle = Obstacle::Box {
+            center: Vector3::<f64>::zeros(),
+            half_extents: Vector3::<f64>::new(1_f64, 1_f64, 5_f64),
+            orientation: *Rotation3::<f64>::from_axis_angle(
+                &Vector3::<f64>::z_axis(),
+                std::f64::consts::FRAC_PI_4,
+            )
+            .matrix(),
+        };
+        assert!(close(
+            rotated.capsule_distance(&capsule),
+            3_f64 - 2_f64.sqrt()
+        ));
+        assert!(close(
+            Obstacle::aligned_box(Vector3::<f64>::zeros(), Vector3::<f64>::repeat(1_f64))
+                .capsule_distance(&capsule),
+            2_f64
+        ));
+
+        // A vertical cylinder that the capsule passes through.
+        let cylinder: Obstacle = Obstacle::Cylinder {
+            start: Vector3::<f64>::new(3_f64, 0_f64, 0_f64),
+            end: Vector3::<f64>::new(3_f64, 10_f64, 0_f64),
+            radius: 0.5_f64,
+        };
+        assert!(close(cylinder.capsule_distance(&capsule), -1.5_f64));
+
+        // A tilted capsule dips below the table by its lowest end.
+        let tilted: Capsule = Capsule::new(
+            Vector3::<f64>::new(0_f64, 0.5_f64, 0_f64),
+            Vector3::<f64>::new(0_f64, 8_f64, 3_f64),
+            1_f64,
+        );
+        assert!(close(
+            Obstacle::floor(0_f64).capsule_distance(&tilted),
+            -0.5_f64
+        ));
+        assert!(close(
+            Obstacle::Plane {
+                point: Vector3::<f64>::zeros(),
+                normal: Vector3::<f64>::new(0_f64, 0_f64, -2_f64),
+            }
+            .capsule_distance(&tilted),
+            -4_f64
+        ));
+    }
+
+    #[test]
+    pub fn refuses_degenerate_obstacles() {
+        let point: Vector3<f64> = Vector3::<f64>::new(1_f64, 2_f64, 3_f64);
+
+        assert!(matches!(
+            Scene::new(
+                vec![
+                    Obstacle::floor(0_f64),
+                    Obstacle::Cylinder {
+                        start: point,
+                        end: point,
+                        radius: 1_f64,
+                    },
+                ],
+                0_f64,
+            ),
+            Err(SceneError::InvalidObstacle { obstacle: 1, .. })
+        ));
+        assert!(matches!(
+            Scene::new(
+                vec![Obstacle::Plane {
+                    point,
+                    normal: Vector3::<f64>::zeros(),
+                }],
+                0_f64,
+            ),
+            Err(SceneError::InvalidObstacle { obstacle: 0, .. })
+        ));
+        assert!(Scene::new(vec![Obstacle::floor(0_f64)], 0_f64).is_ok());
+
+        for size in [-1_f64, f64::NAN, f64::INFINITY] {
+            for obstacle in [
+                Obstacle::aligned_box(point, Vector3::<f64>::new(1_f64, size, 1_f64)),
+                Obstacle::Sphere {
+                    center: point,
+                    radius: size,
+                },
+                Obstacle::Cylinder {
+                    start: point,
+                    end: Vector3::<f64>::zeros(),
+                    radius: size,
+                },
+            ] {
+                assert!(matches!(
+                    Scene::new(vec![obstacle], 0_f64),
+                    Err(SceneError::InvalidObstacle { obstacle: 0, .. })
+                ));
+            }
+        }
+    }
+}
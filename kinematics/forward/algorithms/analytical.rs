@@ -1,7 +1,10 @@
-use nalgebra::{Matrix3, Vector3};
+use nalgebra::{Matrix3, Matrix6x5, Vector3};
 
-use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::forward::algorithms::{
+    limb_geometric_jacobian, ForwardKinematicAlgorithm, JointAxis, Limb,
+};
 use crate::model::{KinematicParameters, KinematicState};
+use crate::orientation::euler::EulerConvention;
 
 /// Analytical forward kinematic approach, see the derivation notebook for the specifics.
 pub struct AnalyticalForwardKinematicAlgorithm {}
@@ -88,6 +91,7 @@ impl ForwardKinematicAlgorithm for AnalyticalForwardKinematicAlgorithm {
             l_2,
             l_3,
             l_4,
+            ..
         }: &KinematicParameters,
         &KinematicState {
             theta_0,
@@ -117,33 +121,16 @@ impl ForwardKinematicAlgorithm for AnalyticalForwardKinematicAlgorithm {
 
     fn limb4_euler_angles(
         &self,
-        &KinematicParameters {
-            l_0,
-            l_1,
-            l_2,
-            l_3,
-            l_4,
-        }: &KinematicParameters,
-        &KinematicState {
-            theta_0,
-            theta_1,
-            theta_2,
-            theta_3,
-            theta_4,
-        }: &KinematicState,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        convention: EulerConvention,
     ) -> Vector3<f64> {
-        todo!()
+        convention.euler_angles(&self.limb4_orientation_matrix(params, state))
     }
 
     fn limb4_orientation_matrix(
         &self,
-        &KinematicParameters {
-            l_0,
-            l_1,
-            l_2,
-            l_3,
-            l_4,
-        }: &KinematicParameters,
+        &KinematicParameters { .. }: &KinematicParameters,
         &KinematicState {
             theta_0,
             theta_1,
@@ -168,15 +155,45 @@ impl ForwardKinematicAlgorithm for AnalyticalForwardKinematicAlgorithm {
                 + theta_0.cos() * theta_4.cos() * (theta_1 + theta_2 + theta_3).cos(),
         )
     }
+
+    fn limb_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Matrix6x5<f64> {
+        let theta_0: f64 = state.theta_0;
+
+        // The base rotates around the vertical axis, the pitch joints around the horizontal axis
+        //  perpendicular to the arm, and the wrist around the tool axis.
+        let pitch_axis: Vector3<f64> = -Vector3::<f64>::new(theta_0.cos(), 0_f64, theta_0.sin());
+        let tool_axis: Vector3<f64> = -self
+            .limb4_orientation_matrix(params, state)
+            .column(1)
+            .into_owned();
+
+        limb_geometric_jacobian(
+            &self.limb_position_vector(params, state, limb),
+            &[
+                JointAxis::revolute(Vector3::<f64>::zeros(), -Vector3::<f64>::y()),
+                JointAxis::revolute(self.limb0_position_vector(params, state), pitch_axis),
+                JointAxis::revolute(self.limb1_position_vector(params, state), pitch_axis),
+                JointAxis::revolute(self.limb2_position_vector(params, state), pitch_axis),
+                JointAxis::revolute(self.limb3_position_vector(params, state), tool_axis),
+            ],
+            limb,
+        )
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use nalgebra::Vector3;
+    use nalgebra::{Matrix3, Matrix6x5, Vector3, Vector5};
 
     use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
-    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
     use crate::model::{KinematicParameters, KinematicState};
+    use crate::orientation::euler::EulerConvention;
 
     #[test]
     pub fn solver_for_straight_pose() {
@@ -202,4 +219,113 @@ pub mod tests {
             Vector3::new(0_f64, params.sum_of_link_lengths(), 0_f64)
         );
     }
+
+    #[test]
+    pub fn euler_angles_round_trip() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        let state: KinematicState = KinematicState {
+            theta_0: 0.4_f64,
+            theta_1: -0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.2_f64,
+            theta_4: -1.1_f64,
+        };
+
+        for convention in [
+            EulerConvention::Zyx,
+            EulerConvention::Xyz,
+            EulerConvention::Zyz,
+            EulerConvention::Yxy,
+        ] {
+            // Turn the euler angles back into a matrix, and compare it to the orientation matrix.
+            let angles: Vector3<f64> = solver.limb4_euler_angles(&params, &state, convention);
+            let matrix: Matrix3<f64> = solver.limb4_orientation_matrix(&params, &state);
+
+            assert!((convention.rotation_matrix(&angles) - matrix).norm() < thresh);
+        }
+    }
+
+    #[test]
+    pub fn euler_angles_for_base_rotation() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        // Only rotate the base, which rotates the end-effector around the vertical (y) axis.
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            ..KinematicState::default()
+        };
+
+        let angles: Vector3<f64> = solver.limb4_euler_angles(&params, &state, EulerConvention::Yxz);
+
+        assert!((angles - Vector3::<f64>::new(-0.5_f64, 0_f64, 0_f64)).norm() < thresh);
+    }
+
+    #[test]
+    pub fn jacobian_matches_finite_differences() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        let eps: f64 = 10_f64.powf(-6_f64);
+        let thresh: f64 = 10_f64.powf(-5_f64);
+
+        let state: KinematicState = KinematicState {
+            theta_0: 0.4_f64,
+            theta_1: -0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.2_f64,
+            theta_4: -1.1_f64,
+        };
+
+        for limb in Limb::ALL {
+            let jacobian: Matrix6x5<f64> = solver.limb_jacobian(&params, &state, limb);
+
+            for i in 0..5 {
+                // Perturb a single joint in both directions.
+                let offset: Vector5<f64> =
+                    Vector5::<f64>::from_fn(|j, _| if i == j { eps } else { 0_f64 });
+                let forward: KinematicState =
+                    KinematicState::from(Vector5::<f64>::from(&state) + offset);
+                let backward: KinematicState =
+                    KinematicState::from(Vector5::<f64>::from(&state) - offset);
+
+                // The linear part is the derivative of the end position of the limb.
+                let linear: Vector3<f64> = (solver.limb_position_vector(&params, &forward, limb)
+                    - solver.limb_position_vector(&params, &backward, limb))
+                    / (2_f64 * eps);
+
+                assert!((linear - jacobian.fixed_view::<3, 1>(0, i)).magnitude() < thresh);
+            }
+        }
+
+        // The angular part of the end-effector follows from `skew(w) = dR/dt * R^T`.
+        let jacobian: Matrix6x5<f64> = solver.limb4_jacobian(&params, &state);
+        let orientation: Matrix3<f64> = solver.limb4_orientation_matrix(&params, &state);
+
+        for i in 0..5 {
+            let offset: Vector5<f64> =
+                Vector5::<f64>::from_fn(|j, _| if i == j { eps } else { 0_f64 });
+            let skew: Matrix3<f64> = (solver.limb4_orientation_matrix(
+                &params,
+                &KinematicState::from(Vector5::<f64>::from(&state) + offset),
+            ) - solver.limb4_orientation_matrix(
+                &params,
+                &KinematicState::from(Vector5::<f64>::from(&state) - offset),
+            )) / (2_f64 * eps)
+                * orientation.transpose();
+            let angular: Vector3<f64> =
+                Vector3::<f64>::new(skew[(2, 1)], skew[(0, 2)], skew[(1, 0)]);
+
+            assert!((angular - jacobian.fixed_view::<3, 1>(3, i)).magnitude() < thresh);
+        }
+    }
 }
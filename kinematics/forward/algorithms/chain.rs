@@ -0,0 +1,281 @@
+use nalgebra::{DMatrix, Matrix3, Matrix6x5, Vector3};
+
+use crate::forward::algorithms::{
+    geometric_jacobian, limb_geometric_jacobian, ForwardKinematicAlgorithm, JointAxis, Limb,
+};
+use crate::model::chain::{ChainState, KinematicChain};
+use crate::model::{KinematicParameters, KinematicState, Pose};
+use crate::orientation::euler::EulerConvention;
+
+/// Forward kinematic approach for kinematic chains with an arbitrary number of joints, the
+///  joint frames are chained one after another.
+///
+/// As a `ForwardKinematicAlgorithm` it operates on the chain of the five-link arm, every limb
+///  ends at the next joint (the last one ends at the end-effector).
+#[derive(Default)]
+pub struct ChainForwardKinematicAlgorithm {}
+
+impl ChainForwardKinematicAlgorithm {
+    /// Compute the poses of the joint frames (before the motion of the joints themselves) in the
+    ///  base frame, missing joint values are taken to be zero.
+    pub fn joint_poses(&self, chain: &KinematicChain, state: &ChainState) -> Vec<Pose> {
+        self.poses(chain, state).0
+    }
+
+    /// Compute the pose of the end-effector in the base frame.
+    pub fn tool_pose(&self, chain: &KinematicChain, state: &ChainState) -> Pose {
+        self.poses(chain, state).1
+    }
+
+    /// Compute the geometric jacobian of the end-effector, the first three rows map the joint
+    ///  velocities to the linear velocity and the last three to the angular velocity, both
+    ///  expressed in the base frame.
+    pub fn tool_jacobian(&self, chain: &KinematicChain, state: &ChainState) -> DMatrix<f64> {
+        let (joints, tool) = self.poses(chain, state);
+
+        geometric_jacobian(&tool.position, &Self::joint_axes(chain, &joints))
+    }
+
+    /// Get the joints of the chain in the base frame, from the poses of their frames.
+    fn joint_axes(chain: &KinematicChain, poses: &[Pose]) -> Vec<JointAxis> {
+        chain
+            .joints
+            .iter()
+            .zip(poses.iter())
+            .map(|(joint, pose)| {
+                JointAxis::new(
+                    pose.position,
+                    pose.orientation * joint.axis.normalize(),
+                    joint.kind,
+                )
+            })
+            .collect()
+    }
+
+    /// Walk along the chain, computing the poses of the joint frames and the end-effector.
+    fn poses(&self, chain: &KinematicChain, state: &ChainState) -> (Vec<Pose>, Pose) {
+        let mut joints: Vec<Pose> = Vec::with_capacity(chain.dof());
+        let mut pose: Pose = Pose::identity();
+
+        for (i, joint) in chain.joints.iter().enumerate() {
+            pose = pose.compose(&joint.origin);
+            joints.push(pose.clone());
+
+            let value: f64 = state.values.get(i).copied().unwrap_or(0_f64);
+            pose = pose.compose(&joint.motion(value));
+        }
+
+        let tool: Pose = pose.compose(&chain.tool);
+
+        (joints, tool)
+    }
+
+    /// Compute the position at the end of the given limb of the five-link arm.
+    fn limb_end_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: usize,
+    ) -> Vector3<f64> {
+        let (joints, tool) = self.poses(&KinematicChain::from(params), &ChainState::from(state));
+
+        joints
+            .get(limb + 1_usize)
+            .map(|pose| pose.position)
+            .unwrap_or(tool.position)
+    }
+}
+
+impl ForwardKinematicAlgorithm for ChainForwardKinematicAlgorithm {
+    fn limb0_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(params, state, 0_usize)
+    }
+
+    fn limb1_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(params, state, 1_usize)
+    }
+
+    fn limb2_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(params, state, 2_usize)
+    }
+
+    fn limb3_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(params, state, 3_usize)
+    }
+
+    fn limb4_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(params, state, 4_usize)
+    }
+
+    fn limb4_euler_angles(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        convention: EulerConvention,
+    ) -> Vector3<f64> {
+        convention.euler_angles(&self.limb4_orientation_matrix(params, state))
+    }
+
+    fn limb4_orientation_matrix(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix3<f64> {
+        self.tool_pose(&KinematicChain::from(params), &ChainState::from(state))
+            .orientation
+    }
+
+    fn limb_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Matrix6x5<f64> {
+        let chain: KinematicChain = KinematicChain::from(params);
+        let poses: Vec<Pose> = self.joint_poses(&chain, &ChainState::from(state));
+
+        limb_geometric_jacobian(
+            &self.limb_position_vector(params, state, limb),
+            &Self::joint_axes(&chain, &poses),
+            limb,
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{DMatrix, DVector, Vector3};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::chain::ChainForwardKinematicAlgorithm;
+    use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+    use crate::model::chain::{ChainJoint, ChainState, KinematicChain};
+    use crate::model::{KinematicParameters, KinematicState, Pose};
+
+    #[test]
+    pub fn matches_analytical() {
+        let params: KinematicParameters = KinematicParameters {
+            l_0: 4_f64,
+            l_1: 9_f64,
+            l_2: 7_f64,
+            l_3: 3_f64,
+            l_4: 2_f64,
+            ..KinematicParameters::default()
+        };
+
+        let analytical: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let chain: ChainForwardKinematicAlgorithm = ChainForwardKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        let state: KinematicState = KinematicState {
+            theta_0: -2.1_f64,
+            theta_1: 1.2_f64,
+            theta_2: -0.6_f64,
+            theta_3: 0.9_f64,
+            theta_4: 0.7_f64,
+        };
+
+        assert!(
+            (analytical.limb2_position_vector(&params, &state)
+                - chain.limb2_position_vector(&params, &state))
+            .magnitude()
+                < thresh
+        );
+        assert!(
+            (analytical.limb3_position_vector(&params, &state)
+                - chain.limb3_position_vector(&params, &state))
+            .magnitude()
+                < thresh
+        );
+        assert!(
+            (analytical.limb4_position_vector(&params, &state)
+                - chain.limb4_position_vector(&params, &state))
+            .magnitude()
+                < thresh
+        );
+        assert!(
+            (analytical.limb4_orientation_matrix(&params, &state)
+                - chain.limb4_orientation_matrix(&params, &state))
+            .norm()
+                < thresh
+        );
+
+        for limb in Limb::ALL {
+            assert!(
+                (analytical.limb_jacobian(&params, &state, limb)
+                    - chain.limb_jacobian(&params, &state, limb))
+                .norm()
+                    < thresh
+            );
+        }
+    }
+
+    #[test]
+    pub fn jacobian_matches_finite_differences() {
+        // A chain that mixes revolute and prismatic joints.
+        let chain: KinematicChain = KinematicChain::new(
+            vec![
+                ChainJoint::revolute(Vector3::<f64>::zeros(), Vector3::<f64>::y()),
+                ChainJoint::prismatic(
+                    Vector3::<f64>::new(0_f64, 5_f64, 0_f64),
+                    Vector3::<f64>::new(1_f64, 1_f64, 0_f64),
+                ),
+                ChainJoint::revolute(
+                    Vector3::<f64>::new(0_f64, 3_f64, 0_f64),
+                    Vector3::<f64>::x(),
+                ),
+                ChainJoint::revolute(
+                    Vector3::<f64>::new(0_f64, 2_f64, 0_f64),
+                    Vector3::<f64>::z(),
+                ),
+            ],
+            Pose::from_position(Vector3::<f64>::new(1_f64, 2_f64, 0_f64)),
+        );
+        let state: ChainState = ChainState::new(DVector::<f64>::from_vec(vec![
+            0.3_f64, 1.5_f64, -0.4_f64, 0.8_f64,
+        ]));
+
+        let fk_solver: ChainForwardKinematicAlgorithm = ChainForwardKinematicAlgorithm::default();
+        let jacobian: DMatrix<f64> = fk_solver.tool_jacobian(&chain, &state);
+
+        let eps: f64 = 10_f64.powf(-6_f64);
+
+        // Compare the linear part with central differences of the end-effector position.
+        for i in 0..chain.dof() {
+            let mut forward: ChainState = state.clone();
+            let mut backward: ChainState = state.clone();
+            forward.values[i] += eps;
+            backward.values[i] -= eps;
+
+            let derivative: Vector3<f64> = (fk_solver.tool_pose(&chain, &forward).position
+                - fk_solver.tool_pose(&chain, &backward).position)
+                / (2_f64 * eps);
+
+            assert!(
+                (derivative - jacobian.fixed_view::<3, 1>(0, i)).magnitude() < 10_f64.powf(-6_f64)
+            );
+        }
+    }
+}
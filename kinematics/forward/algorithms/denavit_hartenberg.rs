@@ -0,0 +1,708 @@
+use std::f64::consts::FRAC_PI_2;
+
+use nalgebra::{Matrix3, Matrix4, Matrix6x5, Vector3, Vector5};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::forward::algorithms::{
+    limb_geometric_jacobian, ForwardKinematicAlgorithm, JointAxis, Limb,
+};
+use crate::model::chain::JointKind;
+use crate::model::{KinematicParameters, KinematicState};
+use crate::orientation::euler::EulerConvention;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum DenavitHartenbergError {
+    #[error("The table has {0} joints, but a kinematic state only drives five")]
+    TooManyJoints(usize),
+}
+
+/// The convention in which the links of a Denavit-Hartenberg table are given.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DenavitHartenbergConvention {
+    /// The classic convention, every link is `Rz(theta) Tz(d) Tx(a) Rx(alpha)` and the joint
+    ///  rotates around the z-axis of the frame before the link.
+    Standard,
+    /// The modified (Craig) convention, every link is `Rx(alpha) Tx(a) Rz(theta) Tz(d)` and the
+    ///  joint rotates around the z-axis of the frame after the link.
+    Modified,
+}
+
+/// The kind of joint a link of a Denavit-Hartenberg table is driven by.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DenavitHartenbergJoint {
+    /// The joint angle is added to the theta of the link, every revolute link takes the next
+    ///  value of the state.
+    Revolute,
+    /// The joint distance is added to the d of the link, every prismatic link takes the next
+    ///  value of the state.
+    Prismatic,
+    /// The link does not move, which allows intermediate frames to be placed.
+    Fixed,
+}
+
+/// A single row of a Denavit-Hartenberg table.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct DenavitHartenbergLink {
+    /// The length of the common normal.
+    pub a: f64,
+    /// The twist around the common normal.
+    pub alpha: f64,
+    /// The offset along the joint axis.
+    pub d: f64,
+    /// The angle around the joint axis, for revolute links the offset of the joint angle.
+    pub theta: f64,
+    pub joint: DenavitHartenbergJoint,
+}
+
+impl DenavitHartenbergLink {
+    pub fn new(a: f64, alpha: f64, d: f64, theta: f64, joint: DenavitHartenbergJoint) -> Self {
+        Self {
+            a,
+            alpha,
+            d,
+            theta,
+            joint,
+        }
+    }
+
+    /// Create a link driven by a revolute joint, with the given offset of the joint angle.
+    pub fn revolute(a: f64, alpha: f64, d: f64, theta_offset: f64) -> Self {
+        Self::new(a, alpha, d, theta_offset, DenavitHartenbergJoint::Revolute)
+    }
+
+    /// Create a link driven by a prismatic joint, with the given offset of the joint distance.
+    pub fn prismatic(a: f64, alpha: f64, d_offset: f64, theta: f64) -> Self {
+        Self::new(a, alpha, d_offset, theta, DenavitHartenbergJoint::Prismatic)
+    }
+
+    /// Create a link that does not move.
+    pub fn fixed(a: f64, alpha: f64, d: f64, theta: f64) -> Self {
+        Self::new(a, alpha, d, theta, DenavitHartenbergJoint::Fixed)
+    }
+
+    /// Compute the part of the link transform in front of the joint axis, the frame it ends in is
+    ///  the one the joint rotates in.
+    fn pre_joint_transform(&self, convention: DenavitHartenbergConvention) -> Matrix4<f64> {
+        match convention {
+            DenavitHartenbergConvention::Standard => Matrix4::<f64>::identity(),
+            DenavitHartenbergConvention::Modified => {
+                rotation_x(self.alpha) * translation(&Vector3::<f64>::new(self.a, 0_f64, 0_f64))
+            }
+        }
+    }
+
+    /// Compute the part of the link transform behind the joint axis, for the given joint value.
+    fn post_joint_transform(
+        &self,
+        convention: DenavitHartenbergConvention,
+        value: f64,
+    ) -> Matrix4<f64> {
+        let (angle, distance) = match self.joint {
+            DenavitHartenbergJoint::Revolute => (value, 0_f64),
+            DenavitHartenbergJoint::Prismatic => (0_f64, value),
+            DenavitHartenbergJoint::Fixed => (0_f64, 0_f64),
+        };
+
+        let joint: Matrix4<f64> = rotation_z(self.theta + angle)
+            * translation(&Vector3::<f64>::new(0_f64, 0_f64, self.d + distance));
+
+        match convention {
+            DenavitHartenbergConvention::Standard => {
+                joint
+                    * translation(&Vector3::<f64>::new(self.a, 0_f64, 0_f64))
+                    * rotation_x(self.alpha)
+            }
+            DenavitHartenbergConvention::Modified => joint,
+        }
+    }
+}
+
+/// Forward kinematic approach driven by a Denavit-Hartenberg table, so the geometry of an arm
+///  is described by its table instead of hand-derived equations.
+///
+/// Every revolute or prismatic link takes the next value of the state, and every limb ends where
+///  the joint of the next limb moves (the last one ends at the tool). The geometry is entirely
+///  described by the table, so the kinematic parameters are not used.
+///
+/// The table is only built through `new` (deserializing goes through it as well), so it never
+///  has more joints than a kinematic state drives.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "DenavitHartenbergTable")]
+pub struct DenavitHartenbergForwardKinematicAlgorithm {
+    convention: DenavitHartenbergConvention,
+    /// The transform from the base frame (with the y-axis pointing up) to the first frame of the
+    ///  table.
+    base: Matrix4<f64>,
+    links: Vec<DenavitHartenbergLink>,
+    /// The transform from the last frame of the table to the end-effector.
+    tool: Matrix4<f64>,
+}
+
+/// The serialized form of a table, which is validated by `new` when deserializing.
+#[derive(Deserialize)]
+struct DenavitHartenbergTable {
+    convention: DenavitHartenbergConvention,
+    base: Matrix4<f64>,
+    links: Vec<DenavitHartenbergLink>,
+    tool: Matrix4<f64>,
+}
+
+impl TryFrom<DenavitHartenbergTable> for DenavitHartenbergForwardKinematicAlgorithm {
+    type Error = DenavitHartenbergError;
+
+    fn try_from(table: DenavitHartenbergTable) -> Result<Self, Self::Error> {
+        Self::new(table.convention, table.base, table.links, table.tool)
+    }
+}
+
+impl DenavitHartenbergForwardKinematicAlgorithm {
+    /// Create an approach from the given table, fails when the table has more revolute and
+    ///  prismatic links than the five joints of a kinematic state (which would silently be left
+    ///  at zero).
+    pub fn new(
+        convention: DenavitHartenbergConvention,
+        base: Matrix4<f64>,
+        links: Vec<DenavitHartenbergLink>,
+        tool: Matrix4<f64>,
+    ) -> Result<Self, DenavitHartenbergError> {
+        let algorithm: Self = Self {
+            convention,
+            base,
+            links,
+            tool,
+        };
+
+        if algorithm.joint_count() > 5_usize {
+            return Err(DenavitHartenbergError::TooManyJoints(
+                algorithm.joint_count(),
+            ));
+        }
+
+        Ok(algorithm)
+    }
+
+    /// Create the table of the five-link arm with the given parameters, which results in the
+    ///  same poses as the analytical approach.
+    ///
+    /// The wrist rolls around the direction of the last two links, so a fixed link is inserted to
+    ///  place the end of the fourth limb on the roll axis.
+    pub fn from_parameters(
+        &KinematicParameters {
+            l_0,
+            l_1,
+            l_2,
+            l_3,
+            l_4,
+            ..
+        }: &KinematicParameters,
+    ) -> Self {
+        Self {
+            convention: DenavitHartenbergConvention::Standard,
+            base: homogeneous(
+                &Matrix3::<f64>::new(
+                    0_f64, -1_f64, 0_f64, //
+                    0_f64, 0_f64, -1_f64, //
+                    1_f64, 0_f64, 0_f64,
+                ),
+                &Vector3::<f64>::zeros(),
+            ),
+            links: vec![
+                DenavitHartenbergLink::revolute(0_f64, -FRAC_PI_2, -l_0, 0_f64),
+                DenavitHartenbergLink::revolute(l_1, 0_f64, 0_f64, FRAC_PI_2),
+                DenavitHartenbergLink::revolute(l_2, 0_f64, 0_f64, 0_f64),
+                DenavitHartenbergLink::revolute(0_f64, -FRAC_PI_2, 0_f64, FRAC_PI_2),
+                DenavitHartenbergLink::fixed(0_f64, 0_f64, -l_3, 0_f64),
+                DenavitHartenbergLink::revolute(0_f64, 0_f64, -l_4, 0_f64),
+            ],
+            tool: homogeneous(
+                &Matrix3::<f64>::new(
+                    0_f64, 0_f64, -1_f64, //
+                    1_f64, 0_f64, 0_f64, //
+                    0_f64, -1_f64, 0_f64,
+                ),
+                &Vector3::<f64>::zeros(),
+            ),
+        }
+    }
+
+    pub fn convention(&self) -> DenavitHartenbergConvention {
+        self.convention
+    }
+
+    pub fn base(&self) -> &Matrix4<f64> {
+        &self.base
+    }
+
+    pub fn links(&self) -> &[DenavitHartenbergLink] {
+        &self.links
+    }
+
+    pub fn tool(&self) -> &Matrix4<f64> {
+        &self.tool
+    }
+
+    /// Get the number of joints that drive the table.
+    pub fn joint_count(&self) -> usize {
+        self.joints().count()
+    }
+
+    /// Compute the frames in which the joints move (along or around their z-axis), expressed in
+    ///  the base frame. Missing joint values are taken to be zero.
+    pub fn joint_transforms(&self, values: &[f64]) -> Vec<Matrix4<f64>> {
+        self.chain(values).0
+    }
+
+    /// Compute the transform of the end-effector, expressed in the base frame. Missing joint
+    ///  values are taken to be zero.
+    pub fn tool_transform(&self, values: &[f64]) -> Matrix4<f64> {
+        self.chain(values).1
+    }
+
+    /// Get the kinds of the joints that drive the table, in order.
+    fn joints(&self) -> impl Iterator<Item = DenavitHartenbergJoint> + '_ {
+        self.links
+            .iter()
+            .map(|link| link.joint)
+            .filter(|joint| *joint != DenavitHartenbergJoint::Fixed)
+    }
+
+    /// Walk along the table, computing the frames of the joints and the end-effector.
+    fn chain(&self, values: &[f64]) -> (Vec<Matrix4<f64>>, Matrix4<f64>) {
+        let mut joints: Vec<Matrix4<f64>> = Vec::new();
+        let mut transform: Matrix4<f64> = self.base;
+
+        for link in self.links.iter() {
+            transform *= link.pre_joint_transform(self.convention);
+
+            let value: f64 = match link.joint {
+                DenavitHartenbergJoint::Revolute | DenavitHartenbergJoint::Prismatic => {
+                    let value: f64 = values.get(joints.len()).copied().unwrap_or(0_f64);
+                    joints.push(transform);
+                    value
+                }
+                DenavitHartenbergJoint::Fixed => 0_f64,
+            };
+
+            transform *= link.post_joint_transform(self.convention, value);
+        }
+
+        (joints, transform * self.tool)
+    }
+
+    /// Compute the transform at the end of the given limb, which is the frame of the next joint,
+    ///  or the end-effector for the last limb.
+    fn limb_transform(&self, state: &KinematicState, limb: usize) -> Matrix4<f64> {
+        let angles: Vector5<f64> = Vector5::<f64>::from(state);
+        let (joints, tool) = self.chain(angles.as_slice());
+
+        joints.get(limb + 1_usize).copied().unwrap_or(tool)
+    }
+
+    /// Compute the position at the end of the given limb.
+    fn limb_end_vector(&self, state: &KinematicState, limb: usize) -> Vector3<f64> {
+        self.limb_transform(state, limb)
+            .fixed_view::<3, 1>(0, 3)
+            .into()
+    }
+}
+
+impl ForwardKinematicAlgorithm for DenavitHartenbergForwardKinematicAlgorithm {
+    fn limb0_position_vector(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(state, 0_usize)
+    }
+
+    fn limb1_position_vector(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(state, 1_usize)
+    }
+
+    fn limb2_position_vector(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(state, 2_usize)
+    }
+
+    fn limb3_position_vector(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(state, 3_usize)
+    }
+
+    fn limb4_position_vector(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb_end_vector(state, 4_usize)
+    }
+
+    fn limb4_euler_angles(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        convention: EulerConvention,
+    ) -> Vector3<f64> {
+        convention.euler_angles(&self.limb4_orientation_matrix(params, state))
+    }
+
+    fn limb4_orientation_matrix(
+        &self,
+        _params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix3<f64> {
+        self.limb_transform(state, 4_usize)
+            .fixed_view::<3, 3>(0, 0)
+            .into()
+    }
+
+    /// The table has at most five joints (see `new`).
+    fn limb_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Matrix6x5<f64> {
+        let values: Vector5<f64> = Vector5::<f64>::from(state);
+
+        // Every joint moves along or around the z-axis of its frame.
+        let joints: Vec<JointAxis> = self
+            .joint_transforms(values.as_slice())
+            .iter()
+            .zip(self.joints())
+            .map(|(transform, joint)| {
+                JointAxis::new(
+                    transform.fixed_view::<3, 1>(0, 3).into(),
+                    transform.fixed_view::<3, 1>(0, 2).into(),
+                    match joint {
+                        DenavitHartenbergJoint::Prismatic => JointKind::Prismatic,
+                        _ => JointKind::Revolute,
+                    },
+                )
+            })
+            .collect();
+
+        limb_geometric_jacobian(
+            &self.limb_position_vector(params, state, limb),
+            &joints,
+            limb,
+        )
+    }
+}
+
+/// Build a homogeneous transform from the given rotation and translation.
+fn homogeneous(rotation: &Matrix3<f64>, translation: &Vector3<f64>) -> Matrix4<f64> {
+    let mut transform: Matrix4<f64> = Matrix4::<f64>::identity();
+
+    transform.fixed_view_mut::<3, 3>(0, 0).copy_from(rotation);
+    transform
+        .fixed_view_mut::<3, 1>(0, 3)
+        .copy_from(translation);
+
+    transform
+}
+
+/// Build a homogeneous transform that translates by the given vector.
+fn translation(vector: &Vector3<f64>) -> Matrix4<f64> {
+    homogeneous(&Matrix3::<f64>::identity(), vector)
+}
+
+/// Build a homogeneous transform that rotates around the x-axis by the given angle.
+fn rotation_x(angle: f64) -> Matrix4<f64> {
+    homogeneous(
+        &Matrix3::<f64>::new(
+            1_f64,
+            0_f64,
+            0_f64,
+            0_f64,
+            angle.cos(),
+            -angle.sin(),
+            0_f64,
+            angle.sin(),
+            angle.cos(),
+        ),
+        &Vector3::<f64>::zeros(),
+    )
+}
+
+/// Build a homogeneous transform that rotates around the z-axis by the given angle.
+fn rotation_z(angle: f64) -> Matrix4<f64> {
+    homogeneous(
+        &Matrix3::<f64>::new(
+            angle.cos(),
+            -angle.sin(),
+            0_f64,
+            angle.sin(),
+            angle.cos(),
+            0_f64,
+            0_f64,
+            0_f64,
+            1_f64,
+        ),
+        &Vector3::<f64>::zeros(),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use nalgebra::{Matrix4, Vector3};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::{
+        DenavitHartenbergConvention, DenavitHartenbergError,
+        DenavitHartenbergForwardKinematicAlgorithm, DenavitHartenbergLink,
+    };
+    use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+    use crate::model::{KinematicParameters, KinematicState};
+    use crate::verification::CrossVerifier;
+
+    #[test]
+    pub fn matches_analytical() {
+        let params: KinematicParameters = KinematicParameters {
+            l_0: 4_f64,
+            l_1: 9_f64,
+            l_2: 7_f64,
+            l_3: 3_f64,
+            l_4: 2_f64,
+            ..KinematicParameters::default()
+        };
+
+        let analytical: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&params);
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        for state in [
+            KinematicState::default(),
+            KinematicState {
+                theta_0: 0.4_f64,
+                theta_1: -0.3_f64,
+                theta_2: 0.8_f64,
+                theta_3: 0.2_f64,
+                theta_4: -1.1_f64,
+            },
+            KinematicState {
+                theta_0: -2.1_f64,
+                theta_1: 1.2_f64,
+                theta_2: -0.6_f64,
+                theta_3: 0.9_f64,
+                theta_4: 0.7_f64,
+            },
+        ] {
+            // Every limb, and the orientation of the end-effector, must match.
+            for (a, b) in [
+                (
+                    analytical.limb0_position_vector(&params, &state),
+                    table.limb0_position_vector(&params, &state),
+                ),
+                (
+                    analytical.limb1_position_vector(&params, &state),
+                    table.limb1_position_vector(&params, &state),
+                ),
+                (
+                    analytical.limb2_position_vector(&params, &state),
+                    table.limb2_position_vector(&params, &state),
+                ),
+                (
+                    analytical.limb3_position_vector(&params, &state),
+                    table.limb3_position_vector(&params, &state),
+                ),
+                (
+                    analytical.limb4_position_vector(&params, &state),
+                    table.limb4_position_vector(&params, &state),
+                ),
+            ] {
+                assert!((a - b).magnitude() < thresh);
+            }
+
+            assert!(
+                (analytical.limb4_orientation_matrix(&params, &state)
+                    - table.limb4_orientation_matrix(&params, &state))
+                .norm()
+                    < thresh
+            );
+
+            // And so must the jacobians of all the limbs.
+            for limb in Limb::ALL {
+                assert!(
+                    (analytical.limb_jacobian(&params, &state, limb)
+                        - table.limb_jacobian(&params, &state, limb))
+                    .norm()
+                        < thresh
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn conventions_agree_on_planar_arm() {
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        // A planar arm with two links, rotating around the z-axis.
+        let standard: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::new(
+                DenavitHartenbergConvention::Standard,
+                Matrix4::<f64>::identity(),
+                vec![
+                    DenavitHartenbergLink::revolute(3_f64, 0_f64, 0_f64, 0_f64),
+                    DenavitHartenbergLink::revolute(2_f64, 0_f64, 0_f64, 0_f64),
+                ],
+                Matrix4::<f64>::identity(),
+            )
+            .unwrap();
+
+        // In the modified convention the link lengths move one row down, and the last one ends
+        //  up in the tool transform.
+        let modified: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::new(
+                DenavitHartenbergConvention::Modified,
+                Matrix4::<f64>::identity(),
+                vec![
+                    DenavitHartenbergLink::revolute(0_f64, 0_f64, 0_f64, 0_f64),
+                    DenavitHartenbergLink::revolute(3_f64, 0_f64, 0_f64, 0_f64),
+                ],
+                Matrix4::<f64>::new_translation(&Vector3::<f64>::new(2_f64, 0_f64, 0_f64)),
+            )
+            .unwrap();
+
+        let angles: [f64; 2] = [0.3_f64, -0.8_f64];
+        let expected: Vector3<f64> = Vector3::<f64>::new(
+            3_f64 * angles[0].cos() + 2_f64 * (angles[0] + angles[1]).cos(),
+            3_f64 * angles[0].sin() + 2_f64 * (angles[0] + angles[1]).sin(),
+            0_f64,
+        );
+
+        for algorithm in [&standard, &modified] {
+            assert_eq!(algorithm.joint_count(), 2_usize);
+
+            // The second joint rotates at the end of the first link.
+            let joints: Vec<Matrix4<f64>> = algorithm.joint_transforms(&angles);
+            assert!(
+                (Vector3::<f64>::from(joints[1].fixed_view::<3, 1>(0, 3))
+                    - Vector3::<f64>::new(3_f64 * angles[0].cos(), 3_f64 * angles[0].sin(), 0_f64))
+                .magnitude()
+                    < thresh
+            );
+
+            let tool: Matrix4<f64> = algorithm.tool_transform(&angles);
+            assert!(
+                (Vector3::<f64>::from(tool.fixed_view::<3, 1>(0, 3)) - expected).magnitude()
+                    < thresh
+            );
+        }
+    }
+
+    #[test]
+    pub fn rejects_too_many_joints() {
+        let links: Vec<DenavitHartenbergLink> = (0..6)
+            .map(|_| DenavitHartenbergLink::revolute(1_f64, 0_f64, 0_f64, 0_f64))
+            .collect();
+
+        assert_eq!(
+            DenavitHartenbergForwardKinematicAlgorithm::new(
+                DenavitHartenbergConvention::Standard,
+                Matrix4::<f64>::identity(),
+                links.clone(),
+                Matrix4::<f64>::identity(),
+            ),
+            Err(DenavitHartenbergError::TooManyJoints(6_usize))
+        );
+
+        // Fixed links do not count as joints.
+        let mut fixed: Vec<DenavitHartenbergLink> = links[1..].to_vec();
+        fixed.push(DenavitHartenbergLink::fixed(1_f64, 0_f64, 0_f64, 0_f64));
+
+        assert!(DenavitHartenbergForwardKinematicAlgorithm::new(
+            DenavitHartenbergConvention::Standard,
+            Matrix4::<f64>::identity(),
+            fixed,
+            Matrix4::<f64>::identity(),
+        )
+        .is_ok());
+    }
+
+    #[test]
+    pub fn prismatic_joints() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // A prismatic joint slides the tool along the z-axis of its frame.
+        let slide: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::new(
+                DenavitHartenbergConvention::Standard,
+                Matrix4::<f64>::identity(),
+                vec![DenavitHartenbergLink::prismatic(0_f64, 0_f64, 1_f64, 0_f64)],
+                Matrix4::<f64>::identity(),
+            )
+            .unwrap();
+
+        assert!(
+            (Vector3::<f64>::from(slide.tool_transform(&[0.5_f64]).fixed_view::<3, 1>(0, 3))
+                - Vector3::<f64>::new(0_f64, 0_f64, 1.5_f64))
+            .magnitude()
+                < 10_f64.powf(-12_f64)
+        );
+
+        // The jacobian of a table that mixes both kinds of joints matches the numerical one.
+        let mixed: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::new(
+                DenavitHartenbergConvention::Standard,
+                Matrix4::<f64>::identity(),
+                vec![
+                    DenavitHartenbergLink::revolute(0_f64, -FRAC_PI_2, 4_f64, 0_f64),
+                    DenavitHartenbergLink::revolute(6_f64, 0_f64, 0_f64, 0_f64),
+                    DenavitHartenbergLink::prismatic(0_f64, FRAC_PI_2, 1_f64, 0_f64),
+                    DenavitHartenbergLink::revolute(0_f64, -FRAC_PI_2, 0_f64, 0_f64),
+                    DenavitHartenbergLink::revolute(0_f64, 0_f64, 2_f64, 0_f64),
+                ],
+                Matrix4::<f64>::identity(),
+            )
+            .unwrap();
+
+        assert!(
+            CrossVerifier::new(20_usize, 3_u64)
+                .verify_jacobian(&mixed, &params)
+                .max_error()
+                < 10_f64.powf(-5_f64)
+        );
+    }
+
+    #[test]
+    pub fn deserializes_through_validation() {
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(
+                &KinematicParameters::default(),
+            );
+
+        let json: String = serde_json::to_string(&table).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DenavitHartenbergForwardKinematicAlgorithm>(&json).unwrap(),
+            table
+        );
+
+        // A table with more joints than a state drives is refused, like `new` does.
+        let mut value: serde_json::Value = serde_json::to_value(&table).unwrap();
+        value["links"].as_array_mut().unwrap().push(
+            serde_json::to_value(DenavitHartenbergLink::revolute(1_f64, 0_f64, 0_f64, 0_f64))
+                .unwrap(),
+        );
+
+        assert!(
+            serde_json::from_value::<DenavitHartenbergForwardKinematicAlgorithm>(value).is_err()
+        );
+    }
+}
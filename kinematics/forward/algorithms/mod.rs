@@ -1,8 +1,46 @@
-use nalgebra::{Matrix3, Vector3};
+use nalgebra::{DMatrix, Matrix3, Matrix6x5, Vector3};
+use serde::{Deserialize, Serialize};
 
-use crate::model::{KinematicParameters, KinematicState};
+use crate::model::chain::JointKind;
+use crate::model::{KinematicParameters, KinematicState, Pose};
+use crate::orientation::euler::EulerConvention;
 
 pub mod analytical;
+pub mod chain;
+pub mod denavit_hartenberg;
+
+/// A limb of the arm, every limb ends at the joint of the next one and the last one ends at the
+///  end-effector.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Limb {
+    Limb0,
+    Limb1,
+    Limb2,
+    Limb3,
+    Limb4,
+}
+
+impl Limb {
+    /// All the limbs, from the base towards the end-effector.
+    pub const ALL: [Limb; 5] = [
+        Limb::Limb0,
+        Limb::Limb1,
+        Limb::Limb2,
+        Limb::Limb3,
+        Limb::Limb4,
+    ];
+
+    /// Get the index of the limb, which is also the index of the joint that drives it.
+    pub fn index(&self) -> usize {
+        match self {
+            Limb::Limb0 => 0_usize,
+            Limb::Limb1 => 1_usize,
+            Limb::Limb2 => 2_usize,
+            Limb::Limb3 => 3_usize,
+            Limb::Limb4 => 4_usize,
+        }
+    }
+}
 
 pub trait ForwardKinematicAlgorithm {
     /// Compute the end-effector position of the first limb.
@@ -40,11 +78,13 @@ pub trait ForwardKinematicAlgorithm {
         state: &KinematicState,
     ) -> Vector3<f64>;
 
-    /// Compute the vector of euler angles for the end-effector of the fourth limb.
+    /// Compute the vector of euler angles for the end-effector of the fourth limb, in the given
+    ///  convention.
     fn limb4_euler_angles(
         &self,
         params: &KinematicParameters,
         state: &KinematicState,
+        convention: EulerConvention,
     ) -> Vector3<f64>;
 
     /// Compute the orientation matrix of the end-effector of the fourth limb.
@@ -53,4 +93,161 @@ pub trait ForwardKinematicAlgorithm {
         params: &KinematicParameters,
         state: &KinematicState,
     ) -> Matrix3<f64>;
+
+    /// Compute the geometric jacobian of the end of the given limb, the first three rows map the
+    ///  joint velocities onto its linear velocity and the last three onto its angular velocity,
+    ///  both expressed in the base frame. The columns of the joints past the limb are zero.
+    ///
+    /// It has a column for each of the five joints a kinematic state drives, which is why its size
+    ///  is fixed. Chains with another number of joints get their jacobian from
+    ///  `ChainForwardKinematicAlgorithm::tool_jacobian`, both are built by `geometric_jacobian`.
+    fn limb_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Matrix6x5<f64>;
+
+    /// Compute the geometric jacobian of the end-effector of the fourth limb.
+    fn limb4_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix6x5<f64> {
+        self.limb_jacobian(params, state, Limb::Limb4)
+    }
+
+    /// Compute the end position of the given limb.
+    fn limb_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Vector3<f64> {
+        match limb {
+            Limb::Limb0 => self.limb0_position_vector(params, state),
+            Limb::Limb1 => self.limb1_position_vector(params, state),
+            Limb::Limb2 => self.limb2_position_vector(params, state),
+            Limb::Limb3 => self.limb3_position_vector(params, state),
+            Limb::Limb4 => self.limb4_position_vector(params, state),
+        }
+    }
+
+    /// Compute the pose (position and orientation) of the end-effector of the fourth limb.
+    fn limb4_pose(&self, params: &KinematicParameters, state: &KinematicState) -> Pose {
+        Pose::new(
+            self.limb4_position_vector(params, state),
+            self.limb4_orientation_matrix(params, state),
+        )
+    }
+}
+
+/// A joint that moves a point, expressed in the base frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct JointAxis {
+    /// The position of the joint.
+    pub origin: Vector3<f64>,
+    /// The (unit) axis the joint moves along or around.
+    pub axis: Vector3<f64>,
+    pub kind: JointKind,
+}
+
+impl JointAxis {
+    pub fn new(origin: Vector3<f64>, axis: Vector3<f64>, kind: JointKind) -> Self {
+        Self { origin, axis, kind }
+    }
+
+    /// Create a joint that rotates around the given axis.
+    pub fn revolute(origin: Vector3<f64>, axis: Vector3<f64>) -> Self {
+        Self::new(origin, axis, JointKind::Revolute)
+    }
+}
+
+/// Compute the geometric jacobian of the given point, which has a column for every given joint.
+///  The first three rows map the joint velocities onto the linear velocity of the point and the
+///  last three onto its angular velocity, both expressed in the base frame.
+pub fn geometric_jacobian(point: &Vector3<f64>, joints: &[JointAxis]) -> DMatrix<f64> {
+    let mut jacobian: DMatrix<f64> = DMatrix::<f64>::zeros(6, joints.len());
+
+    for (i, joint) in joints.iter().enumerate() {
+        let (linear, angular) = match joint.kind {
+            JointKind::Revolute => (joint.axis.cross(&(point - joint.origin)), joint.axis),
+            JointKind::Prismatic => (joint.axis, Vector3::<f64>::zeros()),
+        };
+
+        jacobian.fixed_view_mut::<3, 1>(0, i).copy_from(&linear);
+        jacobian.fixed_view_mut::<3, 1>(3, i).copy_from(&angular);
+    }
+
+    jacobian
+}
+
+/// Compute the geometric jacobian of a point at the end of the given limb, from the (at most
+///  five) joints of the arm. The columns of the joints past the limb are zero.
+pub(crate) fn limb_geometric_jacobian(
+    point: &Vector3<f64>,
+    joints: &[JointAxis],
+    limb: Limb,
+) -> Matrix6x5<f64> {
+    let joints: &[JointAxis] = &joints[..joints.len().min(limb.index() + 1_usize)];
+    let mut jacobian: Matrix6x5<f64> = Matrix6x5::<f64>::zeros();
+
+    jacobian
+        .columns_mut(0, joints.len())
+        .copy_from(&geometric_jacobian(point, joints));
+
+    jacobian
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{DMatrix, Vector3};
+
+    use crate::forward::algorithms::{
+        geometric_jacobian, limb_geometric_jacobian, JointAxis, Limb,
+    };
+    use crate::model::chain::JointKind;
+
+    #[test]
+    pub fn jacobian_of_both_joint_kinds() {
+        let point: Vector3<f64> = Vector3::<f64>::new(2_f64, 0_f64, 0_f64);
+        let joints: [JointAxis; 3] = [
+            JointAxis::revolute(Vector3::<f64>::zeros(), Vector3::<f64>::z()),
+            JointAxis::new(
+                Vector3::<f64>::zeros(),
+                Vector3::<f64>::y(),
+                JointKind::Prismatic,
+            ),
+            JointAxis::revolute(
+                Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+                Vector3::<f64>::y(),
+            ),
+        ];
+
+        // Rotating around the z-axis moves the point along the y-axis, sliding along the y-axis
+        //  moves it along the y-axis without rotating it.
+        let jacobian: DMatrix<f64> = geometric_jacobian(&point, &joints);
+        assert_eq!(jacobian.ncols(), 3_usize);
+        assert_eq!(
+            jacobian.column(0).as_slice(),
+            &[0_f64, 2_f64, 0_f64, 0_f64, 0_f64, 1_f64]
+        );
+        assert_eq!(
+            jacobian.column(1).as_slice(),
+            &[0_f64, 1_f64, 0_f64, 0_f64, 0_f64, 0_f64]
+        );
+        assert_eq!(
+            jacobian.column(2).as_slice(),
+            &[0_f64, 0_f64, -1_f64, 0_f64, 1_f64, 0_f64]
+        );
+
+        // The jacobian of a limb only has the columns of the joints up to the limb.
+        let limb: DMatrix<f64> = DMatrix::<f64>::from_column_slice(
+            6,
+            5,
+            limb_geometric_jacobian(&point, &joints, Limb::Limb1).as_slice(),
+        );
+        assert_eq!(limb.columns(0, 2), jacobian.columns(0, 2));
+        assert!(limb.columns(2, 3).iter().all(|value| *value == 0_f64));
+    }
 }
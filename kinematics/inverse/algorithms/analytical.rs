@@ -0,0 +1,629 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Matrix3, Rotation3, Vector3, Vector5};
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{InverseKinematicAlgorithm, InverseKinematicError, PoseWeights};
+use crate::model::{KinematicParameters, KinematicState, Pose};
+
+/// The horizontal distance below which a target is considered to lie on the base axis, in which
+///  case the base angle is free and zero is chosen.
+const BASE_AXIS_EPS: f64 = 1e-12_f64;
+
+/// The tolerance on the cosine of the elbow angle, below which both elbow branches coincide.
+const ELBOW_EPS: f64 = 1e-12_f64;
+
+/// The pose difference above which the given forward kinematics are considered to describe
+///  another arm than the parameters.
+const MODEL_EPS: f64 = 1e-9_f64;
+
+/// The elbow branch of a solution, the elbow is up when it lies above the line between the
+///  second joint and the wrist (the fourth joint).
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ElbowBranch {
+    Up,
+    Down,
+}
+
+/// The base branch of a solution, when flipped the base is rotated half a turn and the arm
+///  reaches backwards over the base.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BaseBranch {
+    Normal,
+    Flipped,
+}
+
+/// A single solution of the analytical inverse kinematics, together with the branch it lies on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnalyticalInverseKinematicSolution {
+    pub state: KinematicState,
+    pub elbow: ElbowBranch,
+    pub base: BaseBranch,
+    /// Whether all the angles of the state lie within the joint limits.
+    pub within_limits: bool,
+}
+
+/// Closed-form inverse kinematic approach, which solves the base angle from the horizontal
+///  direction of the target and the pitch chain as a planar two-link problem towards the wrist.
+///
+/// The approach pitch is the angle between the tool axis and the vertical axis, positive when
+///  the tool leans away from the base axis towards the target.
+///
+/// The closed form only describes the arm of the parameters, so the steps refuse forward
+///  kinematics of any other arm (such as a table or chain with other dimensions) with
+///  `ModelMismatch`, those have to be solved with an iterative algorithm instead.
+#[derive(Default)]
+pub struct AnalyticalInverseKinematicAlgorithm {}
+
+impl AnalyticalInverseKinematicAlgorithm {
+    /// Compute all the states in which the end-effector of the fourth limb is at the given
+    ///  position with the given approach pitch and roll, ordered by base and then elbow branch.
+    ///
+    /// Every solution results in the same end-effector pose, which is why the roll of the flipped
+    ///  base solutions is shifted by half a turn. Solutions outside of the joint limits are
+    ///  included as well, they are marked as such.
+    pub fn limb4_solutions(
+        &self,
+        params: &KinematicParameters,
+        position: &Vector3<f64>,
+        pitch: f64,
+        roll: f64,
+    ) -> Vec<AnalyticalInverseKinematicSolution> {
+        let mut solutions: Vec<AnalyticalInverseKinematicSolution> = Vec::new();
+
+        // Compute the base angle, and the horizontal distance of the target from the base axis.
+        let (theta_0, radius) = Self::base_angle(position);
+
+        for base in [BaseBranch::Normal, BaseBranch::Flipped] {
+            // The flipped base reaches backwards, so the horizontal distance, pitch and roll
+            //  change accordingly.
+            let (theta_0, radius, pitch, roll, sign) = match base {
+                BaseBranch::Normal => (theta_0, radius, pitch, roll, 1_f64),
+                BaseBranch::Flipped => (theta_0 + PI, -radius, -pitch, roll - PI, -1_f64),
+            };
+
+            for (theta_1, theta_2, theta_3) in
+                Self::pitch_chain_angles(params, radius, position.y, pitch)
+            {
+                let elbow: ElbowBranch = if sign * theta_2 >= 0_f64 {
+                    ElbowBranch::Up
+                } else {
+                    ElbowBranch::Down
+                };
+
+                let state: KinematicState = KinematicState {
+                    theta_0: Self::wrap_angle(theta_0),
+                    theta_1: Self::wrap_angle(theta_1),
+                    theta_2: Self::wrap_angle(theta_2),
+                    theta_3: Self::wrap_angle(theta_3),
+                    theta_4: Self::wrap_angle(roll),
+                };
+
+                solutions.push(AnalyticalInverseKinematicSolution {
+                    within_limits: params.limits.contains(&state),
+                    state,
+                    elbow,
+                    base,
+                });
+            }
+        }
+
+        solutions.sort_by_key(|solution| (solution.base, solution.elbow));
+
+        solutions
+    }
+
+    /// Compute all the states in which the end-effector of the fourth limb reaches the given pose.
+    ///
+    /// Since the arm only has five degrees of freedom, the tool axis of the pose is projected onto
+    ///  the vertical plane through the target position before solving.
+    pub fn limb4_pose_solutions(
+        &self,
+        params: &KinematicParameters,
+        pose: &Pose,
+    ) -> Vec<AnalyticalInverseKinematicSolution> {
+        let (theta_0, _) = Self::base_angle(&pose.position);
+
+        // Compute the approach pitch from the tool axis, relative to the horizontal direction
+        //  in which the normal base branch reaches.
+        let tool: Vector3<f64> = pose.orientation.column(1).into();
+        let reach: Vector3<f64> = Vector3::<f64>::new(theta_0.sin(), 0_f64, -theta_0.cos());
+        let pitch: f64 = tool.dot(&reach).atan2(tool.y);
+
+        // Compute the roll that remains after the base and pitch rotation, for the normal base.
+        let base_pitch: Rotation3<f64> =
+            Rotation3::<f64>::from_axis_angle(&Vector3::<f64>::y_axis(), -theta_0)
+                * Rotation3::<f64>::from_axis_angle(&Vector3::<f64>::x_axis(), -pitch);
+        let remainder: Matrix3<f64> = base_pitch.inverse().into_inner() * pose.orientation;
+        let roll: f64 = remainder[(2, 0)].atan2(remainder[(0, 0)]);
+
+        self.limb4_solutions(params, &pose.position, pitch, roll)
+    }
+
+    /// Pick the solution for the given pose within the joint limits which lies closest (in joint
+    ///  space) to the given state.
+    pub fn closest_limb4_pose_solution(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        pose: &Pose,
+    ) -> Option<AnalyticalInverseKinematicSolution> {
+        Self::closest_solution(params, state, self.limb4_pose_solutions(params, pose))
+    }
+
+    /// Pick the solution within the joint limits which lies closest to the given state.
+    fn closest_solution(
+        params: &KinematicParameters,
+        state: &KinematicState,
+        solutions: Vec<AnalyticalInverseKinematicSolution>,
+    ) -> Option<AnalyticalInverseKinematicSolution> {
+        let current: Vector5<f64> = Vector5::<f64>::from(state);
+
+        solutions
+            .into_iter()
+            .filter_map(|mut solution| {
+                // Unwrap the angles of the solution so that they lie closest to the current state,
+                //  unless that moves them out of the joint limits.
+                let unwrapped: KinematicState = KinematicState::from(
+                    current.zip_map(&Vector5::<f64>::from(&solution.state), |current, angle| {
+                        current + Self::wrap_angle(angle - current)
+                    }),
+                );
+
+                if params.limits.contains(&unwrapped) {
+                    solution.state = unwrapped;
+                } else if !solution.within_limits {
+                    return None;
+                }
+
+                solution.within_limits = true;
+
+                let distance: f64 = (Vector5::<f64>::from(&solution.state) - current).magnitude();
+
+                Some((solution, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(solution, _)| solution)
+    }
+
+    /// Compute the base angle that points the arm towards the given position, and the horizontal
+    ///  distance between the base axis and the position.
+    fn base_angle(position: &Vector3<f64>) -> (f64, f64) {
+        let radius: f64 = (position.x.powi(2) + position.z.powi(2)).sqrt();
+
+        if radius < BASE_AXIS_EPS {
+            return (0_f64, 0_f64);
+        }
+
+        (position.x.atan2(-position.z), radius)
+    }
+
+    /// Solve the planar pitch chain for a target at the given horizontal distance (in the plane of
+    ///  the arm) and height, with the given approach pitch.
+    fn pitch_chain_angles(
+        &KinematicParameters {
+            l_0,
+            l_1,
+            l_2,
+            l_3,
+            l_4,
+            ..
+        }: &KinematicParameters,
+        radius: f64,
+        height: f64,
+        pitch: f64,
+    ) -> Vec<(f64, f64, f64)> {
+        // Compute the wrist position relative to the second joint, the last two links share the
+        //  same direction.
+        let horizontal: f64 = radius - (l_3 + l_4) * pitch.sin();
+        let vertical: f64 = height - l_0 - (l_3 + l_4) * pitch.cos();
+
+        // Solve the elbow angle using the law of cosines.
+        let cos_theta_2: f64 = (horizontal.powi(2) + vertical.powi(2) - l_1.powi(2) - l_2.powi(2))
+            / (2_f64 * l_1 * l_2);
+
+        if !cos_theta_2.is_finite() || cos_theta_2.abs() > 1_f64 + ELBOW_EPS {
+            return Vec::new();
+        }
+
+        let theta_2: f64 = cos_theta_2.clamp(-1_f64, 1_f64).acos();
+        let elbows: Vec<f64> = if 1_f64 - cos_theta_2.abs() <= ELBOW_EPS {
+            vec![theta_2]
+        } else {
+            vec![theta_2, -theta_2]
+        };
+
+        elbows
+            .into_iter()
+            .map(|theta_2| {
+                let theta_1: f64 = horizontal.atan2(vertical)
+                    - (l_2 * theta_2.sin()).atan2(l_1 + l_2 * theta_2.cos());
+
+                (theta_1, theta_2, pitch - theta_1 - theta_2)
+            })
+            .collect()
+    }
+
+    /// Wrap the given angle into the range `(-pi, pi]`.
+    fn wrap_angle(angle: f64) -> f64 {
+        let wrapped: f64 = (angle + PI).rem_euclid(2_f64 * PI) - PI;
+
+        if wrapped == -PI {
+            PI
+        } else {
+            wrapped
+        }
+    }
+
+    /// Take the current pose from the given forward kinematics, and refuse them when they don't
+    ///  describe the arm of the parameters (compared at the current state).
+    fn current_pose(
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Result<Pose, InverseKinematicError> {
+        let pose: Pose = fk.limb4_pose(params, state);
+        let model: Pose = AnalyticalForwardKinematicAlgorithm::default().limb4_pose(params, state);
+
+        if (pose.position - model.position).magnitude() > MODEL_EPS
+            || (pose.orientation - model.orientation).norm() > MODEL_EPS
+        {
+            return Err(InverseKinematicError::ModelMismatch);
+        }
+
+        Ok(pose)
+    }
+
+    /// Solve for the state closest to the current one that reaches the given pose.
+    fn solve_closest(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        pose: &Pose,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let solutions: Vec<AnalyticalInverseKinematicSolution> =
+            self.limb4_pose_solutions(params, pose);
+
+        if solutions.is_empty() {
+            return Err(InverseKinematicError::Unreachable(pose.position));
+        }
+
+        match Self::closest_solution(params, state, solutions) {
+            Some(solution) => Ok(solution.state),
+            None => Err(InverseKinematicError::JointLimit(pose.position)),
+        }
+    }
+}
+
+impl InverseKinematicAlgorithm for AnalyticalInverseKinematicAlgorithm {
+    fn translate_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let pose: Pose = Self::current_pose(fk, params, state)?;
+
+        self.solve_closest(
+            params,
+            state,
+            &Pose::new(pose.position + delta, pose.orientation),
+        )
+    }
+
+    fn rotate_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let pose: Pose = Self::current_pose(fk, params, state)?;
+
+        self.solve_closest(
+            params,
+            state,
+            &Pose::new(
+                pose.position,
+                Rotation3::<f64>::new(*delta).into_inner() * pose.orientation,
+            ),
+        )
+    }
+
+    /// The position is always reached exactly and the unreachable part of the orientation is
+    ///  projected away, so the weights are not used.
+    fn transform_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        translation: &Vector3<f64>,
+        rotation: &Vector3<f64>,
+        _weights: &PoseWeights,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let pose: Pose = Self::current_pose(fk, params, state)?;
+
+        self.solve_closest(
+            params,
+            state,
+            &Pose::new(
+                pose.position + translation,
+                Rotation3::<f64>::new(*rotation).into_inner() * pose.orientation,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::{Vector3, Vector5};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::DenavitHartenbergForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::analytical::{
+        AnalyticalInverseKinematicAlgorithm, AnalyticalInverseKinematicSolution, BaseBranch,
+        ElbowBranch,
+    };
+    use crate::inverse::algorithms::{
+        InverseKinematicAlgorithm, InverseKinematicError, PoseWeights,
+    };
+    use crate::model::{KinematicLimits, KinematicParameters, KinematicState, Pose};
+
+    #[test]
+    pub fn all_branches_reach_pose() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        // Take the target pose from a known state, so it's reachable.
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            theta_1: 0.6_f64,
+            theta_2: 0.4_f64,
+            theta_3: -0.3_f64,
+            theta_4: 0.2_f64,
+        };
+        let target: Pose = fk_solver.limb4_pose(&params, &state);
+
+        let solutions: Vec<AnalyticalInverseKinematicSolution> =
+            ik_solver.limb4_pose_solutions(&params, &target);
+
+        // Both base branches must have both elbow branches.
+        assert_eq!(
+            solutions
+                .iter()
+                .map(|solution| (solution.base, solution.elbow))
+                .collect::<Vec<(BaseBranch, ElbowBranch)>>(),
+            vec![
+                (BaseBranch::Normal, ElbowBranch::Up),
+                (BaseBranch::Normal, ElbowBranch::Down),
+                (BaseBranch::Flipped, ElbowBranch::Up),
+                (BaseBranch::Flipped, ElbowBranch::Down),
+            ]
+        );
+
+        // Every solution must reach the exact same pose.
+        for solution in solutions.iter() {
+            let pose: Pose = fk_solver.limb4_pose(&params, &solution.state);
+
+            assert!((pose.position - target.position).magnitude() < thresh);
+            assert!((pose.orientation - target.orientation).norm() < thresh);
+        }
+
+        // And the original state must be one of them.
+        assert!(solutions.iter().any(|solution| {
+            (Vector5::<f64>::from(&solution.state) - Vector5::<f64>::from(&state)).magnitude()
+                < thresh
+        }));
+    }
+
+    #[test]
+    pub fn unreachable_target() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        // The target lies way outside of the reach of the arm.
+        assert!(ik_solver
+            .limb4_solutions(
+                &params,
+                &Vector3::<f64>::new(0_f64, 0_f64, -100_f64),
+                0_f64,
+                0_f64
+            )
+            .is_empty());
+        assert!(ik_solver
+            .translate_limb4_end_effector(
+                &fk_solver,
+                &params,
+                &KinematicState::default(),
+                &Vector3::<f64>::new(100_f64, 0_f64, 0_f64)
+            )
+            .is_err());
+    }
+
+    #[test]
+    pub fn translate_keeps_branch() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        // Start in an elbow down configuration with the base flipped.
+        let state: KinematicState = KinematicState {
+            theta_0: 2.8_f64,
+            theta_1: -0.4_f64,
+            theta_2: 0.7_f64,
+            theta_3: -0.9_f64,
+            theta_4: 0.2_f64,
+        };
+
+        let delta: Vector3<f64> = Vector3::<f64>::new(0.1_f64, -0.2_f64, 0.1_f64);
+        let translated: KinematicState = ik_solver
+            .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+            .unwrap();
+
+        // The position must be reached exactly, without jumping to another branch.
+        assert!(
+            (fk_solver.limb4_position_vector(&params, &translated)
+                - fk_solver.limb4_position_vector(&params, &state)
+                - delta)
+                .magnitude()
+                < thresh
+        );
+        assert!(
+            (Vector5::<f64>::from(&translated) - Vector5::<f64>::from(&state)).magnitude()
+                < 0.1_f64
+        );
+    }
+
+    #[test]
+    pub fn closest_solution_within_limits() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            theta_1: 0.6_f64,
+            theta_2: 0.4_f64,
+            theta_3: -0.3_f64,
+            theta_4: 0.2_f64,
+        };
+        let target: Pose = fk_solver.limb4_pose(&params, &state);
+
+        // The flipped base solutions need a roll of more than a quarter turn.
+        let solutions: Vec<AnalyticalInverseKinematicSolution> =
+            ik_solver.limb4_pose_solutions(&params, &target);
+
+        assert!(solutions
+            .iter()
+            .filter(|solution| solution.base == BaseBranch::Flipped)
+            .all(|solution| !solution.within_limits));
+
+        // So even when starting on the flipped side, the normal base is chosen.
+        let solution: AnalyticalInverseKinematicSolution = ik_solver
+            .closest_limb4_pose_solution(
+                &params,
+                &KinematicState {
+                    theta_0: 0.5_f64 - PI,
+                    theta_4: 0.2_f64 - PI,
+                    ..KinematicState::default()
+                },
+                &target,
+            )
+            .unwrap();
+
+        assert_eq!(solution.base, BaseBranch::Normal);
+        assert!(params.limits.contains(&solution.state));
+    }
+
+    #[test]
+    pub fn outside_joint_limits() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        // The base of this state is turned too far, and the flipped solutions need too much roll.
+        let state: KinematicState = KinematicState {
+            theta_0: 2.8_f64,
+            theta_1: -0.4_f64,
+            theta_2: 0.7_f64,
+            theta_3: -0.9_f64,
+            theta_4: 0.2_f64,
+        };
+
+        let error: InverseKinematicError = ik_solver
+            .translate_limb4_end_effector(
+                &fk_solver,
+                &params,
+                &state,
+                &Vector3::<f64>::new(0.1_f64, 0_f64, 0_f64),
+            )
+            .unwrap_err();
+
+        assert!(matches!(error, InverseKinematicError::JointLimit(_)));
+    }
+
+    #[test]
+    pub fn refuses_other_model() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let ik_solver: AnalyticalInverseKinematicAlgorithm =
+            AnalyticalInverseKinematicAlgorithm::default();
+
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            theta_1: 0.6_f64,
+            theta_2: 0.4_f64,
+            theta_3: -0.3_f64,
+            theta_4: 0.2_f64,
+        };
+
+        // A table of the arm of the parameters is solved like the analytical model.
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&params);
+        let target: Pose = table.limb4_pose(&params, &state);
+        let solved: KinematicState = ik_solver
+            .solve_limb4_pose(
+                &table,
+                &params,
+                &KinematicState::default(),
+                &target,
+                &PoseWeights::default(),
+            )
+            .unwrap();
+        assert!(
+            (table.limb4_pose(&params, &solved).position - target.position).magnitude()
+                < 10_f64.powf(-9_f64)
+        );
+
+        // The closed form cannot solve for a table of an arm with other link lengths.
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_1: 20_f64,
+                ..KinematicParameters::default()
+            });
+        assert_eq!(
+            ik_solver.transform_limb4_end_effector(
+                &table,
+                &params,
+                &state,
+                &Vector3::<f64>::new(0.1_f64, 0_f64, 0_f64),
+                &Vector3::<f64>::zeros(),
+                &PoseWeights::default(),
+            ),
+            Err(InverseKinematicError::ModelMismatch)
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use nalgebra::{
+    DMatrix, DVector, Dyn, Matrix3, Matrix3x5, Matrix3xX, Matrix5, Matrix6, Matrix6x5, Vector3,
+    Vector5, Vector6, SVD,
+};
+
+use crate::forward::algorithms::chain::ChainForwardKinematicAlgorithm;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{
+    apply_limited_step, end_effector_frame_jacobian, pose_error_vector, InverseKinematicAlgorithm,
+    InverseKinematicError, PoseWeights,
+};
+use crate::inverse::singularity::{guarded_step_scale, SingularityGuard};
+use crate::model::chain::{ChainState, KinematicChain};
+use crate::model::{KinematicParameters, KinematicState};
+
+/// The singular value below which a direction of the jacobian is considered to be in its null space.
+const NULL_SPACE_EPS: f64 = 1e-9_f64;
+
+/// Damped least-squares (Levenberg-Marquardt) inverse kinematic approach.
+///
+/// The squared damping factor is adapted on every step, it grows with the squared error (so large
+///  steps towards far away or unreachable targets are shortened) and with the proximity of a
+///  singularity (measured by the smallest singular value of the jacobian).
+///
+/// The remaining freedom of the arm is used to move the joints away from their limits, and every
+///  step is clamped into the limits.
+pub struct DampedLeastSquaresInverseKinematicAlgorithm {
+    /// The gain of the squared error in the squared damping factor.
+    error_damping: f64,
+    /// The smallest singular value below which the jacobian is damped.
+    singular_value_threshold: f64,
+    /// The damping factor that is applied when the jacobian is fully singular.
+    max_singular_damping: f64,
+    /// The gain of the null space motion that moves the joints away from their limits.
+    limit_avoidance_gain: f64,
+    /// The optional guard that refuses or shortens steps with an ill-conditioned jacobian.
+    singularity_guard: Option<SingularityGuard>,
+}
+
+impl Default for DampedLeastSquaresInverseKinematicAlgorithm {
+    fn default() -> Self {
+        Self {
+            error_damping: 0.5_f64,
+            singular_value_threshold: 1_f64,
+            max_singular_damping: 1_f64,
+            limit_avoidance_gain: 1_f64,
+            singularity_guard: None,
+        }
+    }
+}
+
+impl DampedLeastSquaresInverseKinematicAlgorithm {
+    pub fn new(
+        error_damping: f64,
+        singular_value_threshold: f64,
+        max_singular_damping: f64,
+        limit_avoidance_gain: f64,
+    ) -> Self {
+        Self {
+            error_damping,
+            singular_value_threshold,
+            max_singular_damping,
+            limit_avoidance_gain,
+            singularity_guard: None,
+        }
+    }
+
+    /// Guard the steps of the algorithm against ill-conditioned jacobians.
+    pub fn with_singularity_guard(self, singularity_guard: SingularityGuard) -> Self {
+        Self {
+            singularity_guard: Some(singularity_guard),
+            ..self
+        }
+    }
+
+    /// Compute the squared damping factor for the given error norm and smallest singular value,
+    ///  the singular part grows quadratically from zero at the threshold to its maximum at zero.
+    fn squared_damping(&self, error_norm: f64, min_singular_value: f64) -> f64 {
+        let singular_damping: f64 = if min_singular_value < self.singular_value_threshold {
+            (1_f64 - (min_singular_value / self.singular_value_threshold).powi(2))
+                * self.max_singular_damping.powi(2)
+        } else {
+            0_f64
+        };
+
+        self.error_damping * error_norm.powi(2) + singular_damping
+    }
+
+    /// Solve the damped least-squares problem `min |J dq - e|^2 + l^2 |dq|^2` for the joint step,
+    ///  and compute the projector onto the null space of the jacobian.
+    ///
+    /// The step is refused or shortened when the singularity guard demands it.
+    fn damped_step(
+        &self,
+        jacobian: DMatrix<f64>,
+        error: DVector<f64>,
+    ) -> Result<(DVector<f64>, DMatrix<f64>), InverseKinematicError> {
+        let scale: f64 = guarded_step_scale(&self.singularity_guard, &jacobian)?;
+
+        let svd: SVD<f64, Dyn, Dyn> = jacobian.svd(true, true);
+        let (u, v_t) = match (svd.u, svd.v_t) {
+            (Some(u), Some(v_t)) => (u, v_t),
+            _ => unreachable!("both singular vector matrices were requested"),
+        };
+
+        let squared_damping: f64 =
+            self.squared_damping(error.magnitude(), svd.singular_values.min());
+
+        // Replace the inverted singular values by their damped counterparts.
+        let damped: DVector<f64> = svd.singular_values.map(|singular_value| {
+            let denominator: f64 = singular_value.powi(2) + squared_damping;
+
+            if denominator > 0_f64 {
+                singular_value / denominator
+            } else {
+                0_f64
+            }
+        });
+
+        // Remove the directions of the non-zero singular values from the identity, which leaves the
+        //  projector onto the null space.
+        let mut projector: DMatrix<f64> = DMatrix::<f64>::identity(v_t.ncols(), v_t.ncols());
+
+        for (i, singular_value) in svd.singular_values.iter().enumerate() {
+            if *singular_value > NULL_SPACE_EPS {
+                projector -= v_t.row(i).transpose() * v_t.row(i);
+            }
+        }
+
+        Ok((
+            v_t.transpose()
+                * DMatrix::<f64>::from_diagonal(&damped)
+                * u.transpose()
+                * error
+                * scale,
+            projector,
+        ))
+    }
+
+    /// Apply a joint step computed by `damped_step` to the given state.
+    fn apply_step(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        (step, projector): (DVector<f64>, DMatrix<f64>),
+    ) -> KinematicState {
+        apply_limited_step(
+            params,
+            state,
+            &Vector5::<f64>::from_iterator(step.iter().copied()),
+            &Matrix5::<f64>::from_iterator(projector.iter().copied()),
+            self.limit_avoidance_gain,
+        )
+    }
+
+    /// Translate and rotate the end-effector of the given kinematic chain in a single weighted
+    ///  least-squares step, like `transform_limb4_end_effector` does for the five-link arm.
+    pub fn transform_chain_end_effector(
+        &self,
+        chain: &KinematicChain,
+        state: &ChainState,
+        translation: &Vector3<f64>,
+        rotation: &Vector3<f64>,
+        weights: &PoseWeights,
+    ) -> Result<ChainState, InverseKinematicError> {
+        chain.check_state(state)?;
+
+        let fk: ChainForwardKinematicAlgorithm = ChainForwardKinematicAlgorithm::default();
+
+        // Compute the weighted pose error, and the weighted jacobian with its angular part
+        //  expressed in the end-effector frame (the frame the orientation weights are given in).
+        let orientation: Matrix3<f64> = fk.tool_pose(chain, state).orientation;
+        let weights: Matrix6<f64> = weights.matrix();
+        let error: Vector6<f64> = weights * pose_error_vector(&orientation, translation, rotation);
+
+        let mut jacobian: DMatrix<f64> = fk.tool_jacobian(chain, state);
+        let angular: Matrix3xX<f64> = orientation.transpose() * jacobian.rows(3, 3);
+        jacobian.rows_mut(3, 3).copy_from(&angular);
+        let jacobian: DMatrix<f64> =
+            DMatrix::<f64>::from_column_slice(6, 6, weights.as_slice()) * jacobian;
+
+        // Take the step, move away from the joint limits in the null space, and clamp the result.
+        let (step, projector) = self.damped_step(
+            jacobian,
+            DVector::<f64>::from_column_slice(error.as_slice()),
+        )?;
+        let avoidance: DVector<f64> =
+            -self.limit_avoidance_gain * (projector * chain.cost_gradient(state)?);
+
+        Ok(chain.clamp(&ChainState::new(&state.values + step + avoidance))?)
+    }
+}
+
+impl InverseKinematicAlgorithm for DampedLeastSquaresInverseKinematicAlgorithm {
+    fn translate_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let jacobian: Matrix3x5<f64> = fk.limb4_jacobian(params, state).fixed_rows::<3>(0).into();
+
+        Ok(self.apply_step(
+            params,
+            state,
+            self.damped_step(
+                DMatrix::<f64>::from_column_slice(3, 5, jacobian.as_slice()),
+                DVector::<f64>::from_column_slice(delta.as_slice()),
+            )?,
+        ))
+    }
+
+    fn rotate_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        let jacobian: Matrix3x5<f64> = fk.limb4_jacobian(params, state).fixed_rows::<3>(3).into();
+
+        Ok(self.apply_step(
+            params,
+            state,
+            self.damped_step(
+                DMatrix::<f64>::from_column_slice(3, 5, jacobian.as_slice()),
+                DVector::<f64>::from_column_slice(delta.as_slice()),
+            )?,
+        ))
+    }
+
+    fn transform_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        translation: &Vector3<f64>,
+        rotation: &Vector3<f64>,
+        weights: &PoseWeights,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        // Compute the weighted pose error, and the weighted jacobian.
+        let orientation: Matrix3<f64> = fk.limb4_orientation_matrix(params, state);
+        let weights: Matrix6<f64> = weights.matrix();
+        let error: Vector6<f64> = weights * pose_error_vector(&orientation, translation, rotation);
+        let jacobian: Matrix6x5<f64> =
+            weights * end_effector_frame_jacobian(&orientation, &fk.limb4_jacobian(params, state));
+
+        Ok(self.apply_step(
+            params,
+            state,
+            self.damped_step(
+                DMatrix::<f64>::from_column_slice(6, 5, jacobian.as_slice()),
+                DVector::<f64>::from_column_slice(error.as_slice()),
+            )?,
+        ))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{Vector3, Vector5};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::chain::ChainForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::DenavitHartenbergForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::damped_least_squares::DampedLeastSquaresInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::{InverseKinematicAlgorithm, PoseWeights};
+    use crate::model::chain::{ChainJoint, ChainState, KinematicChain};
+    use crate::model::{JointLimit, KinematicParameters, KinematicState, Pose};
+
+    #[test]
+    pub fn solve() {
+        // Create the kinematic state (in a way that all links point straight up).
+        let mut state: KinematicState = KinematicState::default();
+
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // Create the analytical forward kinematics algorithm and the damped least-squares
+        //  inverse kinematics algorithm.
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-4_f64);
+
+        let target: Vector3<f64> = Vector3::<f64>::new(2_f64, 48_f64, 2_f64);
+
+        for _ in 1..100 {
+            // Compute the current end effector position, and the difference between it and the
+            //  target.
+            let delta: Vector3<f64> = target - fk_solver.limb4_position_vector(&params, &state);
+
+            // If the target is really close, just break.
+            if delta.magnitude() < thresh {
+                break;
+            }
+
+            // Update the state.
+            state = ik_solver
+                .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+                .unwrap()
+        }
+
+        // Make sure that the algorithm reached the destinaton.
+        assert!((fk_solver.limb4_position_vector(&params, &state) - target).magnitude() < thresh);
+    }
+
+    #[test]
+    pub fn stable_near_singularity() {
+        // Create a kinematic state that is almost fully stretched out.
+        let state: KinematicState = KinematicState {
+            theta_1: 1e-5_f64,
+            ..KinematicState::default()
+        };
+
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let heuristic: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let damped: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+
+        // Request a small sideways motion, along the singular direction.
+        let delta: Vector3<f64> = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+
+        let step = |state: &KinematicState| -> f64 {
+            (Vector5::<f64>::from(state) - Vector5::<f64>::from(&KinematicState::default()))
+                .magnitude()
+        };
+
+        // The plain pseudo-inverse takes an enormous step, while the damped one stays bounded.
+        let heuristic_state: KinematicState = heuristic
+            .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+            .unwrap();
+        let damped_state: KinematicState = damped
+            .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+            .unwrap();
+
+        assert!(step(&heuristic_state) > 1000_f64);
+        assert!(step(&damped_state) < 1_f64);
+    }
+
+    #[test]
+    pub fn stable_when_stretched_out() {
+        // Create a kinematic state in which the arm is slightly bent.
+        let mut state: KinematicState = KinematicState {
+            theta_1: 0.2_f64,
+            ..KinematicState::default()
+        };
+
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+
+        // Target a point out of reach, the arm should stretch towards it without diverging.
+        let target: Vector3<f64> = Vector3::<f64>::new(60_f64, 10_f64, 0_f64);
+
+        for _ in 1..200 {
+            let delta: Vector3<f64> = target - fk_solver.limb4_position_vector(&params, &state);
+
+            state = ik_solver
+                .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+                .unwrap()
+        }
+
+        // The closest reachable point lies on the reach sphere around the top of the base.
+        let base: Vector3<f64> = Vector3::<f64>::new(0_f64, params.l_0, 0_f64);
+        let closest: f64 =
+            (target - base).magnitude() - (params.sum_of_link_lengths() - params.l_0);
+
+        assert!(Vector5::<f64>::from(&state).iter().all(|x| x.is_finite()));
+        assert!(
+            ((fk_solver.limb4_position_vector(&params, &state) - target).magnitude() - closest)
+                .abs()
+                < 10_f64.powf(-2_f64)
+        );
+    }
+
+    #[test]
+    pub fn solve_pose_with_given_model() {
+        // The table describes an arm with other link lengths than the parameters, so the pose step
+        //  must take its jacobian from the table instead of from the analytical model.
+        let params: KinematicParameters = KinematicParameters::default();
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_0: 4_f64,
+                l_1: 20_f64,
+                l_2: 3_f64,
+                l_3: 10_f64,
+                l_4: 10_f64,
+                ..KinematicParameters::default()
+            });
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-6_f64);
+
+        // Take the target pose from a known state of the table, so it's reachable.
+        let target: Pose = table.limb4_pose(
+            &params,
+            &KinematicState {
+                theta_0: 0.5_f64,
+                theta_1: 0.6_f64,
+                theta_2: 0.4_f64,
+                theta_3: -0.3_f64,
+                theta_4: 0.2_f64,
+            },
+        );
+
+        let state: KinematicState = ik_solver
+            .solve_limb4_pose(
+                &table,
+                &params,
+                &KinematicState {
+                    theta_0: 0.3_f64,
+                    theta_1: 0.2_f64,
+                    theta_2: 0.2_f64,
+                    theta_3: 0.2_f64,
+                    theta_4: 0_f64,
+                },
+                &target,
+                &PoseWeights::default(),
+            )
+            .unwrap();
+
+        let pose: Pose = table.limb4_pose(&params, &state);
+        assert!((pose.position - target.position).magnitude() < thresh);
+        assert!((pose.orientation - target.orientation).norm() < thresh);
+    }
+
+    #[test]
+    pub fn solve_chain() {
+        // A six joint chain, with a prismatic joint that extends the second link.
+        let mut joints: Vec<ChainJoint> = vec![
+            ChainJoint::revolute(Vector3::<f64>::zeros(), Vector3::<f64>::y()),
+            ChainJoint::revolute(
+                Vector3::<f64>::new(0_f64, 10_f64, 0_f64),
+                Vector3::<f64>::x(),
+            ),
+            ChainJoint::prismatic(Vector3::<f64>::zeros(), Vector3::<f64>::y()),
+            ChainJoint::revolute(
+                Vector3::<f64>::new(0_f64, 10_f64, 0_f64),
+                Vector3::<f64>::x(),
+            ),
+            ChainJoint::revolute(
+                Vector3::<f64>::new(0_f64, 10_f64, 0_f64),
+                Vector3::<f64>::x(),
+            ),
+            ChainJoint::revolute(
+                Vector3::<f64>::new(0_f64, 5_f64, 0_f64),
+                Vector3::<f64>::y(),
+            ),
+        ];
+        joints[2].limit = JointLimit::new(0_f64, 5_f64);
+
+        let chain: KinematicChain = KinematicChain::new(
+            joints,
+            Pose::from_position(Vector3::<f64>::new(0_f64, 5_f64, 0_f64)),
+        );
+
+        let fk_solver: ChainForwardKinematicAlgorithm = ChainForwardKinematicAlgorithm::default();
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-4_f64);
+
+        let mut state: ChainState = ChainState::zeros(chain.dof());
+        state.values[1] = 0.2_f64;
+        state.values[3] = 0.2_f64;
+
+        // Only the position of the end-effector matters.
+        let weights: PoseWeights =
+            PoseWeights::new(Vector3::<f64>::repeat(1_f64), Vector3::<f64>::zeros());
+        let target: Vector3<f64> = Vector3::<f64>::new(12_f64, 20_f64, 15_f64);
+
+        for _ in 1..200 {
+            let delta: Vector3<f64> = target - fk_solver.tool_pose(&chain, &state).position;
+
+            if delta.magnitude() < thresh {
+                break;
+            }
+
+            state = ik_solver
+                .transform_chain_end_effector(
+                    &chain,
+                    &state,
+                    &delta,
+                    &Vector3::<f64>::zeros(),
+                    &weights,
+                )
+                .unwrap();
+
+            assert!(chain.contains(&state).unwrap());
+        }
+
+        assert!((fk_solver.tool_pose(&chain, &state).position - target).magnitude() < thresh);
+    }
+}
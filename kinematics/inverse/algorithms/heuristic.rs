@@ -1,140 +1,159 @@
-use std::error::Error;
-use std::sync::Arc;
+use nalgebra::{
+    DMatrix, Matrix3, Matrix3x5, Matrix5, Matrix5x3, Matrix5x6, Matrix6, Matrix6x5, SMatrix,
+    Vector3, Vector6,
+};
 
-use nalgebra::{Matrix3x5, Matrix5x3, Vector3, Vector5};
-use thiserror::Error;
-
-use crate::inverse::algorithms::InverseKinematicAlgorithm;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{
+    apply_limited_step, end_effector_frame_jacobian, pose_error_vector, InverseKinematicAlgorithm,
+    InverseKinematicError, PoseWeights,
+};
+use crate::inverse::singularity::{guarded_step_scale, SingularityError, SingularityGuard};
 use crate::model::{KinematicParameters, KinematicState};
 
-#[derive(Debug, Error)]
-pub enum HeuristicInverseKinematicsAlgorithmError {
-    #[error("Failed to pseudo-invert jacobian matrix, error: {0}")]
-    PseudoInvertFailure(&'static str),
-}
-
 pub struct HeuristicInverseKinematicAlgorithm {
     pseudo_inverse_eps: f64,
+    /// The gain of the null space motion that moves the joints away from their limits.
+    limit_avoidance_gain: f64,
+    /// The optional guard that refuses or shortens steps with an ill-conditioned jacobian.
+    singularity_guard: Option<SingularityGuard>,
 }
 
 impl Default for HeuristicInverseKinematicAlgorithm {
     fn default() -> Self {
         Self {
             pseudo_inverse_eps: 10_f64.powf(-5_f64),
+            limit_avoidance_gain: 1_f64,
+            singularity_guard: None,
         }
     }
 }
 
 impl HeuristicInverseKinematicAlgorithm {
-    fn limb4_end_effector_position_jacobian(
+    pub fn new(pseudo_inverse_eps: f64, limit_avoidance_gain: f64) -> Self {
+        Self {
+            pseudo_inverse_eps,
+            limit_avoidance_gain,
+            singularity_guard: None,
+        }
+    }
+
+    /// Guard the steps of the algorithm against ill-conditioned jacobians.
+    pub fn with_singularity_guard(self, singularity_guard: SingularityGuard) -> Self {
+        Self {
+            singularity_guard: Some(singularity_guard),
+            ..self
+        }
+    }
+
+    /// Compute the factor a step taken with the given jacobian is scaled by.
+    fn step_scale<const R: usize>(
         &self,
-        &KinematicParameters {
-            l_0,
-            l_1,
-            l_2,
-            l_3,
-            l_4,
-        }: &KinematicParameters,
-        &KinematicState {
-            theta_0,
-            theta_1,
-            theta_2,
-            theta_3,
-            theta_4,
-        }: &KinematicState,
-    ) -> Matrix3x5<f64> {
-        Matrix3x5::<f64>::new(
-            (l_1 * theta_1.sin()
-                + l_2 * (theta_1 + theta_2).sin()
-                + l_3 * (theta_1 + theta_2 + theta_3).sin()
-                + l_4 * (theta_1 + theta_2 + theta_3).sin())
-                * theta_0.cos(),
-            (l_1 * theta_1.cos()
-                + l_2 * (theta_1 + theta_2).cos()
-                + l_3 * (theta_1 + theta_2 + theta_3).cos()
-                + l_4 * (theta_1 + theta_2 + theta_3).cos())
-                * theta_0.sin(),
-            (l_2 * (theta_1 + theta_2).cos()
-                + l_3 * (theta_1 + theta_2 + theta_3).cos()
-                + l_4 * (theta_1 + theta_2 + theta_3).cos())
-                * theta_0.sin(),
-            (l_3 + l_4) * theta_0.sin() * (theta_1 + theta_2 + theta_3).cos(),
-            0_f64,
-            0_f64,
-            -l_1 * theta_1.sin()
-                - l_2 * (theta_1 + theta_2).sin()
-                - l_3 * (theta_1 + theta_2 + theta_3).sin()
-                - l_4 * (theta_1 + theta_2 + theta_3).sin(),
-            -l_2 * (theta_1 + theta_2).sin()
-                - l_3 * (theta_1 + theta_2 + theta_3).sin()
-                - l_4 * (theta_1 + theta_2 + theta_3).sin(),
-            (-l_3 - l_4) * (theta_1 + theta_2 + theta_3).sin(),
-            0_f64,
-            (l_1 * theta_1.sin()
-                + l_2 * (theta_1 + theta_2).sin()
-                + l_3 * (theta_1 + theta_2 + theta_3).sin()
-                + l_4 * (theta_1 + theta_2 + theta_3).sin())
-                * theta_0.sin(),
-            -(l_1 * theta_1.cos()
-                + l_2 * (theta_1 + theta_2).cos()
-                + l_3 * (theta_1 + theta_2 + theta_3).cos()
-                + l_4 * (theta_1 + theta_2 + theta_3).cos())
-                * theta_0.cos(),
-            -(l_2 * (theta_1 + theta_2).cos()
-                + l_3 * (theta_1 + theta_2 + theta_3).cos()
-                + l_4 * (theta_1 + theta_2 + theta_3).cos())
-                * theta_0.cos(),
-            (-l_3 - l_4) * theta_0.cos() * (theta_1 + theta_2 + theta_3).cos(),
-            0_f64,
-        )
+        jacobian: &SMatrix<f64, R, 5>,
+    ) -> Result<f64, InverseKinematicError> {
+        Ok(guarded_step_scale(
+            &self.singularity_guard,
+            &DMatrix::<f64>::from_column_slice(R, 5, jacobian.as_slice()),
+        )?)
     }
 }
 
 impl InverseKinematicAlgorithm for HeuristicInverseKinematicAlgorithm {
     fn translate_limb4_end_effector(
         &self,
+        fk: &dyn ForwardKinematicAlgorithm,
         params: &KinematicParameters,
         state: &KinematicState,
         delta: &Vector3<f64>,
-    ) -> Result<KinematicState, Arc<dyn Error>> {
+    ) -> Result<KinematicState, InverseKinematicError> {
         // Compute the jacobian matrix for the end-effector position.
-        let jacobian: Matrix3x5<f64> = self.limb4_end_effector_position_jacobian(params, state);
+        let jacobian: Matrix3x5<f64> = fk.limb4_jacobian(params, state).fixed_rows::<3>(0).into();
 
         // Invert the jacobian matrix.
-        let jacobian_inverse: Matrix5x3<f64> =
-            match jacobian.pseudo_inverse(self.pseudo_inverse_eps) {
-                Ok(x) => x,
-                Err(error) => {
-                    return Err(Arc::new(
-                        HeuristicInverseKinematicsAlgorithmError::PseudoInvertFailure(error),
-                    ));
-                }
-            };
-
-        // Compute the new kinematic state and return it.
-        Ok(KinematicState::from(
-            (jacobian_inverse * delta) + Vector5::<f64>::from(state),
+        let jacobian_inverse: Matrix5x3<f64> = jacobian
+            .pseudo_inverse(self.pseudo_inverse_eps)
+            .map_err(SingularityError::PseudoInvertFailure)?;
+
+        // Compute the new kinematic state, respecting the joint limits, and return it.
+        Ok(apply_limited_step(
+            params,
+            state,
+            &(jacobian_inverse * delta * self.step_scale(&jacobian)?),
+            &(Matrix5::<f64>::identity() - jacobian_inverse * jacobian),
+            self.limit_avoidance_gain,
         ))
     }
 
     fn rotate_limb4_end_effector(
         &self,
+        fk: &dyn ForwardKinematicAlgorithm,
         params: &KinematicParameters,
         state: &KinematicState,
         delta: &Vector3<f64>,
-    ) -> Result<KinematicState, Arc<dyn Error>> {
-        todo!()
+    ) -> Result<KinematicState, InverseKinematicError> {
+        // Compute the jacobian matrix for the end-effector orientation.
+        let jacobian: Matrix3x5<f64> = fk.limb4_jacobian(params, state).fixed_rows::<3>(3).into();
+
+        // Invert the jacobian matrix.
+        let jacobian_inverse: Matrix5x3<f64> = jacobian
+            .pseudo_inverse(self.pseudo_inverse_eps)
+            .map_err(SingularityError::PseudoInvertFailure)?;
+
+        // Compute the new kinematic state, respecting the joint limits, and return it.
+        Ok(apply_limited_step(
+            params,
+            state,
+            &(jacobian_inverse * delta * self.step_scale(&jacobian)?),
+            &(Matrix5::<f64>::identity() - jacobian_inverse * jacobian),
+            self.limit_avoidance_gain,
+        ))
+    }
+
+    fn transform_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        translation: &Vector3<f64>,
+        rotation: &Vector3<f64>,
+        weights: &PoseWeights,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        // Compute the weighted pose error, with the rotation expressed in the end-effector frame
+        //  since that is the frame the orientation weights are given in.
+        let orientation: Matrix3<f64> = fk.limb4_orientation_matrix(params, state);
+        let weights: Matrix6<f64> = weights.matrix();
+        let error: Vector6<f64> = weights * pose_error_vector(&orientation, translation, rotation);
+
+        // Compute the weighted jacobian matrix for the end-effector pose.
+        let jacobian: Matrix6x5<f64> =
+            weights * end_effector_frame_jacobian(&orientation, &fk.limb4_jacobian(params, state));
+
+        // Invert the jacobian matrix.
+        let jacobian_inverse: Matrix5x6<f64> = jacobian
+            .pseudo_inverse(self.pseudo_inverse_eps)
+            .map_err(SingularityError::PseudoInvertFailure)?;
+
+        // Compute the new kinematic state, respecting the joint limits, and return it.
+        Ok(apply_limited_step(
+            params,
+            state,
+            &(jacobian_inverse * error * self.step_scale(&jacobian)?),
+            &(Matrix5::<f64>::identity() - jacobian_inverse * jacobian),
+            self.limit_avoidance_gain,
+        ))
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use nalgebra::Vector3;
+    use nalgebra::{Matrix3, Rotation3, Vector3};
+
     use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::DenavitHartenbergForwardKinematicAlgorithm;
     use crate::forward::algorithms::ForwardKinematicAlgorithm;
     use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
-    use crate::inverse::algorithms::InverseKinematicAlgorithm;
-    use crate::model::{KinematicParameters, KinematicState};
+    use crate::inverse::algorithms::{InverseKinematicAlgorithm, PoseWeights};
+    use crate::model::{KinematicLimits, KinematicParameters, KinematicState, Pose};
 
     #[test]
     pub fn solve() {
@@ -173,10 +192,271 @@ pub mod tests {
             }
 
             // Update the state.
-            state = ik_solver.translate_limb4_end_effector(&params, &state, &delta).unwrap()
+            state = ik_solver
+                .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+                .unwrap()
         }
 
         // Make sure that the algorithm reached the destinaton.
         assert!((fk_solver.limb4_position_vector(&params, &state) - target).magnitude() < thresh);
     }
+
+    #[test]
+    pub fn rotate() {
+        // Create a kinematic state in which the end-effector does not point straight up.
+        let mut state: KinematicState = KinematicState {
+            theta_0: 0.3_f64,
+            theta_1: 0.4_f64,
+            theta_2: 0.3_f64,
+            theta_3: 0.2_f64,
+            theta_4: -0.5_f64,
+        };
+
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // Create the analytical forward kinematics algorithm and the heuristic
+        //  inverse kinematics algorithm.
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-6_f64);
+
+        // Compute the target orientation by applying the requested rotation to the current one.
+        let rotation: Vector3<f64> = Vector3::<f64>::new(0.1_f64, -0.2_f64, 0.15_f64);
+        let target: Matrix3<f64> = Rotation3::<f64>::new(rotation).into_inner()
+            * fk_solver.limb4_orientation_matrix(&params, &state);
+
+        // A single step must already rotate the end-effector roughly by the requested rotation.
+        let stepped: KinematicState = ik_solver
+            .rotate_limb4_end_effector(&fk_solver, &params, &state, &rotation)
+            .unwrap();
+        assert!(
+            (fk_solver.limb4_orientation_matrix(&params, &stepped) - target).norm()
+                < 0.1_f64 * rotation.magnitude()
+        );
+
+        for _ in 1..100 {
+            // Compute the rotation vector that takes the current orientation onto the target.
+            let delta: Vector3<f64> = Rotation3::<f64>::from_matrix_unchecked(
+                target
+                    * fk_solver
+                        .limb4_orientation_matrix(&params, &state)
+                        .transpose(),
+            )
+            .scaled_axis();
+
+            // If the target is really close, just break.
+            if delta.magnitude() < thresh {
+                break;
+            }
+
+            // Update the state.
+            state = ik_solver
+                .rotate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+                .unwrap()
+        }
+
+        // Make sure that the algorithm reached the requested orientation.
+        assert!((fk_solver.limb4_orientation_matrix(&params, &state) - target).norm() < thresh);
+    }
+
+    #[test]
+    pub fn solve_pose() {
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // Create the analytical forward kinematics algorithm and the heuristic
+        //  inverse kinematics algorithm.
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-6_f64);
+
+        // Take the target pose from a known state, so it's reachable.
+        let target: Pose = fk_solver.limb4_pose(
+            &params,
+            &KinematicState {
+                theta_0: 0.5_f64,
+                theta_1: 0.6_f64,
+                theta_2: 0.4_f64,
+                theta_3: -0.3_f64,
+                theta_4: 0.2_f64,
+            },
+        );
+
+        // Solve for the pose, starting at a different state.
+        let state: KinematicState = ik_solver
+            .solve_limb4_pose(
+                &fk_solver,
+                &params,
+                &KinematicState {
+                    theta_0: 0.3_f64,
+                    theta_1: 0.2_f64,
+                    theta_2: 0.2_f64,
+                    theta_3: 0.2_f64,
+                    theta_4: 0_f64,
+                },
+                &target,
+                &PoseWeights::default(),
+            )
+            .unwrap();
+
+        // Make sure that both the position and orientation have been reached.
+        let pose: Pose = fk_solver.limb4_pose(&params, &state);
+        assert!((pose.position - target.position).magnitude() < thresh);
+        assert!((pose.orientation - target.orientation).norm() < thresh);
+    }
+
+    #[test]
+    pub fn solve_pose_with_given_model() {
+        // Create the default kinematic parameters, and a table of an arm with other link lengths.
+        let params: KinematicParameters = KinematicParameters::default();
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_0: 4_f64,
+                l_1: 20_f64,
+                l_2: 3_f64,
+                l_3: 10_f64,
+                l_4: 10_f64,
+                ..KinematicParameters::default()
+            });
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-6_f64);
+
+        // Take the target pose from a known state of the table, so it's reachable.
+        let target: Pose = table.limb4_pose(
+            &params,
+            &KinematicState {
+                theta_0: 0.5_f64,
+                theta_1: 0.6_f64,
+                theta_2: 0.4_f64,
+                theta_3: -0.3_f64,
+                theta_4: 0.2_f64,
+            },
+        );
+
+        // Solve for the pose with the table, the steps must use its jacobian instead of the one of
+        //  the parameters.
+        let state: KinematicState = ik_solver
+            .solve_limb4_pose(
+                &table,
+                &params,
+                &KinematicState {
+                    theta_0: 0.3_f64,
+                    theta_1: 0.2_f64,
+                    theta_2: 0.2_f64,
+                    theta_3: 0.2_f64,
+                    theta_4: 0_f64,
+                },
+                &target,
+                &PoseWeights::default(),
+            )
+            .unwrap();
+
+        let pose: Pose = table.limb4_pose(&params, &state);
+        assert!((pose.position - target.position).magnitude() < thresh);
+        assert!((pose.orientation - target.orientation).norm() < thresh);
+    }
+
+    #[test]
+    pub fn solve_pose_with_optional_orientation() {
+        // Create the default kinematic parameters.
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // Create the analytical forward kinematics algorithm and the heuristic
+        //  inverse kinematics algorithm.
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-4_f64);
+
+        // Request a position together with an orientation that cannot be reached at that
+        //  position (the tool yawed away from the plane of the arm).
+        let state: KinematicState = KinematicState {
+            theta_0: 0.5_f64,
+            theta_1: 0.6_f64,
+            theta_2: 0.4_f64,
+            theta_3: -0.3_f64,
+            theta_4: 0.2_f64,
+        };
+        let target: Pose = Pose::new(
+            fk_solver.limb4_position_vector(&params, &state),
+            Rotation3::<f64>::new(Vector3::<f64>::new(0_f64, 0_f64, 0.4_f64)).into_inner()
+                * fk_solver.limb4_orientation_matrix(&params, &state),
+        );
+
+        // Make the position much more important than the orientation, and ignore the roll.
+        let weights: PoseWeights = PoseWeights::new(
+            Vector3::<f64>::repeat(1000_f64),
+            Vector3::<f64>::new(1_f64, 0_f64, 1_f64),
+        );
+
+        let solved: KinematicState = ik_solver
+            .solve_limb4_pose(&fk_solver, &params, &state, &target, &weights)
+            .unwrap();
+
+        // The position must still be reached, even though the orientation cannot be.
+        assert!(
+            (fk_solver.limb4_position_vector(&params, &solved) - target.position).magnitude()
+                < thresh
+        );
+    }
+
+    #[test]
+    pub fn respects_joint_limits() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let thresh: f64 = 10_f64.powf(-4_f64);
+
+        // Take the target from a state within the limits, so it can be reached.
+        let target: Vector3<f64> = fk_solver.limb4_position_vector(
+            &params,
+            &KinematicState {
+                theta_0: 0.3_f64,
+                theta_1: 0.5_f64,
+                theta_2: 0.6_f64,
+                theta_3: 0.4_f64,
+                theta_4: 0.1_f64,
+            },
+        );
+
+        let mut state: KinematicState = KinematicState {
+            theta_1: 0.2_f64,
+            ..KinematicState::default()
+        };
+
+        for _ in 1..100 {
+            let delta: Vector3<f64> = target - fk_solver.limb4_position_vector(&params, &state);
+
+            if delta.magnitude() < thresh {
+                break;
+            }
+
+            state = ik_solver
+                .translate_limb4_end_effector(&fk_solver, &params, &state, &delta)
+                .unwrap();
+
+            // Every intermediate state must lie within the limits.
+            assert!(params.limits.contains(&state));
+        }
+
+        assert!((fk_solver.limb4_position_vector(&params, &state) - target).magnitude() < thresh);
+    }
 }
@@ -1,25 +1,258 @@
-use std::error::Error;
-use std::sync::Arc;
-use nalgebra::Vector3;
+use nalgebra::{
+    Matrix3, Matrix3x5, Matrix5, Matrix6, Matrix6x5, Rotation3, Vector3, Vector5, Vector6,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::model::{KinematicParameters, KinematicState};
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::reach::ReachSpheres;
+use crate::inverse::singularity::SingularityError;
+use crate::inverse::solver::SolverReport;
+use crate::model::chain::KinematicChainError;
+use crate::model::{KinematicParameters, KinematicState, Pose};
 
+pub mod analytical;
+pub mod damped_least_squares;
 pub mod heuristic;
 
+/// The maximum number of steps taken by a pose solve.
+const POSE_SOLVE_MAX_ITERATIONS: usize = 200_usize;
+
+/// The joint step size below which a pose solve is considered to be finished.
+const POSE_SOLVE_STEP_EPS: f64 = 1e-10_f64;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum InverseKinematicError {
+    #[error("Target position {0} lies outside of the reach of the arm")]
+    Unreachable(Vector3<f64>),
+    #[error("The jacobian is singular, error: {0}")]
+    Singular(#[from] SingularityError),
+    #[error("Target position {0} can only be reached outside of the joint limits")]
+    JointLimit(Vector3<f64>),
+    #[error("The solve did not converge, it ended {:?} with residual {}", .0.status, .0.residual)]
+    NotConverged(SolverReport),
+    #[error("Invalid kinematic chain state, error: {0}")]
+    Chain(#[from] KinematicChainError),
+    #[error("The forward kinematics describe another arm than the algorithm solves for")]
+    ModelMismatch,
+}
+
+/// The per-axis weights of a pose solve, a weight of zero means that the axis is ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoseWeights {
+    /// The weights of the position error along the axes of the base frame.
+    pub position: Vector3<f64>,
+    /// The weights of the orientation error around the axes of the end-effector frame, the
+    ///  y-axis is the tool axis (so its weight is the one of the roll).
+    pub orientation: Vector3<f64>,
+}
+
+impl PoseWeights {
+    pub fn new(position: Vector3<f64>, orientation: Vector3<f64>) -> Self {
+        Self {
+            position,
+            orientation,
+        }
+    }
+
+    /// Get the diagonal weighting matrix, which weights a vector built by `pose_error_vector`.
+    pub fn matrix(&self) -> Matrix6<f64> {
+        Matrix6::<f64>::from_diagonal(&Vector6::<f64>::new(
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.orientation.x,
+            self.orientation.y,
+            self.orientation.z,
+        ))
+    }
+}
+
+impl Default for PoseWeights {
+    fn default() -> Self {
+        Self {
+            position: Vector3::<f64>::repeat(1_f64),
+            orientation: Vector3::<f64>::repeat(1_f64),
+        }
+    }
+}
+
 pub trait InverseKinematicAlgorithm {
-    /// Translate the end-effector position of the fourth link.
+    /// Translate the end-effector position of the fourth link. The jacobian is taken from the given
+    ///  forward kinematics, so that the step moves the arm those kinematics describe.
     fn translate_limb4_end_effector(
         &self,
+        fk: &dyn ForwardKinematicAlgorithm,
         params: &KinematicParameters,
         state: &KinematicState,
         delta: &Vector3<f64>,
-    ) -> Result<KinematicState, Arc<dyn Error>>;
+    ) -> Result<KinematicState, InverseKinematicError>;
 
-    /// Rotate the end-effector of the fourth-link.
+    /// Rotate the end-effector of the fourth-link, the delta is a rotation vector (axis scaled
+    ///  by the angle) expressed in the base frame. The jacobian is taken from the given forward
+    ///  kinematics, so that the step moves the arm those kinematics describe.
     fn rotate_limb4_end_effector(
         &self,
+        fk: &dyn ForwardKinematicAlgorithm,
         params: &KinematicParameters,
         state: &KinematicState,
         delta: &Vector3<f64>,
-    ) -> Result<KinematicState, Arc<dyn Error>>;
+    ) -> Result<KinematicState, InverseKinematicError>;
+
+    /// Translate and rotate the end-effector of the fourth link in a single weighted
+    ///  least-squares step, the translation is expressed in the base frame and the rotation is a
+    ///  rotation vector expressed in the base frame. The jacobian is taken from the given forward
+    ///  kinematics, so that it matches the model the pose error is computed with.
+    fn transform_limb4_end_effector(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        translation: &Vector3<f64>,
+        rotation: &Vector3<f64>,
+        weights: &PoseWeights,
+    ) -> Result<KinematicState, InverseKinematicError>;
+
+    /// Solve for the state in which the end-effector of the fourth link reaches the given pose.
+    ///
+    /// Since the arm only has five degrees of freedom not every pose can be reached, in that case
+    ///  the state that minimizes the weighted pose error is returned. A fully weighted target
+    ///  position outside of the reach spheres is refused up front.
+    fn solve_limb4_pose(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target: &Pose,
+        weights: &PoseWeights,
+    ) -> Result<KinematicState, InverseKinematicError> {
+        if weights.position.iter().all(|weight| *weight != 0_f64) {
+            ReachSpheres::check_target(fk, params, state, &target.position)?;
+        }
+
+        let mut state: KinematicState = state.clone();
+        let mut cost: f64 = weighted_pose_error(fk, params, &state, target, weights).magnitude();
+
+        for _ in 0..POSE_SOLVE_MAX_ITERATIONS {
+            // Compute the current pose, and the translation and rotation towards the target.
+            let pose: Pose = fk.limb4_pose(params, &state);
+            let translation: Vector3<f64> = target.position - pose.position;
+            let rotation: Vector3<f64> = Rotation3::<f64>::from_matrix_unchecked(
+                target.orientation * pose.orientation.transpose(),
+            )
+            .scaled_axis();
+
+            // Compute the full step.
+            let next: KinematicState = self.transform_limb4_end_effector(
+                fk,
+                params,
+                &state,
+                &translation,
+                &rotation,
+                weights,
+            )?;
+            let current: Vector5<f64> = Vector5::<f64>::from(&state);
+            let step: Vector5<f64> = Vector5::<f64>::from(&next) - current;
+
+            // Halve the step until it decreases the weighted pose error, since a full step can
+            //  overshoot when the pose is not reachable.
+            let mut scale: f64 = 1_f64;
+            let mut improved: bool = false;
+
+            while scale * step.magnitude() >= POSE_SOLVE_STEP_EPS {
+                let next: KinematicState = KinematicState::from(current + scale * step);
+                let next_cost: f64 =
+                    weighted_pose_error(fk, params, &next, target, weights).magnitude();
+
+                if next_cost < cost {
+                    state = next;
+                    cost = next_cost;
+                    improved = true;
+                    break;
+                }
+
+                scale *= 0.5_f64;
+            }
+
+            // Stop as soon as no step improves the pose anymore.
+            if !improved {
+                break;
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+/// Stack the translation (in the base frame) and the rotation vector (given in the base frame, and
+///  expressed in the end-effector frame with the given orientation) into a single pose error.
+pub(crate) fn pose_error_vector(
+    orientation: &Matrix3<f64>,
+    translation: &Vector3<f64>,
+    rotation: &Vector3<f64>,
+) -> Vector6<f64> {
+    let rotation: Vector3<f64> = orientation.transpose() * rotation;
+
+    Vector6::<f64>::new(
+        translation.x,
+        translation.y,
+        translation.z,
+        rotation.x,
+        rotation.y,
+        rotation.z,
+    )
+}
+
+/// Express the angular part of a geometric jacobian (given in the base frame) in the
+///  end-effector frame with the given orientation, which matches the rows of `pose_error_vector`.
+pub(crate) fn end_effector_frame_jacobian(
+    orientation: &Matrix3<f64>,
+    jacobian: &Matrix6x5<f64>,
+) -> Matrix6x5<f64> {
+    let mut jacobian: Matrix6x5<f64> = *jacobian;
+    let angular: Matrix3x5<f64> = orientation.transpose() * jacobian.fixed_rows::<3>(3);
+    jacobian.fixed_rows_mut::<3>(3).copy_from(&angular);
+
+    jacobian
+}
+
+/// Apply a joint step to the given state together with a motion in the null space of the task,
+///  which moves the joints towards the centers of their limits without disturbing the task, and
+///  clamp the resulting state into the limits.
+///
+/// The projector maps joint motions into the null space of the task jacobian (`I - J^+ J`).
+pub(crate) fn apply_limited_step(
+    params: &KinematicParameters,
+    state: &KinematicState,
+    step: &Vector5<f64>,
+    projector: &Matrix5<f64>,
+    limit_avoidance_gain: f64,
+) -> KinematicState {
+    let avoidance: Vector5<f64> =
+        -limit_avoidance_gain * (projector * params.limits.cost_gradient(state));
+
+    params.limits.clamp(&KinematicState::from(
+        Vector5::<f64>::from(state) + step + avoidance,
+    ))
+}
+
+/// Compute the weighted pose error between the end-effector of the fourth link and the target.
+fn weighted_pose_error(
+    fk: &dyn ForwardKinematicAlgorithm,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    target: &Pose,
+    weights: &PoseWeights,
+) -> Vector6<f64> {
+    let pose: Pose = fk.limb4_pose(params, state);
+
+    weights.matrix()
+        * pose_error_vector(
+            &pose.orientation,
+            &(target.position - pose.position),
+            &Rotation3::<f64>::from_matrix_unchecked(
+                target.orientation * pose.orientation.transpose(),
+            )
+            .scaled_axis(),
+        )
 }
@@ -1 +1,4 @@
 pub mod algorithms;
+pub mod reach;
+pub mod singularity;
+pub mod solver;
@@ -0,0 +1,236 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::InverseKinematicError;
+use crate::model::{JointLimit, KinematicLimits, KinematicParameters, KinematicState};
+
+/// The tolerance on the distance from the shoulder, so targets on the spheres are reachable.
+const REACH_EPS: f64 = 1e-9_f64;
+
+/// The spheres around the shoulder (the second joint) between which the end-effector of the
+///  fourth limb can reach, taking the limits of the elbow and wrist pitch joints into account.
+///
+/// The base and shoulder joints only turn the arm around the shoulder, so their limits restrict
+///  the direction of the target but not its distance. Lying between the spheres is therefore
+///  required, but not sufficient, for a target to be reachable.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReachSpheres {
+    /// The position of the shoulder, the center of both spheres.
+    pub center: Vector3<f64>,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+}
+
+impl ReachSpheres {
+    /// Compute the reach spheres of the arm with the given parameters.
+    ///
+    /// The distance from the shoulder is a function of the elbow and wrist pitch angles, of which
+    ///  the extremes lie in the corners of the limits, in the configurations in which the links
+    ///  are aligned, or where the free link of an edge of the limits aligns with the others.
+    pub fn new(params: &KinematicParameters) -> Self {
+        let elbow: (f64, f64) = Self::turn_range(&params.limits.theta_2);
+        let wrist: (f64, f64) = Self::turn_range(&params.limits.theta_3);
+        let (a, b, c) = (params.l_1, params.l_2, params.l_3 + params.l_4);
+
+        let mut candidates: Vec<(f64, f64)> = Vec::new();
+
+        // The corners, and the configurations with aligned (or folded back) links.
+        for theta_2 in [elbow.0, elbow.1, 0_f64, PI, -PI] {
+            for theta_3 in [wrist.0, wrist.1, 0_f64, PI, -PI] {
+                candidates.push((theta_2, theta_3));
+            }
+        }
+
+        // The edges on which the elbow angle is fixed, where the last link aligns with the
+        //  first two.
+        for theta_2 in [elbow.0, elbow.1] {
+            let first: Vector2<f64> = Vector2::<f64>::new(a + b * theta_2.cos(), b * theta_2.sin());
+            let angle: f64 = first.y.atan2(first.x) - theta_2;
+
+            for theta_3 in [angle, angle + PI] {
+                candidates.push((theta_2, theta_3));
+            }
+        }
+
+        // The edges on which the wrist angle is fixed, where the last two links align with the
+        //  first one.
+        for theta_3 in [wrist.0, wrist.1] {
+            let last: Vector2<f64> = Vector2::<f64>::new(b + c * theta_3.cos(), c * theta_3.sin());
+            let angle: f64 = -last.y.atan2(last.x);
+
+            for theta_2 in [angle, angle + PI] {
+                candidates.push((theta_2, theta_3));
+            }
+        }
+
+        let distances: Vec<f64> = candidates
+            .into_iter()
+            .filter_map(|(theta_2, theta_3)| {
+                Some((
+                    Self::shift_into(theta_2, elbow)?,
+                    Self::shift_into(theta_3, wrist)?,
+                ))
+            })
+            .map(|(theta_2, theta_3)| {
+                (Vector2::<f64>::new(a, 0_f64)
+                    + b * Vector2::<f64>::new(theta_2.cos(), theta_2.sin())
+                    + c * Vector2::<f64>::new((theta_2 + theta_3).cos(), (theta_2 + theta_3).sin()))
+                .magnitude()
+            })
+            .collect();
+
+        Self {
+            center: Vector3::<f64>::new(0_f64, params.l_0, 0_f64),
+            inner_radius: distances.iter().copied().fold(f64::INFINITY, f64::min),
+            outer_radius: distances.iter().copied().fold(0_f64, f64::max),
+        }
+    }
+
+    /// Check if the given position lies between the spheres.
+    pub fn contains(&self, position: &Vector3<f64>) -> bool {
+        let distance: f64 = (position - self.center).magnitude();
+
+        self.inner_radius - REACH_EPS <= distance && distance <= self.outer_radius + REACH_EPS
+    }
+
+    /// Check that the given target lies within reach of the arm with the given parameters, a
+    ///  target that is only within reach without the joint limits is reported as such.
+    ///
+    /// The spheres are derived from the parameters, so when the given forward kinematics describe
+    ///  another arm (compared at the given state and at the zero state) the target is not checked.
+    pub fn check_target(
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target: &Vector3<f64>,
+    ) -> Result<(), InverseKinematicError> {
+        if !Self::describes(fk, params, state) || Self::new(params).contains(target) {
+            return Ok(());
+        }
+
+        let unlimited: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::unlimited(),
+            ..params.clone()
+        };
+
+        if Self::new(&unlimited).contains(target) {
+            Err(InverseKinematicError::JointLimit(*target))
+        } else {
+            Err(InverseKinematicError::Unreachable(*target))
+        }
+    }
+
+    /// Check if the given forward kinematics place the end-effector where the parameters do, at
+    ///  the given state and at the zero state.
+    fn describes(
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> bool {
+        let model: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        [state.clone(), KinematicState::default()]
+            .iter()
+            .all(|state| {
+                (fk.limb4_position_vector(params, state)
+                    - model.limb4_position_vector(params, state))
+                .magnitude()
+                    <= REACH_EPS
+            })
+    }
+
+    /// Get the part of the limit that lies within a single turn.
+    fn turn_range(limit: &JointLimit) -> (f64, f64) {
+        (limit.min.max(-PI), limit.max.min(PI))
+    }
+
+    /// Shift the given angle by whole turns into the given range, if any shift lands in it.
+    fn shift_into(angle: f64, (min, max): (f64, f64)) -> Option<f64> {
+        (-2_i32..=2_i32)
+            .map(|turns| angle + turns as f64 * 2_f64 * PI)
+            .find(|angle| min <= *angle && *angle <= max)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::InverseKinematicError;
+    use crate::inverse::reach::ReachSpheres;
+    use crate::model::{JointLimit, KinematicLimits, KinematicParameters, KinematicState};
+
+    #[test]
+    pub fn spheres_bound_the_workspace() {
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        let unlimited: KinematicParameters = KinematicParameters::default();
+        let limited: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits {
+                theta_2: JointLimit::new(-0.5_f64, 1_f64),
+                theta_3: JointLimit::new(0.2_f64, 0.8_f64),
+                ..KinematicLimits::servo()
+            },
+            ..KinematicParameters::default()
+        };
+
+        // Without limits the arm reaches from the shoulder itself up to its full length.
+        let spheres: ReachSpheres = ReachSpheres::new(&unlimited);
+
+        assert!(spheres.inner_radius.abs() < 10_f64.powf(-9_f64));
+        assert!(
+            (spheres.outer_radius - (unlimited.sum_of_link_lengths() - unlimited.l_0)).abs()
+                < 10_f64.powf(-9_f64)
+        );
+
+        // With limits, every state stays between the spheres, and the spheres are tight.
+        let spheres: ReachSpheres = ReachSpheres::new(&limited);
+        let mut rng: StdRng = StdRng::seed_from_u64(0_u64);
+        let mut closest: f64 = f64::INFINITY;
+        let mut farthest: f64 = 0_f64;
+
+        for _ in 0..20_000 {
+            let state: KinematicState = limited.limits.sample(&mut rng);
+            let position: Vector3<f64> = fk_solver.limb4_position_vector(&limited, &state);
+            let distance: f64 = (position - spheres.center).magnitude();
+
+            assert!(spheres.contains(&position));
+
+            closest = closest.min(distance);
+            farthest = farthest.max(distance);
+        }
+
+        let state: KinematicState = KinematicState::default();
+
+        // The shoulder itself can only be reached by folding the arm beyond the limits.
+        assert_eq!(
+            ReachSpheres::check_target(&fk_solver, &limited, &state, &spheres.center),
+            Err(InverseKinematicError::JointLimit(spheres.center))
+        );
+        assert_eq!(
+            ReachSpheres::check_target(
+                &fk_solver,
+                &limited,
+                &state,
+                &Vector3::<f64>::new(0_f64, 60_f64, 0_f64)
+            ),
+            Err(InverseKinematicError::Unreachable(Vector3::<f64>::new(
+                0_f64, 60_f64, 0_f64
+            )))
+        );
+
+        assert!(spheres.inner_radius > 20_f64);
+        assert!(closest - spheres.inner_radius < 0.1_f64);
+        assert!(spheres.outer_radius - farthest < 0.1_f64);
+    }
+}
@@ -0,0 +1,350 @@
+use nalgebra::{DMatrix, DVector, Dyn, Matrix6x5, SVD};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::model::{KinematicParameters, KinematicState};
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum SingularityError {
+    #[error("Failed to pseudo-invert jacobian matrix, error: {0}")]
+    PseudoInvertFailure(&'static str),
+    #[error("The condition number {condition_number} of the jacobian exceeds {threshold}")]
+    IllConditioned {
+        condition_number: f64,
+        threshold: f64,
+    },
+}
+
+/// The part of the end-effector motion the diagnostics are computed for.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TaskSpace {
+    /// The linear velocity of the end-effector.
+    Position,
+    /// The angular velocity of the end-effector.
+    Orientation,
+    /// Both the linear and the angular velocity of the end-effector.
+    Pose,
+}
+
+impl TaskSpace {
+    /// Select the rows of the given geometric jacobian that belong to the task space.
+    pub fn jacobian(&self, jacobian: &Matrix6x5<f64>) -> DMatrix<f64> {
+        let (first, count) = match self {
+            TaskSpace::Position => (0_usize, 3_usize),
+            TaskSpace::Orientation => (3_usize, 3_usize),
+            TaskSpace::Pose => (0_usize, 6_usize),
+        };
+
+        DMatrix::<f64>::from_fn(count, 5, |i, j| jacobian[(first + i, j)])
+    }
+}
+
+/// The direction in which the arm is closest to a singularity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SingularDirection {
+    /// The unit direction (in the task space) the end-effector can barely move along.
+    pub task: DVector<f64>,
+    /// The unit joint motion that produces the (small) end-effector motion along the direction.
+    pub joints: DVector<f64>,
+    /// The end-effector velocity along the direction per unit of joint velocity.
+    pub singular_value: f64,
+}
+
+/// The singularity diagnostics of a jacobian, computed from its singular values.
+///
+/// Rows of the jacobian that are entirely zero (axes with a weight of zero) are ignored, and only
+///  as many singular values as the smallest dimension of the jacobian are taken into account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SingularityDiagnostics {
+    /// The Yoshikawa manipulability `sqrt(det(J J^T))`, the product of the singular values, which
+    ///  drops to zero in a singularity.
+    pub manipulability: f64,
+    /// The ratio between the largest and the smallest singular value, infinite in a singularity.
+    pub condition_number: f64,
+    pub nearest_singular_direction: SingularDirection,
+}
+
+impl SingularityDiagnostics {
+    /// Compute the diagnostics of the jacobian of the end-effector of the fourth limb in the given
+    ///  state.
+    pub fn compute(
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        task_space: TaskSpace,
+    ) -> Self {
+        Self::from_jacobian(&task_space.jacobian(&fk.limb4_jacobian(params, state)))
+    }
+
+    /// Compute the diagnostics of the given jacobian.
+    pub fn from_jacobian(jacobian: &DMatrix<f64>) -> Self {
+        let rows: Vec<usize> = (0..jacobian.nrows())
+            .filter(|i| jacobian.row(*i).iter().any(|x| *x != 0_f64))
+            .collect();
+        let task_dimension: usize = jacobian.nrows();
+        let jacobian: DMatrix<f64> = jacobian.select_rows(rows.iter());
+
+        // A jacobian without any motion is singular in every direction.
+        if jacobian.nrows() == 0_usize {
+            return Self {
+                manipulability: 0_f64,
+                condition_number: f64::INFINITY,
+                nearest_singular_direction: SingularDirection {
+                    task: DVector::<f64>::zeros(task_dimension),
+                    joints: DVector::<f64>::zeros(jacobian.ncols()),
+                    singular_value: 0_f64,
+                },
+            };
+        }
+
+        let svd: SVD<f64, Dyn, Dyn> = jacobian.svd(true, true);
+        let (u, v_t) = match (svd.u, svd.v_t) {
+            (Some(u), Some(v_t)) => (u, v_t),
+            _ => unreachable!("both singular vector matrices were requested"),
+        };
+
+        let smallest: usize = svd.singular_values.argmin().0;
+        let min: f64 = svd.singular_values[smallest];
+        let max: f64 = svd.singular_values.max();
+
+        // Scatter the task direction back onto all the rows, including the ignored ones.
+        let mut task: DVector<f64> = DVector::<f64>::zeros(task_dimension);
+
+        for (i, row) in rows.iter().enumerate() {
+            task[*row] = u[(i, smallest)];
+        }
+
+        Self {
+            manipulability: svd.singular_values.product(),
+            condition_number: if min > 0_f64 {
+                max / min
+            } else {
+                f64::INFINITY
+            },
+            nearest_singular_direction: SingularDirection {
+                task,
+                joints: v_t.row(smallest).transpose(),
+                singular_value: min,
+            },
+        }
+    }
+}
+
+/// Compute the Yoshikawa manipulability of the end-effector of the fourth limb.
+pub fn manipulability(
+    fk: &dyn ForwardKinematicAlgorithm,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    task_space: TaskSpace,
+) -> f64 {
+    SingularityDiagnostics::compute(fk, params, state, task_space).manipulability
+}
+
+/// Compute the condition number of the jacobian of the end-effector of the fourth limb.
+pub fn condition_number(
+    fk: &dyn ForwardKinematicAlgorithm,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    task_space: TaskSpace,
+) -> f64 {
+    SingularityDiagnostics::compute(fk, params, state, task_space).condition_number
+}
+
+/// Compute the direction in which the end-effector of the fourth limb is closest to a singularity.
+pub fn nearest_singular_direction(
+    fk: &dyn ForwardKinematicAlgorithm,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    task_space: TaskSpace,
+) -> SingularDirection {
+    SingularityDiagnostics::compute(fk, params, state, task_space).nearest_singular_direction
+}
+
+/// What an inverse kinematic algorithm does with a step whose jacobian is ill-conditioned.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SingularityPolicy {
+    /// Refuse the step with a `SingularityError`.
+    Refuse,
+    /// Shorten the step by the ratio between the threshold and the condition number.
+    SlowDown,
+}
+
+/// Guards the steps of an inverse kinematic algorithm against ill-conditioned jacobians.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct SingularityGuard {
+    /// The condition number above which the policy is applied.
+    pub threshold: f64,
+    pub policy: SingularityPolicy,
+}
+
+impl SingularityGuard {
+    pub fn new(threshold: f64, policy: SingularityPolicy) -> Self {
+        Self { threshold, policy }
+    }
+
+    /// Compute the factor a step taken with the given jacobian is scaled by, or refuse the step.
+    pub fn step_scale(&self, jacobian: &DMatrix<f64>) -> Result<f64, SingularityError> {
+        let condition_number: f64 =
+            SingularityDiagnostics::from_jacobian(jacobian).condition_number;
+
+        if condition_number <= self.threshold {
+            return Ok(1_f64);
+        }
+
+        match self.policy {
+            SingularityPolicy::Refuse => Err(SingularityError::IllConditioned {
+                condition_number,
+                threshold: self.threshold,
+            }),
+            SingularityPolicy::SlowDown => Ok(self.threshold / condition_number),
+        }
+    }
+}
+
+/// Compute the factor a step is scaled by under the optional guard, a missing guard never scales.
+pub(crate) fn guarded_step_scale(
+    guard: &Option<SingularityGuard>,
+    jacobian: &DMatrix<f64>,
+) -> Result<f64, SingularityError> {
+    match guard {
+        Some(guard) => guard.step_scale(jacobian),
+        None => Ok(1_f64),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{DMatrix, DVector, Vector3, Vector5};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::damped_least_squares::DampedLeastSquaresInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::InverseKinematicAlgorithm;
+    use crate::inverse::singularity::{
+        condition_number, manipulability, SingularityDiagnostics, SingularityGuard,
+        SingularityPolicy, TaskSpace,
+    };
+    use crate::model::{KinematicParameters, KinematicState};
+
+    #[test]
+    pub fn stretched_out_arm_is_singular() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        // Tilt the stretched out arm forward, so it can still move sideways and along the tilt.
+        let stretched: KinematicState = KinematicState {
+            theta_1: 0.2_f64,
+            ..KinematicState::default()
+        };
+        let bent: KinematicState = KinematicState {
+            theta_1: 0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.5_f64,
+            ..KinematicState::default()
+        };
+
+        let diagnostics: SingularityDiagnostics =
+            SingularityDiagnostics::compute(&fk_solver, &params, &stretched, TaskSpace::Position);
+
+        assert!(diagnostics.manipulability < 10_f64.powf(-6_f64));
+        assert!(diagnostics.condition_number > 10_f64.powf(6_f64));
+
+        // The end-effector cannot move along the arm, away from the shoulder.
+        let along: Vector3<f64> = (fk_solver.limb4_position_vector(&params, &stretched)
+            - Vector3::<f64>::new(0_f64, params.l_0, 0_f64))
+        .normalize();
+        let direction: DVector<f64> = diagnostics.nearest_singular_direction.task;
+
+        assert!(
+            (Vector3::<f64>::new(direction[0], direction[1], direction[2])
+                .dot(&along)
+                .abs()
+                - 1_f64)
+                .abs()
+                < 10_f64.powf(-6_f64)
+        );
+
+        // Bending the arm moves it away from the singularity.
+        assert!(manipulability(&fk_solver, &params, &bent, TaskSpace::Position) > 10_f64);
+        assert!(condition_number(&fk_solver, &params, &bent, TaskSpace::Position) < 100_f64);
+    }
+
+    #[test]
+    pub fn ignores_unweighted_rows() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let state: KinematicState = KinematicState {
+            theta_1: 0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.5_f64,
+            ..KinematicState::default()
+        };
+
+        let mut jacobian: DMatrix<f64> = TaskSpace::Pose.jacobian(
+            &AnalyticalForwardKinematicAlgorithm::default().limb4_jacobian(&params, &state),
+        );
+        let position: SingularityDiagnostics =
+            SingularityDiagnostics::from_jacobian(&jacobian.rows(0, 3).into_owned());
+
+        // Zeroing the orientation rows leaves the diagnostics of the position.
+        jacobian.rows_mut(3, 3).fill(0_f64);
+        let diagnostics: SingularityDiagnostics = SingularityDiagnostics::from_jacobian(&jacobian);
+
+        assert!(
+            (diagnostics.condition_number - position.condition_number).abs() < 10_f64.powf(-9_f64)
+        );
+        assert_eq!(diagnostics.nearest_singular_direction.task.len(), 6_usize);
+    }
+
+    #[test]
+    pub fn guard_refuses_and_slows_down() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let guard = |policy: SingularityPolicy| SingularityGuard::new(1000_f64, policy);
+        let delta: Vector3<f64> = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+
+        let bent: KinematicState = KinematicState {
+            theta_1: 0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.5_f64,
+            ..KinematicState::default()
+        };
+        let stretched: KinematicState = KinematicState {
+            theta_1: 1e-5_f64,
+            ..KinematicState::default()
+        };
+
+        let refusing: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default()
+                .with_singularity_guard(guard(SingularityPolicy::Refuse));
+
+        assert!(refusing
+            .translate_limb4_end_effector(&fk_solver, &params, &bent, &delta)
+            .is_ok());
+        assert!(refusing
+            .translate_limb4_end_effector(&fk_solver, &params, &stretched, &delta)
+            .is_err());
+
+        // The plain pseudo-inverse takes an enormous step close to the singularity, the slowed
+        //  down one stays small.
+        let step = |ik_solver: &HeuristicInverseKinematicAlgorithm| -> f64 {
+            (Vector5::<f64>::from(
+                &ik_solver
+                    .translate_limb4_end_effector(&fk_solver, &params, &stretched, &delta)
+                    .unwrap(),
+            ) - Vector5::<f64>::from(&stretched))
+            .magnitude()
+        };
+
+        assert!(step(&HeuristicInverseKinematicAlgorithm::default()) > 1000_f64);
+        assert!(
+            step(
+                &HeuristicInverseKinematicAlgorithm::default()
+                    .with_singularity_guard(guard(SingularityPolicy::SlowDown))
+            ) < 1_f64
+        );
+    }
+}
@@ -0,0 +1,561 @@
+use nalgebra::{Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{
+    pose_error_vector, InverseKinematicAlgorithm, InverseKinematicError, PoseWeights,
+};
+use crate::inverse::reach::ReachSpheres;
+use crate::model::{KinematicParameters, KinematicState, Pose};
+
+/// The way an iterative solve ended.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConvergenceStatus {
+    /// The residual dropped below the tolerance.
+    Converged,
+    /// The residual stopped changing, or the maximum number of iterations was reached.
+    Stalled,
+    /// The residual kept growing, or became non-finite.
+    Diverged,
+    /// The residual stopped changing while a joint rests against its limit, so the target can
+    ///  likely only be reached outside of the joint limits.
+    JointLimited,
+}
+
+/// The report of an iterative solve.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolverReport {
+    /// The number of inverse kinematic steps that have been taken.
+    pub iterations: usize,
+    /// The residual of the returned state.
+    pub residual: f64,
+    /// The way the solve ended.
+    pub status: ConvergenceStatus,
+}
+
+impl SolverReport {
+    /// Check if the solve converged.
+    pub fn converged(&self) -> bool {
+        self.status == ConvergenceStatus::Converged
+    }
+
+    /// Turn the report into a `NotConverged` error when the solve did not converge.
+    pub fn ensure_converged(self) -> Result<Self, InverseKinematicError> {
+        if !self.converged() {
+            return Err(InverseKinematicError::NotConverged(self));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Iteratively drives the end-effector towards an absolute target, by repeatedly computing the
+///  remaining error with the forward kinematics and taking an inverse kinematic step towards it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IterativeSolver {
+    /// The residual below which the target is considered to be reached.
+    pub tolerance: f64,
+    /// The maximum number of inverse kinematic steps.
+    pub max_iterations: usize,
+    /// The fraction of the remaining error that is requested in every step.
+    pub step_size: f64,
+    /// The relative change of the residual below which a step is considered to make no progress.
+    pub stall_tolerance: f64,
+    /// The number of consecutive steps without progress after which the solve stalls.
+    pub stall_iterations: usize,
+    /// The number of consecutive steps with a growing residual after which the solve diverges.
+    pub divergence_iterations: usize,
+    /// The distance from a joint limit within which a stalled solve is considered to be limited.
+    pub limit_distance: f64,
+    /// Whether targets outside of the reach spheres are refused before solving.
+    pub check_reach: bool,
+}
+
+impl Default for IterativeSolver {
+    fn default() -> Self {
+        Self {
+            tolerance: 10_f64.powf(-4_f64),
+            max_iterations: 100_usize,
+            step_size: 1_f64,
+            stall_tolerance: 10_f64.powf(-6_f64),
+            stall_iterations: 5_usize,
+            divergence_iterations: 5_usize,
+            limit_distance: 10_f64.powf(-3_f64),
+            check_reach: true,
+        }
+    }
+}
+
+impl IterativeSolver {
+    pub fn new(tolerance: f64, max_iterations: usize, step_size: f64) -> Self {
+        Self {
+            tolerance,
+            max_iterations,
+            step_size,
+            ..Self::default()
+        }
+    }
+
+    /// Solve for the state in which the end-effector of the fourth limb reaches the target
+    ///  position, the residual is the distance between the end-effector and the target.
+    ///
+    /// The returned state is the one with the smallest residual seen during the solve, targets
+    ///  outside of the reach spheres are refused up front (unless disabled).
+    pub fn solve_limb4_position(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        ik: &dyn InverseKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target: &Vector3<f64>,
+    ) -> Result<(KinematicState, SolverReport), InverseKinematicError> {
+        if self.check_reach {
+            ReachSpheres::check_target(fk, params, state, target)?;
+        }
+
+        let delta = |state: &KinematicState| -> Vector3<f64> {
+            target - fk.limb4_position_vector(params, state)
+        };
+
+        self.solve(
+            params,
+            state,
+            |state| delta(state).magnitude(),
+            |state| {
+                ik.translate_limb4_end_effector(fk, params, state, &(delta(state) * self.step_size))
+            },
+        )
+    }
+
+    /// Solve for the state in which the end-effector of the fourth limb reaches the target pose,
+    ///  the residual is the weighted norm of the translation and the rotation vector towards it.
+    ///
+    /// The returned state is the one with the smallest residual seen during the solve.
+    pub fn solve_limb4_pose(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        ik: &dyn InverseKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target: &Pose,
+        weights: &PoseWeights,
+    ) -> Result<(KinematicState, SolverReport), InverseKinematicError> {
+        // The target position can only be refused when it is fully weighted.
+        if self.check_reach && weights.position.iter().all(|weight| *weight != 0_f64) {
+            ReachSpheres::check_target(fk, params, state, &target.position)?;
+        }
+
+        let delta = |state: &KinematicState| -> (Pose, Vector3<f64>, Vector3<f64>) {
+            let pose: Pose = fk.limb4_pose(params, state);
+            let translation: Vector3<f64> = target.position - pose.position;
+            let rotation: Vector3<f64> = Rotation3::<f64>::from_matrix_unchecked(
+                target.orientation * pose.orientation.transpose(),
+            )
+            .scaled_axis();
+
+            (pose, translation, rotation)
+        };
+
+        self.solve(
+            params,
+            state,
+            |state| {
+                let (pose, translation, rotation) = delta(state);
+
+                (weights.matrix() * pose_error_vector(&pose.orientation, &translation, &rotation))
+                    .magnitude()
+            },
+            |state| {
+                let (_, translation, rotation) = delta(state);
+
+                ik.transform_limb4_end_effector(
+                    fk,
+                    params,
+                    state,
+                    &(translation * self.step_size),
+                    &(rotation * self.step_size),
+                    weights,
+                )
+            },
+        )
+    }
+
+    /// Run the iteration with the given residual and step functions.
+    fn solve<R, S>(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        residual: R,
+        mut step: S,
+    ) -> Result<(KinematicState, SolverReport), InverseKinematicError>
+    where
+        R: Fn(&KinematicState) -> f64,
+        S: FnMut(&KinematicState) -> Result<KinematicState, InverseKinematicError>,
+    {
+        let mut state: KinematicState = state.clone();
+        let mut previous_residual: f64 = residual(&state);
+        let mut best: (KinematicState, f64) = (state.clone(), previous_residual);
+        let mut iterations_without_progress: usize = 0_usize;
+        let mut iterations_with_growth: usize = 0_usize;
+        let mut iterations: usize = 0_usize;
+
+        let status: ConvergenceStatus = loop {
+            let current_residual: f64 = residual(&state);
+
+            // Keep track of the best state, and of how long the residual has been stagnating or
+            //  growing.
+            if current_residual < best.1 {
+                best = (state.clone(), current_residual);
+            }
+
+            if iterations > 0_usize
+                && (current_residual - previous_residual).abs()
+                    <= self.stall_tolerance * previous_residual
+            {
+                iterations_without_progress += 1_usize;
+            } else {
+                iterations_without_progress = 0_usize;
+            }
+
+            if current_residual > previous_residual {
+                iterations_with_growth += 1_usize;
+            } else {
+                iterations_with_growth = 0_usize;
+            }
+
+            previous_residual = current_residual;
+
+            if current_residual < self.tolerance {
+                break ConvergenceStatus::Converged;
+            }
+
+            if !current_residual.is_finite() || iterations_with_growth >= self.divergence_iterations
+            {
+                break ConvergenceStatus::Diverged;
+            }
+
+            if iterations_without_progress >= self.stall_iterations
+                || iterations >= self.max_iterations
+            {
+                break ConvergenceStatus::Stalled;
+            }
+
+            state = step(&state)?;
+            iterations += 1_usize;
+        };
+
+        let (state, residual) = best;
+
+        // A stalled solve which ends against a joint limit is limited by it.
+        let status: ConvergenceStatus = if status == ConvergenceStatus::Stalled
+            && params.limits.is_near_limit(&state, self.limit_distance)
+        {
+            ConvergenceStatus::JointLimited
+        } else {
+            status
+        };
+
+        Ok((
+            state,
+            SolverReport {
+                iterations,
+                residual,
+                status,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::DenavitHartenbergForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::damped_least_squares::DampedLeastSquaresInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::{InverseKinematicAlgorithm, InverseKinematicError};
+    use crate::inverse::solver::{ConvergenceStatus, IterativeSolver, SolverReport};
+    use crate::model::{JointLimit, KinematicLimits, KinematicParameters, KinematicState};
+
+    #[test]
+    pub fn converges() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-4_f64), 100_usize, 1_f64);
+
+        let target: Vector3<f64> = Vector3::<f64>::new(2_f64, 48_f64, 2_f64);
+
+        let (state, report): (KinematicState, SolverReport) = solver
+            .solve_limb4_position(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &KinematicState::default(),
+                &target,
+            )
+            .unwrap();
+
+        assert_eq!(report.status, ConvergenceStatus::Converged);
+        assert!(report.iterations > 0_usize);
+        assert!(
+            (fk_solver.limb4_position_vector(&params, &state) - target).magnitude()
+                < 10_f64.powf(-4_f64)
+        );
+    }
+
+    #[test]
+    pub fn converges_with_given_model() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        // The table describes an arm with other link lengths than the parameters, so the steps
+        //  must take their jacobian from the table for the solve to converge.
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_0: 4_f64,
+                l_1: 20_f64,
+                l_2: 3_f64,
+                l_3: 10_f64,
+                l_4: 10_f64,
+                ..KinematicParameters::default()
+            });
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-4_f64), 100_usize, 1_f64);
+
+        // Take the target from a known state of the table, so it's reachable.
+        let target: Vector3<f64> = table.limb4_position_vector(
+            &params,
+            &KinematicState {
+                theta_0: 0.5_f64,
+                theta_1: 0.6_f64,
+                theta_2: 0.4_f64,
+                theta_3: -0.3_f64,
+                theta_4: 0.2_f64,
+            },
+        );
+
+        let ik_solvers: [&dyn InverseKinematicAlgorithm; 2] = [
+            &HeuristicInverseKinematicAlgorithm::default(),
+            &DampedLeastSquaresInverseKinematicAlgorithm::default(),
+        ];
+
+        for ik_solver in ik_solvers {
+            let (state, report): (KinematicState, SolverReport) = solver
+                .solve_limb4_position(
+                    &table,
+                    ik_solver,
+                    &params,
+                    &KinematicState {
+                        theta_1: 0.2_f64,
+                        theta_2: 0.2_f64,
+                        theta_3: 0.2_f64,
+                        ..KinematicState::default()
+                    },
+                    &target,
+                )
+                .unwrap();
+
+            assert_eq!(report.status, ConvergenceStatus::Converged);
+            assert!(
+                (table.limb4_position_vector(&params, &state) - target).magnitude()
+                    < 10_f64.powf(-4_f64)
+            );
+        }
+    }
+
+    #[test]
+    pub fn reach_of_given_model() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let table: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_1: 30_f64,
+                ..KinematicParameters::default()
+            });
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-4_f64), 100_usize, 1_f64);
+        let start: KinematicState = KinematicState {
+            theta_1: 0.2_f64,
+            theta_2: 0.2_f64,
+            theta_3: 0.2_f64,
+            ..KinematicState::default()
+        };
+
+        // The target lies beyond the reach of the arm of the parameters, but not of the table.
+        let target: Vector3<f64> = table.limb4_position_vector(
+            &params,
+            &KinematicState {
+                theta_1: 0.3_f64,
+                ..KinematicState::default()
+            },
+        );
+
+        assert_eq!(
+            solver.solve_limb4_position(
+                &AnalyticalForwardKinematicAlgorithm::default(),
+                &DampedLeastSquaresInverseKinematicAlgorithm::default(),
+                &params,
+                &start,
+                &target,
+            ),
+            Err(InverseKinematicError::Unreachable(target))
+        );
+
+        let (state, _): (KinematicState, SolverReport) = solver
+            .solve_limb4_position(
+                &table,
+                &DampedLeastSquaresInverseKinematicAlgorithm::default(),
+                &params,
+                &start,
+                &target,
+            )
+            .unwrap();
+
+        assert!(
+            (table.limb4_position_vector(&params, &state) - target).magnitude()
+                < 10_f64.powf(-3_f64)
+        );
+    }
+
+    #[test]
+    pub fn stalls_on_unreachable_target() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+        let solver: IterativeSolver = IterativeSolver {
+            check_reach: false,
+            ..IterativeSolver::new(10_f64.powf(-4_f64), 500_usize, 1_f64)
+        };
+
+        // The target lies out of reach, so the residual can never drop below the tolerance.
+        let (_, report): (KinematicState, SolverReport) = solver
+            .solve_limb4_position(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &KinematicState {
+                    theta_1: 0.2_f64,
+                    ..KinematicState::default()
+                },
+                &Vector3::<f64>::new(60_f64, 10_f64, 0_f64),
+            )
+            .unwrap();
+
+        assert_eq!(report.status, ConvergenceStatus::Stalled);
+        assert!((report.residual - 20_f64).abs() < 10_f64.powf(-2_f64));
+    }
+
+    #[test]
+    pub fn refuses_unreachable_target() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let solver: IterativeSolver = IterativeSolver::default();
+
+        let target: Vector3<f64> = Vector3::<f64>::new(60_f64, 10_f64, 0_f64);
+
+        assert_eq!(
+            solver
+                .solve_limb4_position(
+                    &fk_solver,
+                    &ik_solver,
+                    &params,
+                    &KinematicState::default(),
+                    &target,
+                )
+                .unwrap_err(),
+            InverseKinematicError::Unreachable(target)
+        );
+
+        // A reachable target which is not reached in time does not converge.
+        let (_, report): (KinematicState, SolverReport) =
+            IterativeSolver::new(10_f64.powf(-4_f64), 1_usize, 1_f64)
+                .solve_limb4_position(
+                    &fk_solver,
+                    &ik_solver,
+                    &params,
+                    &KinematicState::default(),
+                    &Vector3::<f64>::new(2_f64, 48_f64, 2_f64),
+                )
+                .unwrap();
+
+        assert!(matches!(
+            report.ensure_converged(),
+            Err(InverseKinematicError::NotConverged(_))
+        ));
+    }
+
+    #[test]
+    pub fn diverges_with_overshooting_steps() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        // Requesting three times the remaining error makes every step overshoot.
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-4_f64), 100_usize, 3_f64);
+
+        let (_, report): (KinematicState, SolverReport) = solver
+            .solve_limb4_position(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &KinematicState {
+                    theta_1: 0.2_f64,
+                    ..KinematicState::default()
+                },
+                &Vector3::<f64>::new(10_f64, 30_f64, 0_f64),
+            )
+            .unwrap();
+
+        assert_eq!(report.status, ConvergenceStatus::Diverged);
+    }
+
+    #[test]
+    pub fn limited_by_joint_limits() {
+        // Keep the first link of the pitch chain almost upright.
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits {
+                theta_1: JointLimit::new(-0.1_f64, 0.1_f64),
+                ..KinematicLimits::unlimited()
+            },
+            ..KinematicParameters::default()
+        };
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: DampedLeastSquaresInverseKinematicAlgorithm =
+            DampedLeastSquaresInverseKinematicAlgorithm::default();
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-4_f64), 500_usize, 1_f64);
+
+        // The target lies within reach of the arm, but not without leaning the first link over.
+        let target: Vector3<f64> = Vector3::<f64>::new(0_f64, -15_f64, -10_f64);
+
+        let (state, report): (KinematicState, SolverReport) = solver
+            .solve_limb4_position(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &KinematicState {
+                    theta_2: 0.2_f64,
+                    ..KinematicState::default()
+                },
+                &target,
+            )
+            .unwrap();
+
+        assert_eq!(report.status, ConvergenceStatus::JointLimited);
+        assert!(params.limits.contains(&state));
+        assert!(report.residual > 1_f64);
+    }
+}
@@ -1,22 +1,675 @@
+pub mod collision;
 pub mod forward;
 pub mod inverse;
 pub mod model;
 pub mod motion;
+pub mod orientation;
+pub mod planning;
+pub mod verification;
+pub mod workspace;
 
-use tokio::sync::watch;
+use std::collections::VecDeque;
+use std::time::Duration;
 
+use nalgebra::Vector5;
+use thiserror::Error;
+use tokio::select;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{interval, Instant, Interval, MissedTickBehavior};
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{InverseKinematicAlgorithm, InverseKinematicError, PoseWeights};
+use crate::inverse::solver::IterativeSolver;
+use crate::model::{KinematicParameters, KinematicState, Pose};
+use crate::motion::profile::{JointDynamicsLimits, JointProfile, ProfileShape};
+use crate::motion::{Motion, MotionError, MotionTarget};
+
+/// The number of operations that can be queued before sending one waits.
+const ARM_OP_CHANNEL_CAPACITY: usize = 32_usize;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ArmError {
+    #[error("The move was stopped before it completed")]
+    Stopped,
+    #[error("The arm task is no longer running")]
+    TaskClosed,
+    #[error("Invalid joint index {0}")]
+    InvalidJoint(usize),
+    #[error("Angle {angle} lies outside of the limits of joint {joint}")]
+    JointLimit { joint: usize, angle: f64 },
+    #[error("Failed to solve the inverse kinematics, error: {0}")]
+    Inverse(#[from] InverseKinematicError),
+    #[error("Invalid motion, error: {0}")]
+    Motion(#[from] MotionError),
+}
+
+/// The sender through which the task reports the completion of a move, with the state it ended
+///  in.
+pub type ArmOpResponder = oneshot::Sender<Result<KinematicState, ArmError>>;
+
+/// The operations the arm task accepts, moves are queued and run one after the other.
 pub enum ArmOp {
+    /// Move a single joint to the given angle.
+    MoveJoint {
+        joint: usize,
+        angle: f64,
+        done: ArmOpResponder,
+    },
+    /// Move the end-effector of the fourth limb to the given pose, through joint space.
+    MoveToPose {
+        pose: Pose,
+        weights: PoseWeights,
+        done: ArmOpResponder,
+    },
+    /// Run the given motion, by solving the inverse kinematics of its target every period.
+    ///
+    /// The arm first moves through joint space to the start of the motion, and then follows it
+    ///  no faster than the joint velocity limits allow.
+    RunMotion {
+        motion: Box<dyn Motion>,
+        done: ArmOpResponder,
+    },
+    /// Stop the running move and drop the queued ones, all of which complete as stopped.
+    Stop { done: ArmOpResponder },
+}
 
+/// The configuration of the arm task.
+#[derive(Debug, Clone)]
+pub struct ArmConfig {
+    pub params: KinematicParameters,
+    /// The joint limits of moves through joint space, of which the velocity limits also bound the
+    ///  following of motions.
+    pub limits: JointDynamicsLimits,
+    /// The profile of moves through joint space.
+    pub shape: ProfileShape,
+    /// The interval at which the state is advanced and published, a move is always advanced to
+    ///  the time that elapsed since its start so delayed periods do not slow it down.
+    pub period: Duration,
+    /// The solver used to move to poses and to follow motions.
+    pub solver: IterativeSolver,
 }
 
-pub struct ArmTask {
+impl Default for ArmConfig {
+    fn default() -> Self {
+        Self {
+            params: KinematicParameters::default(),
+            limits: JointDynamicsLimits::uniform(1_f64, 2_f64, 10_f64),
+            shape: ProfileShape::Trapezoidal,
+            period: Duration::from_millis(20_u64),
+            solver: IterativeSolver::default(),
+        }
+    }
+}
+
+/// The way the running move advances the state.
+enum Playback {
+    Profile(JointProfile),
+    /// A motion, preceded by the move to the state that reaches its start.
+    Motion {
+        approach: JointProfile,
+        motion: Box<dyn Motion>,
+    },
+}
 
+/// The move that is currently being run.
+struct ActiveMove {
+    playback: Playback,
+    /// The instant at which the move started.
+    started: Instant,
+    /// The time since the start at which the state was last advanced.
+    advanced: f64,
+    done: ArmOpResponder,
+}
+
+/// The task that owns the state of the arm, which runs the operations sent through its handles
+///  and publishes the state every period while moving.
+pub struct ArmTask {
+    config: ArmConfig,
+    fk: Box<dyn ForwardKinematicAlgorithm + Send>,
+    ik: Box<dyn InverseKinematicAlgorithm + Send>,
+    state: KinematicState,
+    op_rx: mpsc::Receiver<ArmOp>,
+    state_tx: watch::Sender<KinematicState>,
+    queue: VecDeque<ArmOp>,
+    active: Option<ActiveMove>,
 }
 
 impl ArmTask {
+    /// Create a new arm task starting in the given state, and the handle to control it.
+    pub fn new(
+        config: ArmConfig,
+        fk: Box<dyn ForwardKinematicAlgorithm + Send>,
+        ik: Box<dyn InverseKinematicAlgorithm + Send>,
+        state: KinematicState,
+    ) -> (ArmTask, ArmHandle) {
+        let (op_tx, op_rx) = mpsc::channel::<ArmOp>(ARM_OP_CHANNEL_CAPACITY);
+        let (state_tx, state_rx) = watch::channel::<KinematicState>(state.clone());
+
+        let arm_task: ArmTask = ArmTask {
+            config,
+            fk,
+            ik,
+            state,
+            op_rx,
+            state_tx,
+            queue: VecDeque::new(),
+            active: None,
+        };
+        let arm_handle: ArmHandle = ArmHandle { op_tx, state_rx };
+
+        (arm_task, arm_handle)
+    }
+
+    /// Run the task until all of its handles have been dropped.
+    pub async fn run(&mut self) {
+        let mut ticker: Interval = interval(self.config.period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                op = self.op_rx.recv() => match op {
+                    Some(op) => self.accept(op),
+                    None => break,
+                },
+                _ = ticker.tick(), if self.active.is_some() => self.advance(),
+            }
+
+            // Start the next queued move as soon as the arm is idle.
+            while self.active.is_none() {
+                match self.queue.pop_front() {
+                    Some(op) => self.start(op),
+                    None => break,
+                }
+            }
+        }
+
+        self.stop_all();
+    }
+
+    /// Accept an operation, a stop is handled immediately while moves are queued.
+    fn accept(&mut self, op: ArmOp) {
+        match op {
+            ArmOp::Stop { done } => {
+                self.stop_all();
+                let _ = done.send(Ok(self.state.clone()));
+            }
+            op => self.queue.push_back(op),
+        }
+    }
+
+    /// Complete the running and queued moves as stopped.
+    fn stop_all(&mut self) {
+        if let Some(active) = self.active.take() {
+            let _ = active.done.send(Err(ArmError::Stopped));
+        }
+
+        for op in self.queue.drain(..) {
+            let done: ArmOpResponder = match op {
+                ArmOp::MoveJoint { done, .. }
+                | ArmOp::MoveToPose { done, .. }
+                | ArmOp::RunMotion { done, .. }
+                | ArmOp::Stop { done } => done,
+            };
+            let _ = done.send(Err(ArmError::Stopped));
+        }
+    }
+
+    /// Plan the given move from the current state and make it the running one, a move that can
+    ///  not be planned completes with its error right away.
+    fn start(&mut self, op: ArmOp) {
+        let (playback, done) = match op {
+            ArmOp::MoveJoint { joint, angle, done } => (self.plan_joint_move(joint, angle), done),
+            ArmOp::MoveToPose {
+                pose,
+                weights,
+                done,
+            } => (self.plan_pose_move(&pose, &weights), done),
+            ArmOp::RunMotion { motion, done } => (self.plan_motion(motion), done),
+            ArmOp::Stop { done } => {
+                let _ = done.send(Ok(self.state.clone()));
+                return;
+            }
+        };
+
+        match playback {
+            Ok(playback) => {
+                self.active = Some(ActiveMove {
+                    playback,
+                    started: Instant::now(),
+                    advanced: 0_f64,
+                    done,
+                })
+            }
+            Err(error) => {
+                let _ = done.send(Err(error));
+            }
+        }
+    }
+
+    fn plan_joint_move(&self, joint: usize, angle: f64) -> Result<Playback, ArmError> {
+        let limits = self.config.params.limits.as_array();
+        let limit = limits.get(joint).ok_or(ArmError::InvalidJoint(joint))?;
+
+        if !limit.contains(angle) {
+            return Err(ArmError::JointLimit { joint, angle });
+        }
+
+        let mut target: Vector5<f64> = Vector5::<f64>::from(&self.state);
+        target[joint] = angle;
+
+        self.plan_profile(&KinematicState::from(target))
+    }
+
+    fn plan_pose_move(&self, pose: &Pose, weights: &PoseWeights) -> Result<Playback, ArmError> {
+        let (target, report) = self.config.solver.solve_limb4_pose(
+            self.fk.as_ref(),
+            self.ik.as_ref(),
+            &self.config.params,
+            &self.state,
+            pose,
+            weights,
+        )?;
+        report.ensure_converged()?;
+
+        self.plan_profile(&target)
+    }
+
+    fn plan_profile(&self, target: &KinematicState) -> Result<Playback, ArmError> {
+        Ok(Playback::Profile(JointProfile::new(
+            &self.state,
+            target,
+            &self.config.limits,
+            self.config.shape,
+        )?))
+    }
+
+    /// Plan the move to the state that reaches the start of the motion, so the motion does not
+    ///  begin with a jump when the arm is somewhere else.
+    fn plan_motion(&self, motion: Box<dyn Motion>) -> Result<Playback, ArmError> {
+        let start: KinematicState = self.solve_target(&motion.start())?;
+
+        Ok(Playback::Motion {
+            approach: JointProfile::new(
+                &self.state,
+                &start,
+                &self.config.limits,
+                self.config.shape,
+            )?,
+            motion,
+        })
+    }
+
+    /// Advance the running move to the time that elapsed since its start, publish the new state,
+    ///  and complete the move when it has ended.
+    ///
+    /// Missed ticks are delayed rather than bursted, since a late tick already catches up with
+    ///  the elapsed time. A motion that asks for faster joints than the limits allow is followed
+    ///  at the limits, and completes once the arm has caught up with its end.
+    fn advance(&mut self) {
+        let Some(mut active) = self.active.take() else {
+            return;
+        };
+
+        let elapsed: f64 = active.started.elapsed().as_secs_f64();
+        let period: f64 = elapsed - active.advanced;
+        active.advanced = elapsed;
+
+        let (next, duration) = match &active.playback {
+            Playback::Profile(profile) => {
+                (Ok((profile.state_at(elapsed), true)), profile.duration())
+            }
+            Playback::Motion { approach, motion } if elapsed < approach.duration() => (
+                Ok((approach.state_at(elapsed), true)),
+                approach.duration() + motion.duration(),
+            ),
+            Playback::Motion { approach, motion } => (
+                self.solve_target(&motion.target_at(elapsed - approach.duration()))
+                    .map(|target| self.limit_velocity(&target, period)),
+                approach.duration() + motion.duration(),
+            ),
+        };
+
+        let reached: bool = match next {
+            Ok((next, reached)) => {
+                self.state = next;
+                self.state_tx.send_replace(self.state.clone());
+                reached
+            }
+            Err(error) => {
+                let _ = active.done.send(Err(error));
+                return;
+            }
+        };
+
+        if elapsed >= duration && reached {
+            let _ = active.done.send(Ok(self.state.clone()));
+        } else {
+            self.active = Some(active);
+        }
+    }
+
+    /// Move from the current state towards the given one, as far as the joint velocity limits
+    ///  allow within the given period, without changing the direction in joint space. Returns
+    ///  whether the given state was reached.
+    fn limit_velocity(&self, target: &KinematicState, period: f64) -> (KinematicState, bool) {
+        let current: Vector5<f64> = Vector5::<f64>::from(&self.state);
+        let delta: Vector5<f64> = Vector5::<f64>::from(target) - current;
+
+        let scale: f64 = self
+            .config
+            .limits
+            .velocity
+            .iter()
+            .zip(delta.iter())
+            .filter(|(_, distance)| **distance != 0_f64)
+            .map(|(limit, distance)| limit * period / distance.abs())
+            .fold(1_f64, f64::min);
 
+        (
+            KinematicState::from(current + delta * scale),
+            scale >= 1_f64,
+        )
+    }
+
+    /// Solve for the state that reaches the target of a motion, starting from the current state,
+    ///  fails when the solve does not converge so the arm is never driven to a stalled or
+    ///  diverged state.
+    fn solve_target(&self, target: &MotionTarget) -> Result<KinematicState, ArmError> {
+        let (state, report) = match target.orientation {
+            Some(orientation) => self.config.solver.solve_limb4_pose(
+                self.fk.as_ref(),
+                self.ik.as_ref(),
+                &self.config.params,
+                &self.state,
+                &Pose::new(target.position, orientation),
+                &PoseWeights::default(),
+            )?,
+            None => self.config.solver.solve_limb4_position(
+                self.fk.as_ref(),
+                self.ik.as_ref(),
+                &self.config.params,
+                &self.state,
+                &target.position,
+            )?,
+        };
+        report.ensure_converged()?;
+
+        Ok(state)
+    }
 }
 
+/// The handle to an arm task, every move resolves once the arm has completed it.
+#[derive(Clone)]
 pub struct ArmHandle {
+    op_tx: mpsc::Sender<ArmOp>,
+    state_rx: watch::Receiver<KinematicState>,
+}
+
+impl ArmHandle {
+    /// Get the current state of the arm.
+    pub fn state(&self) -> KinematicState {
+        self.state_rx.borrow().clone()
+    }
+
+    /// Subscribe to the state of the arm, which is published every period while moving.
+    pub fn subscribe(&self) -> watch::Receiver<KinematicState> {
+        self.state_rx.clone()
+    }
+
+    /// Move a single joint to the given angle.
+    pub async fn move_joint(&self, joint: usize, angle: f64) -> Result<KinematicState, ArmError> {
+        self.request(|done| ArmOp::MoveJoint { joint, angle, done })
+            .await
+    }
+
+    /// Move the end-effector of the fourth limb to the given pose, through joint space.
+    pub async fn move_to_pose(
+        &self,
+        pose: Pose,
+        weights: PoseWeights,
+    ) -> Result<KinematicState, ArmError> {
+        self.request(|done| ArmOp::MoveToPose {
+            pose,
+            weights,
+            done,
+        })
+        .await
+    }
+
+    /// Run the given motion.
+    pub async fn run_motion<M: Motion + 'static>(
+        &self,
+        motion: M,
+    ) -> Result<KinematicState, ArmError> {
+        self.request(|done| ArmOp::RunMotion {
+            motion: Box::new(motion),
+            done,
+        })
+        .await
+    }
+
+    /// Stop the running move and drop the queued ones, resolves with the state the arm stopped
+    ///  in.
+    pub async fn stop(&self) -> Result<KinematicState, ArmError> {
+        self.request(|done| ArmOp::Stop { done }).await
+    }
+
+    /// Send the operation built around a responder, and wait for its response.
+    async fn request<F: FnOnce(ArmOpResponder) -> ArmOp>(
+        &self,
+        op: F,
+    ) -> Result<KinematicState, ArmError> {
+        let (done_tx, done_rx) = oneshot::channel::<Result<KinematicState, ArmError>>();
+
+        self.op_tx
+            .send(op(done_tx))
+            .await
+            .map_err(|_| ArmError::TaskClosed)?;
+
+        done_rx.await.map_err(|_| ArmError::TaskClosed)?
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use nalgebra::{Vector3, Vector5};
+    use tokio::select;
+    use tokio::sync::watch;
+    use tokio::task::JoinHandle;
+    use tokio::time::Instant;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::PoseWeights;
+    use crate::model::{KinematicState, Pose};
+    use crate::motion::circular::{ArcDirection, CircularMotion};
+    use crate::motion::CurveMotion;
+    use crate::{ArmConfig, ArmError, ArmHandle, ArmTask};
+
+    fn spawn_arm() -> (JoinHandle<()>, ArmHandle) {
+        let (mut task, handle) = ArmTask::new(
+            ArmConfig {
+                period: Duration::from_millis(5_u64),
+                ..ArmConfig::default()
+            },
+            Box::new(AnalyticalForwardKinematicAlgorithm::default()),
+            Box::new(HeuristicInverseKinematicAlgorithm::default()),
+            KinematicState::default(),
+        );
+
+        (tokio::spawn(async move { task.run().await }), handle)
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn moves_and_publishes() {
+        let (task, handle) = spawn_arm();
+        let mut state_rx: watch::Receiver<KinematicState> = handle.subscribe();
+
+        // Moving a joint resolves once the move has completed, after publishing the states on
+        //  the way there.
+        let state: KinematicState = handle.move_joint(1_usize, 0.3_f64).await.unwrap();
+
+        assert!((state.theta_1 - 0.3_f64).abs() < 10_f64.powf(-12_f64));
+        assert!(state_rx.has_changed().unwrap());
+        assert_eq!(*state_rx.borrow_and_update(), state);
+        assert_eq!(handle.state(), state);
 
-}
\ No newline at end of file
+        assert_eq!(
+            handle.move_joint(7_usize, 0_f64).await,
+            Err(ArmError::InvalidJoint(7_usize))
+        );
+
+        // Moving to the pose of another state ends in that pose.
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let target: KinematicState = KinematicState {
+            theta_0: 0.2_f64,
+            theta_1: 0.4_f64,
+            theta_2: 1_f64,
+            ..KinematicState::default()
+        };
+        let pose: Pose = fk_solver.limb4_pose(&ArmConfig::default().params, &target);
+        let state: KinematicState = handle
+            .move_to_pose(pose.clone(), PoseWeights::default())
+            .await
+            .unwrap();
+
+        assert!(
+            (fk_solver.limb4_position_vector(&ArmConfig::default().params, &state) - pose.position)
+                .magnitude()
+                < 10_f64.powf(-3_f64)
+        );
+
+        // Running a short arc ends on its end.
+        let arc: CircularMotion = CircularMotion::arc(
+            pose.position - Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+            1_f64,
+            Vector3::<f64>::y(),
+            0_f64,
+            1_f64,
+            ArcDirection::CounterClockwise,
+        )
+        .unwrap();
+        let end: Vector3<f64> = arc.position_at(1_f64);
+        let state: KinematicState = handle
+            .run_motion(CurveMotion::new(arc, 10_f64).unwrap())
+            .await
+            .unwrap();
+
+        assert!(
+            (fk_solver.limb4_position_vector(&ArmConfig::default().params, &state) - end)
+                .magnitude()
+                < 10_f64.powf(-3_f64)
+        );
+
+        // The task ends once all of its handles are dropped.
+        drop(handle);
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn stops_running_move() {
+        let (_task, handle) = spawn_arm();
+
+        // A move of two radians takes two and a half seconds, and a second move is queued behind
+        //  it. The clock is paused, so it only advances once all tasks are waiting on it.
+        let moving: JoinHandle<Result<KinematicState, ArmError>> = tokio::spawn({
+            let handle: ArmHandle = handle.clone();
+
+            async move { handle.move_joint(0_usize, 2_f64).await }
+        });
+        let queued: JoinHandle<Result<KinematicState, ArmError>> = tokio::spawn({
+            let handle: ArmHandle = handle.clone();
+
+            async move {
+                tokio::time::sleep(Duration::from_millis(20_u64)).await;
+                handle.move_joint(1_usize, 1_f64).await
+            }
+        });
+
+        // Stop between two ticks, so the state is the one of the tick at two hundred milliseconds
+        //  rather than depending on whether that tick or the stop is handled first.
+        tokio::time::sleep(Duration::from_millis(202_u64)).await;
+        let stopped: KinematicState = handle.stop().await.unwrap();
+
+        assert_eq!(moving.await.unwrap(), Err(ArmError::Stopped));
+        assert_eq!(queued.await.unwrap(), Err(ArmError::Stopped));
+        // Two hundred milliseconds into an acceleration of two radians per second squared, the
+        //  joint has moved four hundredths of a radian.
+        assert!((stopped.theta_0 - 0.04_f64).abs() < 10_f64.powf(-3_f64));
+        assert_eq!(stopped.theta_1, 0_f64);
+        assert_eq!(handle.state(), stopped);
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn approaches_motion_start() {
+        let (_task, handle) = spawn_arm();
+        let mut state_rx: watch::Receiver<KinematicState> = handle.subscribe();
+        let config: ArmConfig = ArmConfig::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        // An arc that starts away from the current pose, and is too fast to follow within the
+        //  velocity limits.
+        let start: Vector3<f64> = fk_solver.limb4_position_vector(
+            &config.params,
+            &KinematicState {
+                theta_0: 0.2_f64,
+                theta_1: 0.4_f64,
+                theta_2: 1_f64,
+                ..KinematicState::default()
+            },
+        );
+        let arc: CircularMotion = CircularMotion::arc(
+            start - Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+            1_f64,
+            Vector3::<f64>::y(),
+            0_f64,
+            1_f64,
+            ArcDirection::CounterClockwise,
+        )
+        .unwrap();
+        let end: Vector3<f64> = arc.position_at(1_f64);
+
+        let mut running: JoinHandle<Result<KinematicState, ArmError>> = tokio::spawn({
+            let handle: ArmHandle = handle.clone();
+
+            async move {
+                handle
+                    .run_motion(CurveMotion::new(arc, 100_f64).unwrap())
+                    .await
+            }
+        });
+
+        // Every published state lies within the velocity limits of the previous one.
+        let mut previous: (Instant, KinematicState) = (Instant::now(), handle.state());
+        let state: KinematicState = loop {
+            select! {
+                result = &mut running => break result.unwrap().unwrap(),
+                _ = state_rx.changed() => {
+                    let now: Instant = Instant::now();
+                    let state: KinematicState = state_rx.borrow_and_update().clone();
+                    let delta: Vector5<f64> =
+                        Vector5::<f64>::from(&state) - Vector5::<f64>::from(&previous.1);
+                    let period: f64 = (now - previous.0).as_secs_f64();
+
+                    assert!(delta
+                        .iter()
+                        .zip(config.limits.velocity.iter())
+                        .all(|(distance, limit)| distance.abs()
+                            <= limit * period + 10_f64.powf(-9_f64)));
+
+                    previous = (now, state);
+                }
+            }
+        };
+
+        assert!(
+            (fk_solver.limb4_position_vector(&config.params, &state) - end).magnitude()
+                < 10_f64.powf(-3_f64)
+        );
+    }
+}
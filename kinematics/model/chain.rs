@@ -0,0 +1,385 @@
+use nalgebra::{DVector, Matrix3, Rotation3, Unit, Vector3};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::model::{
+    JointLimit, KinematicLimits, KinematicParameters, KinematicState, LinkRadii, Pose,
+};
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum KinematicChainError {
+    #[error("Expected {expected} joint values, got {actual}")]
+    JointCountMismatch { expected: usize, actual: usize },
+    #[error("The chain does not have the layout of the five-link arm, {0}")]
+    NotFiveLinkArm(&'static str),
+}
+
+/// The tolerance used when comparing the layout of a chain to the one of the five-link arm.
+const LAYOUT_EPS: f64 = 1e-9_f64;
+
+/// The way a joint moves along its axis.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JointKind {
+    /// The joint rotates around its axis, its value is an angle.
+    Revolute,
+    /// The joint slides along its axis, its value is a distance.
+    Prismatic,
+}
+
+/// A single joint of a kinematic chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainJoint {
+    pub kind: JointKind,
+    /// The pose of the joint frame, relative to the frame of the previous joint after its motion
+    ///  (or to the base frame for the first joint).
+    pub origin: Pose,
+    /// The unit axis the joint rotates around or slides along, expressed in the joint frame.
+    pub axis: Vector3<f64>,
+    /// The offset that is added to the joint value.
+    pub offset: f64,
+    pub limit: JointLimit,
+}
+
+impl ChainJoint {
+    pub fn new(
+        kind: JointKind,
+        origin: Pose,
+        axis: Vector3<f64>,
+        offset: f64,
+        limit: JointLimit,
+    ) -> Self {
+        Self {
+            kind,
+            origin,
+            axis: axis.normalize(),
+            offset,
+            limit,
+        }
+    }
+
+    /// Create an unlimited revolute joint without offset, placed at the given position.
+    pub fn revolute(position: Vector3<f64>, axis: Vector3<f64>) -> Self {
+        Self::new(
+            JointKind::Revolute,
+            Pose::from_position(position),
+            axis,
+            0_f64,
+            JointLimit::unlimited(),
+        )
+    }
+
+    /// Create an unlimited prismatic joint without offset, placed at the given position.
+    pub fn prismatic(position: Vector3<f64>, axis: Vector3<f64>) -> Self {
+        Self::new(
+            JointKind::Prismatic,
+            Pose::from_position(position),
+            axis,
+            0_f64,
+            JointLimit::unlimited(),
+        )
+    }
+
+    /// Compute the motion of the joint for the given value, relative to the joint frame.
+    pub fn motion(&self, value: f64) -> Pose {
+        let value: f64 = value + self.offset;
+
+        match self.kind {
+            JointKind::Revolute => Pose::new(
+                Vector3::<f64>::zeros(),
+                Rotation3::<f64>::from_axis_angle(&Unit::new_normalize(self.axis), value)
+                    .into_inner(),
+            ),
+            JointKind::Prismatic => Pose::from_position(self.axis * value),
+        }
+    }
+}
+
+/// The values of all the joints of a kinematic chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainState {
+    pub values: DVector<f64>,
+}
+
+impl ChainState {
+    pub fn new(values: DVector<f64>) -> Self {
+        Self { values }
+    }
+
+    /// Create the state in which all the joints of a chain with the given number of joints are at
+    ///  zero.
+    pub fn zeros(dof: usize) -> Self {
+        Self::new(DVector::<f64>::zeros(dof))
+    }
+}
+
+impl From<&KinematicState> for ChainState {
+    fn from(value: &KinematicState) -> Self {
+        Self::new(DVector::<f64>::from_vec(vec![
+            value.theta_0,
+            value.theta_1,
+            value.theta_2,
+            value.theta_3,
+            value.theta_4,
+        ]))
+    }
+}
+
+impl TryFrom<&ChainState> for KinematicState {
+    type Error = KinematicChainError;
+
+    fn try_from(value: &ChainState) -> Result<Self, Self::Error> {
+        if value.values.len() != 5_usize {
+            return Err(KinematicChainError::JointCountMismatch {
+                expected: 5_usize,
+                actual: value.values.len(),
+            });
+        }
+
+        Ok(Self {
+            theta_0: value.values[0],
+            theta_1: value.values[1],
+            theta_2: value.values[2],
+            theta_3: value.values[3],
+            theta_4: value.values[4],
+        })
+    }
+}
+
+/// A serial chain of an arbitrary number of revolute and prismatic joints, expressed in the base
+///  frame (with the y-axis pointing up).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KinematicChain {
+    pub joints: Vec<ChainJoint>,
+    /// The pose of the end-effector, relative to the frame of the last joint after its motion.
+    pub tool: Pose,
+}
+
+impl KinematicChain {
+    pub fn new(joints: Vec<ChainJoint>, tool: Pose) -> Self {
+        Self { joints, tool }
+    }
+
+    /// Get the number of joints (degrees of freedom) of the chain.
+    pub fn dof(&self) -> usize {
+        self.joints.len()
+    }
+
+    /// Check if the given state has a value for every joint of the chain.
+    pub fn check_state(&self, state: &ChainState) -> Result<(), KinematicChainError> {
+        if state.values.len() != self.dof() {
+            return Err(KinematicChainError::JointCountMismatch {
+                expected: self.dof(),
+                actual: state.values.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check if all the joint values of the given state lie within the limits, fails when the
+    ///  state does not have a value for every joint.
+    pub fn contains(&self, state: &ChainState) -> Result<bool, KinematicChainError> {
+        self.check_state(state)?;
+
+        Ok(self
+            .joints
+            .iter()
+            .zip(state.values.iter())
+            .all(|(joint, value)| joint.limit.contains(*value)))
+    }
+
+    /// Clamp all the joint values of the given state into the limits, fails when the state does
+    ///  not have a value for every joint.
+    pub fn clamp(&self, state: &ChainState) -> Result<ChainState, KinematicChainError> {
+        self.check_state(state)?;
+
+        Ok(ChainState::new(state.values.map_with_location(
+            |i, _, value| self.joints[i].limit.clamp(value),
+        )))
+    }
+
+    /// Compute the gradient of the summed limit costs, stepping against it moves the joints
+    ///  towards the centers of their limits. Fails when the state does not have a value for every
+    ///  joint.
+    pub fn cost_gradient(&self, state: &ChainState) -> Result<DVector<f64>, KinematicChainError> {
+        self.check_state(state)?;
+
+        Ok(state
+            .values
+            .map_with_location(|i, _, value| self.joints[i].limit.cost_gradient(value)))
+    }
+}
+
+impl From<&KinematicParameters> for KinematicChain {
+    /// Create the chain of the five-link arm, the base rotates around the vertical axis, the
+    ///  three pitch joints around the horizontal axis and the wrist rolls around the last link.
+    fn from(params: &KinematicParameters) -> Self {
+        let limits: [JointLimit; 5] = params.limits.as_array();
+        let pitch_axis: Vector3<f64> = -Vector3::<f64>::x();
+        let yaw_axis: Vector3<f64> = -Vector3::<f64>::y();
+
+        let joints: Vec<ChainJoint> = [
+            (0_f64, yaw_axis),
+            (params.l_0, pitch_axis),
+            (params.l_1, pitch_axis),
+            (params.l_2, pitch_axis),
+            (params.l_3, yaw_axis),
+        ]
+        .into_iter()
+        .zip(limits)
+        .map(|((length, axis), limit)| {
+            ChainJoint::new(
+                JointKind::Revolute,
+                Pose::from_position(Vector3::<f64>::new(0_f64, length, 0_f64)),
+                axis,
+                0_f64,
+                limit,
+            )
+        })
+        .collect();
+
+        Self::new(
+            joints,
+            Pose::from_position(Vector3::<f64>::new(0_f64, params.l_4, 0_f64)),
+        )
+    }
+}
+
+impl TryFrom<&KinematicChain> for KinematicParameters {
+    type Error = KinematicChainError;
+
+    /// Recover the parameters of the five-link arm from a chain with its layout, which is the
+    ///  layout of the chain created from the parameters.
+    fn try_from(chain: &KinematicChain) -> Result<Self, Self::Error> {
+        let preset: KinematicChain = KinematicChain::from(&KinematicParameters::default());
+
+        if chain.dof() != preset.dof() {
+            return Err(KinematicChainError::NotFiveLinkArm("it needs five joints"));
+        }
+
+        // Every joint must be placed straight above the previous one, and move in the same way.
+        let mut lengths: Vec<f64> = Vec::with_capacity(preset.dof() + 1_usize);
+
+        for (joint, expected) in chain.joints.iter().zip(preset.joints.iter()) {
+            if joint.kind != expected.kind
+                || (joint.axis - expected.axis).magnitude() > LAYOUT_EPS
+                || joint.offset.abs() > LAYOUT_EPS
+            {
+                return Err(KinematicChainError::NotFiveLinkArm(
+                    "the joints move differently",
+                ));
+            }
+
+            lengths.push(Self::vertical_length(&joint.origin)?);
+        }
+
+        lengths.push(Self::vertical_length(&chain.tool)?);
+
+        // The first joint lies in the origin of the base frame.
+        if lengths[0].abs() > LAYOUT_EPS {
+            return Err(KinematicChainError::NotFiveLinkArm(
+                "the base joint is not in the origin",
+            ));
+        }
+
+        Ok(Self {
+            l_0: lengths[1],
+            l_1: lengths[2],
+            l_2: lengths[3],
+            l_3: lengths[4],
+            l_4: lengths[5],
+            limits: KinematicLimits {
+                theta_0: chain.joints[0].limit,
+                theta_1: chain.joints[1].limit,
+                theta_2: chain.joints[2].limit,
+                theta_3: chain.joints[3].limit,
+                theta_4: chain.joints[4].limit,
+            },
+            // The chain does not describe the shape of the links.
+            radii: LinkRadii::default(),
+        })
+    }
+}
+
+impl KinematicParameters {
+    /// Get the length of a link of the five-link arm from the pose at its end, which must point
+    ///  straight up without rotating.
+    fn vertical_length(pose: &Pose) -> Result<f64, KinematicChainError> {
+        if pose.position.x.abs() > LAYOUT_EPS
+            || pose.position.z.abs() > LAYOUT_EPS
+            || (pose.orientation - Matrix3::<f64>::identity()).norm() > LAYOUT_EPS
+        {
+            return Err(KinematicChainError::NotFiveLinkArm(
+                "the links do not point straight up",
+            ));
+        }
+
+        Ok(pose.position.y)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{DVector, Vector3};
+
+    use crate::model::chain::{ChainJoint, ChainState, JointKind, KinematicChain};
+    use crate::model::{JointLimit, KinematicLimits, KinematicParameters, KinematicState, Pose};
+
+    #[test]
+    pub fn preset_keeps_limits() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+
+        let chain: KinematicChain = KinematicChain::from(&params);
+
+        assert_eq!(chain.dof(), 5_usize);
+        assert!(chain
+            .joints
+            .iter()
+            .all(|joint| joint.kind == JointKind::Revolute && joint.limit == JointLimit::servo()));
+
+        // Clamping must agree with the limits of the parameters.
+        let state: KinematicState = KinematicState {
+            theta_0: 2_f64,
+            theta_3: -3_f64,
+            ..KinematicState::default()
+        };
+
+        assert_eq!(
+            KinematicState::try_from(&chain.clamp(&ChainState::from(&state)).unwrap()).unwrap(),
+            params.limits.clamp(&state)
+        );
+    }
+
+    #[test]
+    pub fn prismatic_motion() {
+        let joint: ChainJoint = ChainJoint::new(
+            JointKind::Prismatic,
+            Pose::identity(),
+            Vector3::<f64>::new(0_f64, 0_f64, 2_f64),
+            0.5_f64,
+            JointLimit::new(0_f64, 1_f64),
+        );
+
+        // The axis is normalized, and the offset is added to the value.
+        assert_eq!(
+            joint.motion(1_f64).position,
+            Vector3::<f64>::new(0_f64, 0_f64, 1.5_f64)
+        );
+
+        let chain: KinematicChain = KinematicChain::new(vec![joint], Pose::identity());
+
+        assert!(chain.check_state(&ChainState::zeros(2_usize)).is_err());
+        assert!(!chain
+            .contains(&ChainState::new(DVector::<f64>::from_vec(vec![2_f64])))
+            .unwrap());
+
+        // A state with another number of values is refused instead of truncated or overrun.
+        let long: ChainState = ChainState::zeros(2_usize);
+        assert!(chain.contains(&long).is_err());
+        assert!(chain.clamp(&long).is_err());
+        assert!(chain.cost_gradient(&long).is_err());
+    }
+}
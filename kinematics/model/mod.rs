@@ -1,13 +1,217 @@
-use nalgebra::Vector5;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use nalgebra::{Matrix3, Vector3, Vector5};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+pub mod chain;
+pub mod urdf;
+
+/// The range of angles a single joint is allowed to reach.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct JointLimit {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl JointLimit {
+    pub fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    /// Create a limit that allows every angle.
+    pub fn unlimited() -> Self {
+        Self {
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+
+    /// Create the limit of a servo, which can rotate a quarter turn in both directions.
+    pub fn servo() -> Self {
+        Self {
+            min: -FRAC_PI_2,
+            max: FRAC_PI_2,
+        }
+    }
+
+    /// Check if the given angle lies within the limit.
+    pub fn contains(&self, angle: f64) -> bool {
+        self.min <= angle && angle <= self.max
+    }
+
+    /// Clamp the given angle into the limit.
+    pub fn clamp(&self, angle: f64) -> f64 {
+        angle.clamp(self.min, self.max)
+    }
+
+    /// Check if the given angle lies within the given distance from either end of the limit.
+    pub fn is_near_limit(&self, angle: f64, distance: f64) -> bool {
+        angle - self.min <= distance || self.max - angle <= distance
+    }
+
+    /// Draw a random angle uniformly from within the limit. An unlimited end is replaced by the
+    ///  end a single turn away from the other one, or by half a turn around zero when both ends
+    ///  are unlimited.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        let (min, max) = match (self.min.is_finite(), self.max.is_finite()) {
+            (true, true) => (self.min, self.max),
+            (true, false) => (self.min, self.min + 2_f64 * PI),
+            (false, true) => (self.max - 2_f64 * PI, self.max),
+            (false, false) => (-PI, PI),
+        };
+
+        rng.gen_range(min..=max)
+    }
+
+    /// Compute the gradient of the limit cost `((angle - center) / range)^2 / 2`, which is zero for
+    ///  unlimited joints.
+    pub fn cost_gradient(&self, angle: f64) -> f64 {
+        let range: f64 = self.max - self.min;
+
+        if !range.is_finite() {
+            return 0_f64;
+        }
+
+        (angle - (self.min + self.max) / 2_f64) / range.powi(2)
+    }
+}
+
+/// The limits of all the joints.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KinematicLimits {
+    pub theta_0: JointLimit,
+    pub theta_1: JointLimit,
+    pub theta_2: JointLimit,
+    pub theta_3: JointLimit,
+    pub theta_4: JointLimit,
+}
+
+impl KinematicLimits {
+    /// Create limits that allow every state.
+    pub fn unlimited() -> Self {
+        Self {
+            theta_0: JointLimit::unlimited(),
+            theta_1: JointLimit::unlimited(),
+            theta_2: JointLimit::unlimited(),
+            theta_3: JointLimit::unlimited(),
+            theta_4: JointLimit::unlimited(),
+        }
+    }
+
+    /// Create the limits of an arm of which every joint is driven by a servo.
+    pub fn servo() -> Self {
+        Self {
+            theta_0: JointLimit::servo(),
+            theta_1: JointLimit::servo(),
+            theta_2: JointLimit::servo(),
+            theta_3: JointLimit::servo(),
+            theta_4: JointLimit::servo(),
+        }
+    }
+
+    /// Get the limits as an array, in the order of the joints.
+    pub fn as_array(&self) -> [JointLimit; 5] {
+        [
+            self.theta_0,
+            self.theta_1,
+            self.theta_2,
+            self.theta_3,
+            self.theta_4,
+        ]
+    }
+
+    /// Check if all the angles of the given state lie within the limits.
+    pub fn contains(&self, state: &KinematicState) -> bool {
+        self.as_array()
+            .iter()
+            .zip(Vector5::<f64>::from(state).iter())
+            .all(|(limit, angle)| limit.contains(*angle))
+    }
+
+    /// Clamp all the angles of the given state into the limits.
+    pub fn clamp(&self, state: &KinematicState) -> KinematicState {
+        let limits: [JointLimit; 5] = self.as_array();
+
+        KinematicState::from(
+            Vector5::<f64>::from(state).map_with_location(|i, _, angle| limits[i].clamp(angle)),
+        )
+    }
+
+    /// Check if any of the angles of the given state lies within the given distance from a limit.
+    pub fn is_near_limit(&self, state: &KinematicState, distance: f64) -> bool {
+        self.as_array()
+            .iter()
+            .zip(Vector5::<f64>::from(state).iter())
+            .any(|(limit, angle)| limit.is_near_limit(*angle, distance))
+    }
+
+    /// Compute the gradient of the summed limit costs, stepping against it moves the joints
+    ///  towards the centers of their limits.
+    pub fn cost_gradient(&self, state: &KinematicState) -> Vector5<f64> {
+        let limits: [JointLimit; 5] = self.as_array();
+
+        Vector5::<f64>::from(state).map_with_location(|i, _, angle| limits[i].cost_gradient(angle))
+    }
+
+    /// Draw a random state uniformly from within the limits, unlimited joints are drawn from a
+    ///  single turn (see `JointLimit::sample`).
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> KinematicState {
+        let limits: [JointLimit; 5] = self.as_array();
+
+        KinematicState::from(Vector5::<f64>::from_fn(|i, _| limits[i].sample(rng)))
+    }
+}
+
+impl Default for KinematicLimits {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+/// The radii of the capsules around the links, which are used for collision checking.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkRadii {
+    pub r_0: f64,
+    pub r_1: f64,
+    pub r_2: f64,
+    pub r_3: f64,
+    pub r_4: f64,
+}
+
+impl LinkRadii {
+    /// Create radii that are the same for every link.
+    pub fn uniform(radius: f64) -> Self {
+        Self {
+            r_0: radius,
+            r_1: radius,
+            r_2: radius,
+            r_3: radius,
+            r_4: radius,
+        }
+    }
+
+    /// Get the radii as an array, in the order of the links.
+    pub fn as_array(&self) -> [f64; 5] {
+        [self.r_0, self.r_1, self.r_2, self.r_3, self.r_4]
+    }
+}
+
+impl Default for LinkRadii {
+    fn default() -> Self {
+        Self::uniform(1_f64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct KinematicParameters {
     pub l_0: f64,
     pub l_1: f64,
     pub l_2: f64,
     pub l_3: f64,
     pub l_4: f64,
+    pub limits: KinematicLimits,
+    pub radii: LinkRadii,
 }
 
 impl KinematicParameters {
@@ -25,11 +229,13 @@ impl Default for KinematicParameters {
             l_2: 10_f64,
             l_3: 10_f64,
             l_4: 10_f64,
+            limits: KinematicLimits::default(),
+            radii: LinkRadii::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct KinematicState {
     pub theta_0: f64,
     pub theta_1: f64,
@@ -73,3 +279,79 @@ impl From<&KinematicState> for Vector5<f64> {
         )
     }
 }
+
+/// The position and orientation of an end-effector, both expressed in the base frame.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub position: Vector3<f64>,
+    pub orientation: Matrix3<f64>,
+}
+
+impl Pose {
+    pub fn new(position: Vector3<f64>, orientation: Matrix3<f64>) -> Self {
+        Self {
+            position,
+            orientation,
+        }
+    }
+
+    /// Create the pose that neither translates nor rotates.
+    pub fn identity() -> Self {
+        Self::new(Vector3::<f64>::zeros(), Matrix3::<f64>::identity())
+    }
+
+    /// Create a pose that only translates.
+    pub fn from_position(position: Vector3<f64>) -> Self {
+        Self::new(position, Matrix3::<f64>::identity())
+    }
+
+    /// Chain the given pose, which is expressed in the frame of this pose, onto this pose.
+    pub fn compose(&self, other: &Pose) -> Pose {
+        Pose::new(
+            self.position + self.orientation * other.position,
+            self.orientation * other.orientation,
+        )
+    }
+
+    /// Transform the given point, expressed in the frame of this pose, into the parent frame.
+    pub fn transform_point(&self, point: &Vector3<f64>) -> Vector3<f64> {
+        self.position + self.orientation * point
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::PI;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::model::{JointLimit, KinematicLimits, KinematicState};
+
+    #[test]
+    pub fn samples_within_limits() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0_u64);
+
+        // Limits that lie entirely outside of a single turn around zero, or that are wider.
+        let limits: KinematicLimits = KinematicLimits {
+            theta_0: JointLimit::new(4_f64, 5_f64),
+            theta_1: JointLimit::new(-10_f64, 10_f64),
+            theta_2: JointLimit::new(1_f64, f64::INFINITY),
+            theta_3: JointLimit::new(f64::NEG_INFINITY, -5_f64),
+            ..KinematicLimits::unlimited()
+        };
+
+        let states: Vec<KinematicState> = (0..1_000).map(|_| limits.sample(&mut rng)).collect();
+
+        assert!(states.iter().all(|state| limits.contains(state)));
+        assert!(states
+            .iter()
+            .all(|state| state.theta_2 <= 1_f64 + 2_f64 * PI
+                && state.theta_3 >= -5_f64 - 2_f64 * PI
+                && state.theta_4.abs() <= PI));
+
+        // The wide limit is not truncated to a single turn.
+        assert!(states.iter().any(|state| state.theta_1 > PI));
+        assert!(states.iter().any(|state| state.theta_1 < -PI));
+    }
+}
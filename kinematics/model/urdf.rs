@@ -0,0 +1,472 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use nalgebra::{Rotation3, Vector3};
+use roxmltree::{Document, Node};
+use thiserror::Error;
+
+use crate::model::chain::{ChainJoint, JointKind, KinematicChain};
+use crate::model::{JointLimit, KinematicParameters, Pose};
+
+#[derive(Debug, Error)]
+pub enum UrdfError {
+    #[error("Failed to read URDF file, error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse URDF document, error: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("Element <{0}> is missing attribute \"{1}\"")]
+    MissingAttribute(String, &'static str),
+    #[error("Value \"{0}\" is not a valid list of numbers")]
+    InvalidNumbers(String),
+    #[error("Joint \"{0}\" has unsupported type \"{1}\"")]
+    UnsupportedJoint(String, String),
+    #[error("The robot is not a serial chain, {0}")]
+    NotASerialChain(&'static str),
+}
+
+/// A joint as it is described in the URDF document.
+struct UrdfJoint<'a> {
+    kind: Option<JointKind>,
+    parent: &'a str,
+    child: &'a str,
+    origin: Pose,
+    axis: Vector3<f64>,
+    limit: JointLimit,
+}
+
+impl KinematicChain {
+    /// Load the kinematic chain from the URDF file at the given path, see `from_urdf`.
+    pub fn load_urdf<P: AsRef<Path>>(path: P) -> Result<Self, UrdfError> {
+        Self::from_urdf(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse the kinematic chain from the given URDF document.
+    ///
+    /// The robot must be a serial chain, fixed joints are merged into the origin of the next
+    ///  moving joint (or into the tool frame when they come last), and the coordinates are taken
+    ///  as they are (so the base frame of the document becomes the base frame of the chain).
+    pub fn from_urdf(xml: &str) -> Result<Self, UrdfError> {
+        let document: Document = Document::parse(xml)?;
+        let joints: Vec<UrdfJoint> = document
+            .root_element()
+            .children()
+            .filter(|node| node.has_tag_name("joint"))
+            .map(parse_joint)
+            .collect::<Result<Vec<UrdfJoint>, UrdfError>>()?;
+
+        if joints.is_empty() {
+            return Ok(KinematicChain::new(Vec::new(), Pose::identity()));
+        }
+
+        // Find the root link, which is the only parent that is never a child (every link of a
+        //  loop is the child of another).
+        let mut roots = joints
+            .iter()
+            .map(|joint| joint.parent)
+            .filter(|parent| joints.iter().all(|joint| joint.child != *parent));
+
+        let mut link: &str = match roots.next() {
+            Some(link) => link,
+            None => return Err(UrdfError::NotASerialChain("it contains a loop")),
+        };
+
+        if roots.any(|other| other != link) {
+            return Err(UrdfError::NotASerialChain("it has multiple root links"));
+        }
+
+        // Walk from the root towards the tip, collecting the fixed transforms in between.
+        let mut chain: Vec<ChainJoint> = Vec::new();
+        let mut pending: Pose = Pose::identity();
+        let mut visited: usize = 0_usize;
+
+        for step in 0.. {
+            let mut children = joints.iter().filter(|joint| joint.parent == link);
+
+            let joint: &UrdfJoint = match children.next() {
+                Some(joint) => joint,
+                None => break,
+            };
+
+            if children.next().is_some() {
+                return Err(UrdfError::NotASerialChain("it has a branching link"));
+            }
+
+            if step >= joints.len() {
+                return Err(UrdfError::NotASerialChain("it contains a loop"));
+            }
+
+            match joint.kind {
+                Some(kind) => {
+                    chain.push(ChainJoint::new(
+                        kind,
+                        pending.compose(&joint.origin),
+                        joint.axis,
+                        0_f64,
+                        joint.limit,
+                    ));
+                    pending = Pose::identity();
+                }
+                None => pending = pending.compose(&joint.origin),
+            }
+
+            link = joint.child;
+            visited += 1_usize;
+        }
+
+        if visited != joints.len() {
+            return Err(UrdfError::NotASerialChain(
+                "it has joints that are not connected to the root link",
+            ));
+        }
+
+        Ok(KinematicChain::new(chain, pending))
+    }
+
+    /// Write the kinematic chain as a URDF document for a robot with the given name.
+    ///
+    /// The joint offsets are merged into the joint origins, and unlimited revolute joints are
+    ///  written as continuous joints.
+    pub fn to_urdf(&self, name: &str) -> String {
+        let mut xml: String = String::new();
+
+        // Writing into a string cannot fail, so the results are ignored.
+        let _ = writeln!(xml, "<?xml version=\"1.0\"?>");
+        let _ = writeln!(xml, "<robot name=\"{}\">", name);
+        let _ = writeln!(xml, "  <link name=\"base_link\"/>");
+
+        for i in 0..self.dof() {
+            let _ = writeln!(xml, "  <link name=\"link_{}\"/>", i);
+        }
+
+        let _ = writeln!(xml, "  <link name=\"tool\"/>");
+
+        let mut parent: String = String::from("base_link");
+
+        for (i, joint) in self.joints.iter().enumerate() {
+            let unlimited: bool = !joint.limit.min.is_finite() || !joint.limit.max.is_finite();
+            let kind: &str = match (joint.kind, unlimited) {
+                (JointKind::Revolute, true) => "continuous",
+                (JointKind::Revolute, false) => "revolute",
+                (JointKind::Prismatic, _) => "prismatic",
+            };
+            let child: String = format!("link_{}", i);
+
+            let _ = writeln!(xml, "  <joint name=\"joint_{}\" type=\"{}\">", i, kind);
+            let _ = writeln!(xml, "    <parent link=\"{}\"/>", parent);
+            let _ = writeln!(xml, "    <child link=\"{}\"/>", child);
+            let _ = writeln!(
+                xml,
+                "    {}",
+                format_origin(&joint.origin.compose(&joint.motion(0_f64)))
+            );
+            let _ = writeln!(xml, "    <axis xyz=\"{}\"/>", format_vector(&joint.axis));
+
+            if !unlimited {
+                let _ = writeln!(
+                    xml,
+                    "    <limit lower=\"{}\" upper=\"{}\" effort=\"0\" velocity=\"0\"/>",
+                    joint.limit.min, joint.limit.max
+                );
+            }
+
+            let _ = writeln!(xml, "  </joint>");
+
+            parent = child;
+        }
+
+        let _ = writeln!(xml, "  <joint name=\"tool_joint\" type=\"fixed\">");
+        let _ = writeln!(xml, "    <parent link=\"{}\"/>", parent);
+        let _ = writeln!(xml, "    <child link=\"tool\"/>");
+        let _ = writeln!(xml, "    {}", format_origin(&self.tool));
+        let _ = writeln!(xml, "  </joint>");
+        let _ = writeln!(xml, "</robot>");
+
+        xml
+    }
+}
+
+impl KinematicParameters {
+    /// Write the five-link arm as a URDF document for a robot with the given name.
+    pub fn to_urdf(&self, name: &str) -> String {
+        KinematicChain::from(self).to_urdf(name)
+    }
+}
+
+/// Parse a single joint element.
+fn parse_joint<'a>(node: Node<'a, 'a>) -> Result<UrdfJoint<'a>, UrdfError> {
+    let name: &str = required_attribute(node, "name")?;
+    let kind: Option<JointKind> = match required_attribute(node, "type")? {
+        "revolute" | "continuous" => Some(JointKind::Revolute),
+        "prismatic" => Some(JointKind::Prismatic),
+        "fixed" => None,
+        other => {
+            return Err(UrdfError::UnsupportedJoint(
+                name.to_string(),
+                other.to_string(),
+            ))
+        }
+    };
+
+    let child_element = |tag: &str| node.children().find(|child| child.has_tag_name(tag));
+
+    let parent: &str = match child_element("parent") {
+        Some(element) => required_attribute(element, "link")?,
+        None => return Err(UrdfError::MissingAttribute(String::from("parent"), "link")),
+    };
+    let child: &str = match child_element("child") {
+        Some(element) => required_attribute(element, "link")?,
+        None => return Err(UrdfError::MissingAttribute(String::from("child"), "link")),
+    };
+
+    // The origin and the axis are optional, and default to the identity and the x-axis.
+    let origin: Pose = match child_element("origin") {
+        Some(element) => {
+            let position: Vector3<f64> = optional_vector(element, "xyz")?;
+            let rpy: Vector3<f64> = optional_vector(element, "rpy")?;
+
+            Pose::new(
+                position,
+                Rotation3::<f64>::from_euler_angles(rpy.x, rpy.y, rpy.z).into_inner(),
+            )
+        }
+        None => Pose::identity(),
+    };
+
+    let axis: Vector3<f64> = match child_element("axis") {
+        Some(element) => optional_vector(element, "xyz")?,
+        None => Vector3::<f64>::x(),
+    };
+
+    // Continuous joints, and joints without limit element, are unlimited.
+    let limit: JointLimit = match child_element("limit") {
+        Some(element) if required_attribute(node, "type")? != "continuous" => JointLimit::new(
+            optional_number(element, "lower")?,
+            optional_number(element, "upper")?,
+        ),
+        _ => JointLimit::unlimited(),
+    };
+
+    Ok(UrdfJoint {
+        kind,
+        parent,
+        child,
+        origin,
+        axis,
+        limit,
+    })
+}
+
+/// Get an attribute that must be present.
+fn required_attribute<'a>(node: Node<'a, 'a>, name: &'static str) -> Result<&'a str, UrdfError> {
+    node.attribute(name)
+        .ok_or_else(|| UrdfError::MissingAttribute(node.tag_name().name().to_string(), name))
+}
+
+/// Parse a vector attribute, which is zero when it is missing.
+fn optional_vector(node: Node, name: &'static str) -> Result<Vector3<f64>, UrdfError> {
+    let value: &str = match node.attribute(name) {
+        Some(value) => value,
+        None => return Ok(Vector3::<f64>::zeros()),
+    };
+
+    let numbers: Vec<f64> = value
+        .split_whitespace()
+        .map(|number| number.parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| UrdfError::InvalidNumbers(value.to_string()))?;
+
+    if numbers.len() != 3_usize {
+        return Err(UrdfError::InvalidNumbers(value.to_string()));
+    }
+
+    Ok(Vector3::<f64>::new(numbers[0], numbers[1], numbers[2]))
+}
+
+/// Parse a number attribute, which is zero when it is missing.
+fn optional_number(node: Node, name: &'static str) -> Result<f64, UrdfError> {
+    match node.attribute(name) {
+        Some(value) => value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| UrdfError::InvalidNumbers(value.to_string())),
+        None => Ok(0_f64),
+    }
+}
+
+/// Format a vector as a space separated list, without negative zeros.
+fn format_vector(vector: &Vector3<f64>) -> String {
+    format!(
+        "{} {} {}",
+        vector.x + 0_f64,
+        vector.y + 0_f64,
+        vector.z + 0_f64
+    )
+}
+
+/// Format a pose as an origin element.
+fn format_origin(pose: &Pose) -> String {
+    let (roll, pitch, yaw) =
+        Rotation3::<f64>::from_matrix_unchecked(pose.orientation).euler_angles();
+
+    format!(
+        "<origin xyz=\"{}\" rpy=\"{}\"/>",
+        format_vector(&pose.position),
+        format_vector(&Vector3::<f64>::new(roll, pitch, yaw))
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{DVector, Rotation3, Vector3};
+
+    use crate::forward::algorithms::chain::ChainForwardKinematicAlgorithm;
+    use crate::model::chain::{ChainJoint, ChainState, JointKind, KinematicChain};
+    use crate::model::urdf::UrdfError;
+    use crate::model::{JointLimit, KinematicLimits, KinematicParameters, Pose};
+
+    /// A URDF document describing the default arm, with servo limits.
+    const DEFAULT_ARM: &str = include_str!("default_arm.urdf");
+
+    #[test]
+    pub fn import_default_arm() {
+        let chain: KinematicChain = KinematicChain::from_urdf(DEFAULT_ARM).unwrap();
+
+        assert_eq!(chain.dof(), 5_usize);
+        assert_eq!(
+            KinematicParameters::try_from(&chain).unwrap(),
+            KinematicParameters {
+                limits: KinematicLimits::servo(),
+                ..KinematicParameters::default()
+            }
+        );
+    }
+
+    #[test]
+    pub fn export_round_trip() {
+        let params: KinematicParameters = KinematicParameters {
+            l_2: 7.5_f64,
+            limits: KinematicLimits {
+                theta_1: JointLimit::new(-0.5_f64, 1_f64),
+                ..KinematicLimits::unlimited()
+            },
+            ..KinematicParameters::default()
+        };
+
+        let xml: String = params.to_urdf("arm");
+
+        assert!(xml.contains("type=\"continuous\""));
+        assert_eq!(
+            KinematicParameters::try_from(&KinematicChain::from_urdf(&xml).unwrap()).unwrap(),
+            params
+        );
+
+        // A chain with offsets, rotated origins and a prismatic joint must keep its poses.
+        let rotated: ChainJoint = ChainJoint::new(
+            JointKind::Revolute,
+            Pose::new(
+                Vector3::<f64>::new(0_f64, 4_f64, 1_f64),
+                Rotation3::<f64>::from_euler_angles(0.3_f64, -0.2_f64, 1.1_f64).into_inner(),
+            ),
+            Vector3::<f64>::new(1_f64, 0_f64, 1_f64),
+            0.4_f64,
+            JointLimit::new(-1_f64, 1_f64),
+        );
+
+        let chain: KinematicChain = KinematicChain::new(
+            vec![
+                ChainJoint::revolute(Vector3::<f64>::zeros(), Vector3::<f64>::y()),
+                rotated,
+                ChainJoint::prismatic(
+                    Vector3::<f64>::new(0_f64, 2_f64, 0_f64),
+                    Vector3::<f64>::y(),
+                ),
+            ],
+            Pose::from_position(Vector3::<f64>::new(0_f64, 3_f64, 0_f64)),
+        );
+        let imported: KinematicChain = KinematicChain::from_urdf(&chain.to_urdf("chain")).unwrap();
+
+        let fk_solver: ChainForwardKinematicAlgorithm = ChainForwardKinematicAlgorithm::default();
+        let state: ChainState =
+            ChainState::new(DVector::<f64>::from_vec(vec![0.7_f64, -0.3_f64, 1.2_f64]));
+
+        let thresh: f64 = 10_f64.powf(-9_f64);
+        let expected: Pose = fk_solver.tool_pose(&chain, &state);
+        let actual: Pose = fk_solver.tool_pose(&imported, &state);
+
+        assert!((expected.position - actual.position).magnitude() < thresh);
+        assert!((expected.orientation - actual.orientation).norm() < thresh);
+    }
+
+    #[test]
+    pub fn rejects_branching_robot() {
+        let xml: &str = r#"
+            <robot name="branching">
+              <joint name="a" type="revolute">
+                <parent link="base"/>
+                <child link="left"/>
+              </joint>
+              <joint name="b" type="revolute">
+                <parent link="base"/>
+                <child link="right"/>
+              </joint>
+            </robot>
+        "#;
+
+        assert!(matches!(
+            KinematicChain::from_urdf(xml),
+            Err(UrdfError::NotASerialChain(_))
+        ));
+    }
+
+    #[test]
+    pub fn rejects_looping_robot() {
+        let looping: &str = r#"
+            <robot name="looping">
+              <joint name="a" type="revolute">
+                <parent link="first"/>
+                <child link="second"/>
+              </joint>
+              <joint name="b" type="revolute">
+                <parent link="second"/>
+                <child link="first"/>
+              </joint>
+            </robot>
+        "#;
+
+        assert!(matches!(
+            KinematicChain::from_urdf(looping),
+            Err(UrdfError::NotASerialChain("it contains a loop"))
+        ));
+
+        // A loop next to a serial chain is not reached from the root link.
+        let disconnected: &str = r#"
+            <robot name="disconnected">
+              <joint name="a" type="revolute">
+                <parent link="base"/>
+                <child link="tool"/>
+              </joint>
+              <joint name="b" type="revolute">
+                <parent link="first"/>
+                <child link="second"/>
+              </joint>
+              <joint name="c" type="revolute">
+                <parent link="second"/>
+                <child link="first"/>
+              </joint>
+            </robot>
+        "#;
+
+        assert!(matches!(
+            KinematicChain::from_urdf(disconnected),
+            Err(UrdfError::NotASerialChain(_))
+        ));
+
+        // Only a document without joints gives an empty chain.
+        assert_eq!(
+            KinematicChain::from_urdf(r#"<robot name="empty"/>"#)
+                .unwrap()
+                .dof(),
+            0_usize
+        );
+    }
+}
@@ -1,12 +1,302 @@
+use std::f64::consts::PI;
+
 use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::motion::{Curve, MotionError, MotionTarget, TimedPosition};
+
+/// The direction in which an arc is traversed, seen from the tip of the normal of its plane.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArcDirection {
+    CounterClockwise,
+    Clockwise,
+}
 
+/// A circle, or an arc of one, traversed by the tool in an arbitrary plane.
+///
+/// Angles are measured in the plane from the projection of the x-axis of the base frame onto it
+///  (or of the z-axis, when the plane is perpendicular to the x-axis), counter-clockwise around
+///  the normal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CircularMotion {
-    center: Vector3<f64>,
-    radius: f64,
+    pub center: Vector3<f64>,
+    pub radius: f64,
+    /// The normal of the plane of the circle.
+    pub normal: Vector3<f64>,
+    /// The angle at which the arc starts.
+    pub start_angle: f64,
+    /// The (unsigned) angle the arc sweeps, a full turn for a circle.
+    pub sweep: f64,
+    pub direction: ArcDirection,
 }
 
 impl CircularMotion {
-    async fn play(&self) {
+    /// Create a full circle, which starts at angle zero and runs counter-clockwise.
+    pub fn new(
+        center: Vector3<f64>,
+        radius: f64,
+        normal: Vector3<f64>,
+    ) -> Result<Self, MotionError> {
+        Self::arc(
+            center,
+            radius,
+            normal,
+            0_f64,
+            2_f64 * PI,
+            ArcDirection::CounterClockwise,
+        )
+    }
+
+    /// Create an arc, fails when the radius or sweep is not positive or the normal is zero.
+    pub fn arc(
+        center: Vector3<f64>,
+        radius: f64,
+        normal: Vector3<f64>,
+        start_angle: f64,
+        sweep: f64,
+        direction: ArcDirection,
+    ) -> Result<Self, MotionError> {
+        if radius.is_nan() || radius <= 0_f64 {
+            return Err(MotionError::InvalidRadius(radius));
+        }
+
+        if sweep.is_nan() || sweep <= 0_f64 {
+            return Err(MotionError::InvalidSweep(sweep));
+        }
+
+        let length: f64 = normal.magnitude();
+
+        if length.is_nan() || length == 0_f64 {
+            return Err(MotionError::InvalidNormal);
+        }
+
+        Ok(Self {
+            center,
+            radius,
+            normal,
+            start_angle,
+            sweep,
+            direction,
+        })
+    }
+
+    /// Get the orthonormal axes of the plane from which the angles are measured, the second axis
+    ///  lies a quarter turn counter-clockwise from the first one.
+    pub fn plane_axes(&self) -> (Vector3<f64>, Vector3<f64>) {
+        let normal: Vector3<f64> = self.normal.normalize();
+        let reference: Vector3<f64> = if normal.x.abs() < 0.9_f64 {
+            Vector3::<f64>::x()
+        } else {
+            Vector3::<f64>::z()
+        };
+        let u: Vector3<f64> = (reference - normal * normal.dot(&reference)).normalize();
+
+        (u, normal.cross(&u))
+    }
+
+    /// Get the length of the arc.
+    pub fn length(&self) -> f64 {
+        self.radius * self.sweep
+    }
+
+    /// Get the position on the arc at the given fraction (between zero and one) of it.
+    pub fn position_at(&self, t: f64) -> Vector3<f64> {
+        let sign: f64 = match self.direction {
+            ArcDirection::CounterClockwise => 1_f64,
+            ArcDirection::Clockwise => -1_f64,
+        };
+        let angle: f64 = self.start_angle + sign * self.sweep * t;
+        let (u, v) = self.plane_axes();
+
+        self.center + self.radius * (u * angle.cos() + v * angle.sin())
+    }
+
+    /// Get the duration of traversing the arc at the given tangential speed.
+    pub fn duration(&self, speed: f64) -> Result<f64, MotionError> {
+        if speed.is_nan() || speed <= 0_f64 {
+            return Err(MotionError::InvalidSpeed(speed));
+        }
 
+        Ok(self.length() / speed)
     }
-}
\ No newline at end of file
+
+    /// Sample the tool positions along the arc traversed at the given tangential speed, at
+    ///  intervals of at most the given period. The first and last samples lie on the ends of the
+    ///  arc.
+    pub fn timed_positions(
+        &self,
+        speed: f64,
+        period: f64,
+    ) -> Result<Vec<TimedPosition>, MotionError> {
+        if period.is_nan() || period <= 0_f64 {
+            return Err(MotionError::InvalidPeriod(period));
+        }
+
+        let duration: f64 = self.duration(speed)?;
+        let intervals: usize = (duration / period).ceil().max(1_f64) as usize;
+
+        Ok((0..=intervals)
+            .map(|i| {
+                let t: f64 = i as f64 / intervals as f64;
+
+                TimedPosition {
+                    time: t * duration,
+                    position: self.position_at(t),
+                }
+            })
+            .collect())
+    }
+}
+
+impl Curve for CircularMotion {
+    fn length(&self) -> f64 {
+        CircularMotion::length(self)
+    }
+
+    fn target_at_fraction(&self, t: f64) -> MotionTarget {
+        MotionTarget::from_position(self.position_at(t))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use nalgebra::Vector3;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::solver::IterativeSolver;
+    use crate::model::{KinematicParameters, KinematicState};
+    use crate::motion::circular::{ArcDirection, CircularMotion};
+    use crate::motion::{solve_timed_positions, MotionError, TimedPosition, TimedState};
+
+    #[test]
+    pub fn samples_circle() {
+        let circle: CircularMotion = CircularMotion::new(
+            Vector3::<f64>::new(0_f64, 25_f64, 20_f64),
+            5_f64,
+            Vector3::<f64>::new(0_f64, 2_f64, 0_f64),
+        )
+        .unwrap();
+        let samples: Vec<TimedPosition> = circle.timed_positions(2_f64, 0.1_f64).unwrap();
+
+        // The circumference of 10 pi takes 5 pi seconds at 2 units per second.
+        let duration: f64 = 5_f64 * PI;
+        assert_eq!(
+            samples.len(),
+            (duration / 0.1_f64).ceil() as usize + 1_usize
+        );
+        assert!((samples.last().unwrap().time - duration).abs() < 10_f64.powf(-9_f64));
+        assert!(
+            (samples.last().unwrap().position - samples[0].position).magnitude()
+                < 10_f64.powf(-9_f64)
+        );
+
+        // Every sample lies on the circle, in the horizontal plane.
+        assert!(samples.iter().all(|sample| {
+            let offset: Vector3<f64> = sample.position - circle.center;
+
+            (offset.magnitude() - 5_f64).abs() < 10_f64.powf(-9_f64)
+                && offset.y.abs() < 10_f64.powf(-9_f64)
+        }));
+
+        // The tangential speed is constant.
+        assert!(samples.windows(2_usize).all(|pair| {
+            let speed: f64 =
+                (pair[1].position - pair[0].position).magnitude() / (pair[1].time - pair[0].time);
+
+            (speed - 2_f64).abs() < 10_f64.powf(-3_f64)
+        }));
+
+        assert_eq!(
+            circle.timed_positions(0_f64, 0.1_f64),
+            Err(MotionError::InvalidSpeed(0_f64))
+        );
+        assert_eq!(
+            circle.timed_positions(1_f64, -1_f64),
+            Err(MotionError::InvalidPeriod(-1_f64))
+        );
+    }
+
+    #[test]
+    pub fn arc_directions() {
+        let arc = |direction: ArcDirection| -> CircularMotion {
+            CircularMotion::arc(
+                Vector3::<f64>::zeros(),
+                1_f64,
+                Vector3::<f64>::z(),
+                0_f64,
+                FRAC_PI_2,
+                direction,
+            )
+            .unwrap()
+        };
+
+        // Around the z-axis, angles start at the x-axis and run towards the y-axis.
+        assert!(
+            (arc(ArcDirection::CounterClockwise).position_at(1_f64) - Vector3::<f64>::y())
+                .magnitude()
+                < 10_f64.powf(-12_f64)
+        );
+        assert!(
+            (arc(ArcDirection::Clockwise).position_at(1_f64) + Vector3::<f64>::y()).magnitude()
+                < 10_f64.powf(-12_f64)
+        );
+        assert!((arc(ArcDirection::Clockwise).length() - FRAC_PI_2).abs() < 10_f64.powf(-12_f64));
+
+        // Arcs without a plane or without a length are refused.
+        assert_eq!(
+            CircularMotion::new(Vector3::<f64>::zeros(), 1_f64, Vector3::<f64>::zeros()),
+            Err(MotionError::InvalidNormal)
+        );
+        assert_eq!(
+            CircularMotion::new(Vector3::<f64>::zeros(), 0_f64, Vector3::<f64>::z()),
+            Err(MotionError::InvalidRadius(0_f64))
+        );
+        assert_eq!(
+            CircularMotion::arc(
+                Vector3::<f64>::zeros(),
+                1_f64,
+                Vector3::<f64>::z(),
+                0_f64,
+                -FRAC_PI_2,
+                ArcDirection::Clockwise,
+            ),
+            Err(MotionError::InvalidSweep(-FRAC_PI_2))
+        );
+    }
+
+    #[test]
+    pub fn draws_circle() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let circle: CircularMotion = CircularMotion::new(
+            Vector3::<f64>::new(0_f64, 25_f64, 20_f64),
+            5_f64,
+            Vector3::<f64>::y(),
+        )
+        .unwrap();
+
+        let states: Vec<TimedState> = solve_timed_positions(
+            &fk_solver,
+            &ik_solver,
+            &IterativeSolver::default(),
+            &params,
+            &KinematicState {
+                theta_1: 0.5_f64,
+                theta_2: 0.5_f64,
+                ..KinematicState::default()
+            },
+            &circle.timed_positions(5_f64, 0.1_f64).unwrap(),
+        )
+        .unwrap();
+
+        assert!(states
+            .iter()
+            .all(|sample| sample.position_deviation < 10_f64.powf(-3_f64)));
+    }
+}
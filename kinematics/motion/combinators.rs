@@ -0,0 +1,309 @@
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::motion::{Motion, MotionError, MotionTarget};
+
+/// Holds a single target for a duration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Dwell {
+    pub target: MotionTarget,
+    pub duration: f64,
+}
+
+impl Dwell {
+    pub fn new(target: MotionTarget, duration: f64) -> Result<Self, MotionError> {
+        if !duration.is_finite() || duration < 0_f64 {
+            return Err(MotionError::InvalidDuration(duration));
+        }
+
+        Ok(Self { target, duration })
+    }
+}
+
+impl Motion for Dwell {
+    fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    fn target_at(&self, _time: f64) -> MotionTarget {
+        self.target.clone()
+    }
+}
+
+/// Plays motions one after the other.
+///
+/// The motions are not required to connect, a motion that starts away from the end of the
+///  previous one makes the target jump.
+pub struct Sequence {
+    pub motions: Vec<Box<dyn Motion>>,
+}
+
+impl Sequence {
+    pub fn new(motions: Vec<Box<dyn Motion>>) -> Self {
+        Self { motions }
+    }
+
+    /// Append the given motion to the sequence.
+    pub fn push<M: Motion + 'static>(&mut self, motion: M) {
+        self.motions.push(Box::new(motion));
+    }
+}
+
+impl Motion for Sequence {
+    fn duration(&self) -> f64 {
+        self.motions.iter().map(|motion| motion.duration()).sum()
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        let mut start: f64 = 0_f64;
+
+        for motion in self.motions.iter() {
+            let duration: f64 = motion.duration();
+
+            if time <= start + duration {
+                return motion.target_at(time - start);
+            }
+
+            start += duration;
+        }
+
+        // Times past the end of the sequence (or an empty one) hold its end.
+        match self.motions.last() {
+            Some(motion) => motion.end(),
+            None => MotionTarget::from_position(Vector3::<f64>::zeros()),
+        }
+    }
+
+    /// Follow the sequence by the given motion, by appending it instead of nesting.
+    fn then<M: Motion + 'static>(mut self, next: M) -> Sequence
+    where
+        Self: Sized + 'static,
+    {
+        self.push(next);
+
+        self
+    }
+}
+
+/// Plays a motion a number of times in a row.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Repeat<M: Motion> {
+    pub motion: M,
+    pub count: usize,
+}
+
+impl<M: Motion> Repeat<M> {
+    pub fn new(motion: M, count: usize) -> Self {
+        Self { motion, count }
+    }
+}
+
+impl<M: Motion> Motion for Repeat<M> {
+    fn duration(&self) -> f64 {
+        self.motion.duration() * self.count as f64
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        let period: f64 = self.motion.duration();
+
+        let time: f64 = time.max(0_f64);
+
+        if period <= 0_f64 || time >= self.duration() {
+            return self.motion.end();
+        }
+
+        // The end of every repetition is the end of the motion, not the start of the next one.
+        let offset: f64 = time % period;
+
+        if offset == 0_f64 && time > 0_f64 {
+            return self.motion.end();
+        }
+
+        self.motion.target_at(offset)
+    }
+}
+
+/// Plays a motion backwards, from its end to its start.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reverse<M: Motion> {
+    pub motion: M,
+}
+
+impl<M: Motion> Reverse<M> {
+    pub fn new(motion: M) -> Self {
+        Self { motion }
+    }
+}
+
+impl<M: Motion> Motion for Reverse<M> {
+    fn duration(&self) -> f64 {
+        self.motion.duration()
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        self.motion.target_at(self.duration() - time)
+    }
+}
+
+/// Plays a motion at a different rate, a factor above one makes it faster.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimeScale<M: Motion> {
+    pub motion: M,
+    pub factor: f64,
+}
+
+impl<M: Motion> TimeScale<M> {
+    pub fn new(motion: M, factor: f64) -> Result<Self, MotionError> {
+        if !factor.is_finite() || factor <= 0_f64 {
+            return Err(MotionError::InvalidTimeScale(factor));
+        }
+
+        Ok(Self { motion, factor })
+    }
+}
+
+impl<M: Motion> Motion for TimeScale<M> {
+    fn duration(&self) -> f64 {
+        self.motion.duration() / self.factor
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        self.motion.target_at(time * self.factor)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::{Matrix3, Vector3};
+
+    use crate::model::Pose;
+    use crate::motion::circular::{ArcDirection, CircularMotion};
+    use crate::motion::combinators::{Dwell, Repeat, Reverse, Sequence, TimeScale};
+    use crate::motion::linear::LinearMove;
+    use crate::motion::{CurveMotion, Motion, MotionError, MotionTarget, TimedTarget};
+
+    fn line(from: Vector3<f64>, to: Vector3<f64>) -> CurveMotion<LinearMove> {
+        CurveMotion::new(
+            LinearMove::new(
+                Pose::new(from, Matrix3::<f64>::identity()),
+                Pose::new(to, Matrix3::<f64>::identity()),
+            ),
+            2_f64,
+        )
+        .unwrap()
+    }
+
+    fn close(a: &MotionTarget, b: Vector3<f64>) -> bool {
+        (a.position - b).magnitude() < 10_f64.powf(-9_f64)
+    }
+
+    #[test]
+    pub fn composes_program() {
+        let home: Vector3<f64> = Vector3::<f64>::new(5_f64, 20_f64, 0_f64);
+        let corner: Vector3<f64> = Vector3::<f64>::new(5_f64, 20_f64, 4_f64);
+
+        // Line, half a circle around the z-axis, wait 500 ms, and return.
+        let arc: CircularMotion = CircularMotion::arc(
+            Vector3::<f64>::new(3_f64, 20_f64, 4_f64),
+            2_f64,
+            Vector3::<f64>::z(),
+            0_f64,
+            PI,
+            ArcDirection::CounterClockwise,
+        )
+        .unwrap();
+        let arc_end: Vector3<f64> = arc.position_at(1_f64);
+        let program: Sequence = line(home, corner)
+            .then(CurveMotion::new(arc, 2_f64).unwrap())
+            .then_dwell(0.5_f64)
+            .unwrap()
+            .then(line(arc_end, home));
+
+        // At 2 units per second, the line takes two seconds and the arc (of length 2 pi) pi seconds.
+        let return_time: f64 = (home - arc_end).magnitude() / 2_f64;
+        assert_eq!(program.motions.len(), 4_usize);
+        assert!((program.duration() - (2.5_f64 + PI + return_time)).abs() < 10_f64.powf(-9_f64));
+
+        assert!(close(&program.start(), home));
+        assert!(close(&program.target_at(2_f64), corner));
+        assert!(close(&program.target_at(2_f64 + PI), arc_end));
+        assert!(close(&program.target_at(2.25_f64 + PI), arc_end));
+        assert!(close(&program.end(), home));
+        assert!(close(&program.target_at(100_f64), home));
+
+        // Lines prescribe the orientation, arcs only the position.
+        assert!(program.target_at(1_f64).orientation.is_some());
+        assert!(program.target_at(3_f64).orientation.is_none());
+
+        // The preview is continuous, since every motion starts where the previous one ends.
+        let preview: Vec<TimedTarget> = program.sample(0.01_f64).unwrap();
+        assert!((preview.last().unwrap().time - program.duration()).abs() < 10_f64.powf(-9_f64));
+        assert!(preview.windows(2_usize).all(|pair| {
+            (pair[1].target.position - pair[0].target.position).magnitude()
+                <= 0.02_f64 + 10_f64.powf(-9_f64)
+        }));
+    }
+
+    #[test]
+    pub fn transforms_timing() {
+        let from: Vector3<f64> = Vector3::<f64>::zeros();
+        let to: Vector3<f64> = Vector3::<f64>::new(4_f64, 0_f64, 0_f64);
+
+        // Backwards, the line runs from its end to its start.
+        let reversed: Reverse<CurveMotion<LinearMove>> = line(from, to).reversed();
+        assert!((reversed.duration() - 2_f64).abs() < 10_f64.powf(-12_f64));
+        assert!(close(&reversed.start(), to));
+        assert!(close(
+            &reversed.target_at(0.5_f64),
+            Vector3::<f64>::new(3_f64, 0_f64, 0_f64)
+        ));
+
+        // Twice as fast takes half the time.
+        let fast: TimeScale<CurveMotion<LinearMove>> = line(from, to).time_scaled(2_f64).unwrap();
+        assert!((fast.duration() - 1_f64).abs() < 10_f64.powf(-12_f64));
+        assert!(close(
+            &fast.target_at(0.5_f64),
+            Vector3::<f64>::new(2_f64, 0_f64, 0_f64)
+        ));
+        assert!(matches!(
+            line(from, to).time_scaled(0_f64),
+            Err(MotionError::InvalidTimeScale(_))
+        ));
+
+        // Dwelling a negative or endless time is refused, as is previewing an endless motion.
+        for duration in [-1_f64, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                line(from, to).then_dwell(duration),
+                Err(MotionError::InvalidDuration(_))
+            ));
+        }
+        assert_eq!(
+            Dwell {
+                target: line(from, to).end(),
+                duration: f64::INFINITY,
+            }
+            .sample(0.1_f64),
+            Err(MotionError::InvalidDuration(f64::INFINITY))
+        );
+
+        // Repeating jumps back to the start for every repetition, and ends on the end.
+        let repeated: Repeat<CurveMotion<LinearMove>> = line(from, to).repeat(3_usize);
+        assert!((repeated.duration() - 6_f64).abs() < 10_f64.powf(-12_f64));
+        assert!(close(
+            &repeated.target_at(2.5_f64),
+            Vector3::<f64>::new(1_f64, 0_f64, 0_f64)
+        ));
+        assert!(close(&repeated.end(), to));
+
+        // Between repetitions the line holds its end, and only the next instant jumps back.
+        assert!(close(&repeated.target_at(0_f64), from));
+        assert!(close(&repeated.target_at(2_f64), to));
+        assert!(close(&repeated.target_at(4_f64), to));
+        assert!(close(
+            &repeated.target_at(4.5_f64),
+            Vector3::<f64>::new(1_f64, 0_f64, 0_f64)
+        ));
+    }
+}
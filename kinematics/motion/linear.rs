@@ -0,0 +1,391 @@
+use nalgebra::{Rotation3, Vector3, Vector5};
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{InverseKinematicAlgorithm, PoseWeights};
+use crate::inverse::solver::{IterativeSolver, SolverReport};
+use crate::model::{KinematicParameters, KinematicState, Pose};
+use crate::motion::{Curve, MotionError, MotionTarget};
+
+/// A straight move of the tool between two poses, the position is interpolated linearly and the
+///  orientation rotates around a fixed axis at a constant rate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinearMove {
+    pub start: Pose,
+    pub end: Pose,
+}
+
+impl LinearMove {
+    pub fn new(start: Pose, end: Pose) -> Self {
+        Self { start, end }
+    }
+
+    /// Get the distance between the start and end positions.
+    pub fn length(&self) -> f64 {
+        (self.end.position - self.start.position).magnitude()
+    }
+
+    /// Get the angle between the start and end orientations.
+    pub fn angle(&self) -> f64 {
+        self.rotation().magnitude()
+    }
+
+    /// Get the pose at the given fraction (between zero and one) of the move.
+    pub fn pose_at(&self, t: f64) -> Pose {
+        Pose::new(
+            self.start.position + (self.end.position - self.start.position) * t,
+            self.start.orientation * Rotation3::<f64>::new(self.rotation() * t).matrix(),
+        )
+    }
+
+    /// Get the rotation vector from the start to the end orientation, expressed in the start
+    ///  frame.
+    fn rotation(&self) -> Vector3<f64> {
+        Rotation3::<f64>::from_matrix_unchecked(
+            self.start.orientation.transpose() * self.end.orientation,
+        )
+        .scaled_axis()
+    }
+}
+
+impl Curve for LinearMove {
+    fn length(&self) -> f64 {
+        LinearMove::length(self)
+    }
+
+    fn target_at_fraction(&self, t: f64) -> MotionTarget {
+        let pose: Pose = self.pose_at(t);
+
+        MotionTarget::new(pose.position, Some(pose.orientation))
+    }
+}
+
+/// A single sample of a followed linear move.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinearSample {
+    /// The pose on the line.
+    pub target: Pose,
+    /// The state that was solved for the pose.
+    pub state: KinematicState,
+    /// The distance between the end-effector and the target position.
+    pub position_deviation: f64,
+    /// The angle between the end-effector and the target orientation.
+    pub orientation_deviation: f64,
+    /// Whether the orientation was solved for, or dropped because it could not be reached.
+    pub orientation_followed: bool,
+    /// The largest change of a single joint from the previous sample.
+    pub joint_step: f64,
+    /// The report of the solve that produced the state.
+    pub report: SolverReport,
+}
+
+/// The samples of a followed linear move, from the start to the end pose.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinearPath {
+    pub samples: Vec<LinearSample>,
+}
+
+impl LinearPath {
+    /// Get the states of all the samples.
+    pub fn states(&self) -> Vec<KinematicState> {
+        self.samples
+            .iter()
+            .map(|sample| sample.state.clone())
+            .collect()
+    }
+
+    /// Get the largest distance between the end-effector and the line.
+    pub fn max_position_deviation(&self) -> f64 {
+        self.samples
+            .iter()
+            .map(|sample| sample.position_deviation)
+            .fold(0_f64, f64::max)
+    }
+
+    /// Get the largest angle between the end-effector and the interpolated orientation.
+    pub fn max_orientation_deviation(&self) -> f64 {
+        self.samples
+            .iter()
+            .map(|sample| sample.orientation_deviation)
+            .fold(0_f64, f64::max)
+    }
+
+    /// Get the largest change of a single joint between two consecutive samples, a large one
+    ///  means that the arm had to jump to another configuration to stay on the line.
+    pub fn max_joint_step(&self) -> f64 {
+        self.samples
+            .iter()
+            .map(|sample| sample.joint_step)
+            .fold(0_f64, f64::max)
+    }
+
+    /// Check if the line was followed within the given position and orientation deviations.
+    pub fn is_followed(&self, position_tolerance: f64, orientation_tolerance: f64) -> bool {
+        self.max_position_deviation() <= position_tolerance
+            && self.max_orientation_deviation() <= orientation_tolerance
+    }
+}
+
+/// Follows linear moves by sampling them at a fixed resolution, and solving the inverse
+///  kinematics of every sample starting from the state of the previous one.
+///
+/// Every sample is first solved for the full pose, when that does not converge (since the arm
+///  only has five degrees of freedom) the orientation is dropped and only the position is solved
+///  for. The remaining deviations are reported per sample.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinearMoveFollower {
+    /// The maximum distance between the positions of two consecutive samples.
+    pub resolution: f64,
+    /// The maximum angle between the orientations of two consecutive samples.
+    pub angular_resolution: f64,
+    /// The solver used for every sample.
+    pub solver: IterativeSolver,
+    /// The weights of the pose solves.
+    pub weights: PoseWeights,
+}
+
+impl Default for LinearMoveFollower {
+    fn default() -> Self {
+        Self {
+            resolution: 0.5_f64,
+            angular_resolution: 0.05_f64,
+            solver: IterativeSolver::default(),
+            weights: PoseWeights::default(),
+        }
+    }
+}
+
+impl LinearMoveFollower {
+    pub fn new(resolution: f64, angular_resolution: f64) -> Self {
+        Self {
+            resolution,
+            angular_resolution,
+            ..Self::default()
+        }
+    }
+
+    /// Get the number of segments the given move is divided into, fails when one of the
+    ///  resolutions is not positive.
+    pub fn segments(&self, motion: &LinearMove) -> Result<usize, MotionError> {
+        for resolution in [self.resolution, self.angular_resolution] {
+            if resolution.is_nan() || resolution <= 0_f64 {
+                return Err(MotionError::InvalidResolution(resolution));
+            }
+        }
+
+        Ok((motion.length() / self.resolution)
+            .max(motion.angle() / self.angular_resolution)
+            .ceil()
+            .max(1_f64) as usize)
+    }
+
+    /// Follow the given move starting from the given state, the first sample lies on the start
+    ///  pose and the last one on the end pose.
+    ///
+    /// Fails when one of the resolutions is not positive, or when a sample can not be solved at
+    ///  all, like a position out of reach.
+    pub fn follow(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        ik: &dyn InverseKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        motion: &LinearMove,
+    ) -> Result<LinearPath, MotionError> {
+        let segments: usize = self.segments(motion)?;
+        let mut state: KinematicState = state.clone();
+        let mut samples: Vec<LinearSample> = Vec::with_capacity(segments + 1_usize);
+
+        for i in 0..=segments {
+            let target: Pose = motion.pose_at(i as f64 / segments as f64);
+
+            // Solve for the full pose, and fall back to the position alone when the pose can not
+            //  be reached.
+            let (pose_state, pose_report) =
+                self.solver
+                    .solve_limb4_pose(fk, ik, params, &state, &target, &self.weights)?;
+
+            let (next, report, orientation_followed) = if pose_report.converged() {
+                (pose_state, pose_report, true)
+            } else {
+                let (next, report) =
+                    self.solver
+                        .solve_limb4_position(fk, ik, params, &state, &target.position)?;
+
+                (next, report, false)
+            };
+
+            let pose: Pose = fk.limb4_pose(params, &next);
+
+            samples.push(LinearSample {
+                position_deviation: (target.position - pose.position).magnitude(),
+                orientation_deviation: Rotation3::<f64>::from_matrix_unchecked(
+                    target.orientation.transpose() * pose.orientation,
+                )
+                .angle(),
+                orientation_followed,
+                joint_step: (Vector5::<f64>::from(&next) - Vector5::<f64>::from(&state)).amax(),
+                state: next.clone(),
+                target,
+                report,
+            });
+
+            state = next;
+        }
+
+        Ok(LinearPath { samples })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{Rotation3, Vector3};
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::InverseKinematicError;
+    use crate::model::{KinematicParameters, KinematicState, Pose};
+    use crate::motion::linear::{LinearMove, LinearMoveFollower, LinearPath};
+    use crate::motion::MotionError;
+
+    #[test]
+    pub fn follows_reachable_line() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let follower: LinearMoveFollower = LinearMoveFollower::default();
+
+        // Push the tool straight down, like inserting a pin, keeping its orientation.
+        let state: KinematicState = KinematicState {
+            theta_1: 0.6_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.4_f64,
+            ..KinematicState::default()
+        };
+        let start: Pose = fk_solver.limb4_pose(&params, &state);
+        let end: Pose = Pose::new(
+            start.position - Vector3::<f64>::new(0_f64, 5_f64, 0_f64),
+            start.orientation,
+        );
+        let motion: LinearMove = LinearMove::new(start, end.clone());
+
+        let path: LinearPath = follower
+            .follow(&fk_solver, &ik_solver, &params, &state, &motion)
+            .unwrap();
+
+        assert_eq!(path.samples.len(), 11_usize);
+        assert!(path
+            .samples
+            .iter()
+            .all(|sample| sample.orientation_followed));
+        assert!(path.is_followed(10_f64.powf(-3_f64), 10_f64.powf(-3_f64)));
+        assert!(path.max_joint_step() < 0.2_f64);
+        assert!(
+            (fk_solver.limb4_position_vector(&params, &path.states()[10]) - end.position)
+                .magnitude()
+                < 10_f64.powf(-3_f64)
+        );
+    }
+
+    #[test]
+    pub fn reports_deviation() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+        let follower: LinearMoveFollower = LinearMoveFollower::default();
+
+        // Tilting the tool sideways around the axis of the pitch joints can not be done by an arm
+        //  with five joints, so only the position is followed.
+        let state: KinematicState = KinematicState {
+            theta_1: 0.6_f64,
+            theta_2: 0.8_f64,
+            ..KinematicState::default()
+        };
+        let start: Pose = fk_solver.limb4_pose(&params, &state);
+        let tilted: Pose = Pose::new(
+            start.position + Vector3::<f64>::new(0_f64, 0_f64, 3_f64),
+            start.orientation
+                * Rotation3::<f64>::from_axis_angle(&Vector3::<f64>::z_axis(), 0.5_f64).matrix(),
+        );
+
+        let path: LinearPath = follower
+            .follow(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &state,
+                &LinearMove::new(start.clone(), tilted),
+            )
+            .unwrap();
+
+        assert!(path
+            .samples
+            .iter()
+            .skip(1_usize)
+            .any(|sample| !sample.orientation_followed));
+        assert!(path.max_position_deviation() < 10_f64.powf(-3_f64));
+        assert!(path.max_orientation_deviation() > 0.1_f64);
+        assert!(!path.is_followed(10_f64.powf(-3_f64), 10_f64.powf(-3_f64)));
+
+        // A line that leaves the reach of the arm can not be followed at all.
+        let far: Pose = Pose::new(
+            Vector3::<f64>::new(0_f64, 10_f64, 60_f64),
+            start.orientation,
+        );
+
+        assert!(matches!(
+            follower.follow(
+                &fk_solver,
+                &ik_solver,
+                &params,
+                &state,
+                &LinearMove::new(start, far)
+            ),
+            Err(MotionError::Inverse(InverseKinematicError::Unreachable(_)))
+        ));
+    }
+
+    #[test]
+    pub fn refuses_invalid_resolution() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let ik_solver: HeuristicInverseKinematicAlgorithm =
+            HeuristicInverseKinematicAlgorithm::default();
+
+        let state: KinematicState = KinematicState::default();
+        let start: Pose = fk_solver.limb4_pose(&params, &state);
+        let motion: LinearMove = LinearMove::new(
+            start.clone(),
+            Pose::new(
+                start.position - Vector3::<f64>::new(0_f64, 5_f64, 0_f64),
+                start.orientation,
+            ),
+        );
+
+        for (resolution, angular_resolution) in [
+            (0_f64, 0.05_f64),
+            (-1_f64, 0.05_f64),
+            (0.5_f64, 0_f64),
+            (0.5_f64, -0.05_f64),
+            (f64::NAN, 0.05_f64),
+        ] {
+            let follower: LinearMoveFollower =
+                LinearMoveFollower::new(resolution, angular_resolution);
+
+            assert!(matches!(
+                follower.segments(&motion),
+                Err(MotionError::InvalidResolution(_))
+            ));
+            assert!(matches!(
+                follower.follow(&fk_solver, &ik_solver, &params, &state, &motion),
+                Err(MotionError::InvalidResolution(_))
+            ));
+        }
+    }
+}
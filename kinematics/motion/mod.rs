@@ -1 +1,268 @@
-pub mod circular;
\ No newline at end of file
+use nalgebra::{Matrix3, Vector3};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::{InverseKinematicAlgorithm, InverseKinematicError};
+use crate::inverse::solver::IterativeSolver;
+use crate::model::{KinematicParameters, KinematicState};
+use crate::motion::combinators::{Dwell, Repeat, Reverse, Sequence, TimeScale};
+
+pub mod circular;
+pub mod combinators;
+pub mod linear;
+pub mod profile;
+pub mod spline;
+pub mod timing;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum MotionError {
+    #[error("Invalid speed {0}, it must be positive")]
+    InvalidSpeed(f64),
+    #[error("Invalid sample period {0}, it must be positive")]
+    InvalidPeriod(f64),
+    #[error("Invalid time scale {0}, it must be positive")]
+    InvalidTimeScale(f64),
+    #[error("Invalid duration {0}, it must be finite and not negative")]
+    InvalidDuration(f64),
+    #[error("Invalid joint dynamics limit {0}, it must be positive and finite")]
+    InvalidDynamicsLimit(f64),
+    #[error("A spline needs at least two waypoints, got {0}")]
+    TooFewWaypoints(usize),
+    #[error("The time of waypoint {0} does not lie after the one of the previous waypoint")]
+    NonIncreasingTimes(usize),
+    #[error("The time of waypoint {0} is not finite")]
+    InvalidWaypointTime(usize),
+    #[error("The spline through the waypoints could not be solved")]
+    UnsolvableSpline,
+    #[error("Invalid path resolution {0}, it must be positive")]
+    InvalidResolution(f64),
+    #[error("Invalid radius {0}, it must be positive")]
+    InvalidRadius(f64),
+    #[error("Invalid sweep {0}, it must be positive")]
+    InvalidSweep(f64),
+    #[error("The normal of the plane must not be zero")]
+    InvalidNormal,
+    #[error("Failed to solve the inverse kinematics of a sample, error: {0}")]
+    Inverse(#[from] InverseKinematicError),
+}
+
+/// The target of the tool at a point of a motion, the orientation is only given by motions that
+///  prescribe one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MotionTarget {
+    pub position: Vector3<f64>,
+    pub orientation: Option<Matrix3<f64>>,
+}
+
+impl MotionTarget {
+    pub fn new(position: Vector3<f64>, orientation: Option<Matrix3<f64>>) -> Self {
+        Self {
+            position,
+            orientation,
+        }
+    }
+
+    /// Create a target that only prescribes the position of the tool.
+    pub fn from_position(position: Vector3<f64>) -> Self {
+        Self::new(position, None)
+    }
+}
+
+/// A target at a point in time (in seconds) since the start of a motion.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedTarget {
+    pub time: f64,
+    pub target: MotionTarget,
+}
+
+/// A time-parameterized motion of the tool, which starts at time zero and ends after its
+///  duration. Motions are `Send`, so they can be handed to the arm task to be run.
+pub trait Motion: Send {
+    /// Get the duration of the motion, in seconds.
+    fn duration(&self) -> f64;
+
+    /// Get the target at the given time, which is clamped into the duration of the motion.
+    fn target_at(&self, time: f64) -> MotionTarget;
+
+    /// Get the target at the start of the motion.
+    fn start(&self) -> MotionTarget {
+        self.target_at(0_f64)
+    }
+
+    /// Get the target at the end of the motion.
+    fn end(&self) -> MotionTarget {
+        self.target_at(self.duration())
+    }
+
+    /// Sample the targets of the motion at intervals of at most the given period, to preview it
+    ///  before running it. The first and last samples lie on the start and end of the motion.
+    ///
+    /// Fails when the duration of the motion is not finite, since it can not be sampled up to its
+    ///  end.
+    fn sample(&self, period: f64) -> Result<Vec<TimedTarget>, MotionError> {
+        if period.is_nan() || period <= 0_f64 {
+            return Err(MotionError::InvalidPeriod(period));
+        }
+
+        let duration: f64 = self.duration();
+
+        if !duration.is_finite() || duration < 0_f64 {
+            return Err(MotionError::InvalidDuration(duration));
+        }
+        let intervals: usize = (duration / period).ceil().max(1_f64) as usize;
+
+        Ok((0..=intervals)
+            .map(|i| {
+                let time: f64 = duration * i as f64 / intervals as f64;
+
+                TimedTarget {
+                    time,
+                    target: self.target_at(time),
+                }
+            })
+            .collect())
+    }
+
+    /// Follow this motion by the given one.
+    fn then<M: Motion + 'static>(self, next: M) -> Sequence
+    where
+        Self: Sized + 'static,
+    {
+        Sequence::new(vec![Box::new(self), Box::new(next)])
+    }
+
+    /// Hold the end of this motion for the given duration, in seconds.
+    fn then_dwell(self, duration: f64) -> Result<Sequence, MotionError>
+    where
+        Self: Sized + 'static,
+    {
+        let dwell: Dwell = Dwell::new(self.end(), duration)?;
+
+        Ok(self.then(dwell))
+    }
+
+    /// Play this motion the given number of times in a row.
+    fn repeat(self, count: usize) -> Repeat<Self>
+    where
+        Self: Sized,
+    {
+        Repeat::new(self, count)
+    }
+
+    /// Play this motion backwards.
+    fn reversed(self) -> Reverse<Self>
+    where
+        Self: Sized,
+    {
+        Reverse::new(self)
+    }
+
+    /// Play this motion faster (a factor above one) or slower (a factor below one).
+    fn time_scaled(self, factor: f64) -> Result<TimeScale<Self>, MotionError>
+    where
+        Self: Sized,
+    {
+        TimeScale::new(self, factor)
+    }
+}
+
+impl Motion for Box<dyn Motion> {
+    fn duration(&self) -> f64 {
+        self.as_ref().duration()
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        self.as_ref().target_at(time)
+    }
+}
+
+/// A geometric curve of the tool, parameterized over the fraction of its length.
+pub trait Curve {
+    /// Get the length of the curve.
+    fn length(&self) -> f64;
+
+    /// Get the target at the given fraction (between zero and one) of the curve.
+    fn target_at_fraction(&self, t: f64) -> MotionTarget;
+}
+
+/// A curve traversed at a constant speed along it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CurveMotion<C: Curve> {
+    pub curve: C,
+    pub speed: f64,
+}
+
+impl<C: Curve> CurveMotion<C> {
+    pub fn new(curve: C, speed: f64) -> Result<Self, MotionError> {
+        if speed.is_nan() || speed <= 0_f64 {
+            return Err(MotionError::InvalidSpeed(speed));
+        }
+
+        Ok(Self { curve, speed })
+    }
+}
+
+impl<C: Curve + Send> Motion for CurveMotion<C> {
+    fn duration(&self) -> f64 {
+        self.curve.length() / self.speed
+    }
+
+    fn target_at(&self, time: f64) -> MotionTarget {
+        let duration: f64 = self.duration();
+
+        // A curve without length is done as soon as it starts.
+        let t: f64 = if duration > 0_f64 {
+            (time / duration).clamp(0_f64, 1_f64)
+        } else {
+            1_f64
+        };
+
+        self.curve.target_at_fraction(t)
+    }
+}
+
+/// A tool position at a point in time (in seconds) since the start of a motion.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedPosition {
+    pub time: f64,
+    pub position: Vector3<f64>,
+}
+
+/// A joint state at a point in time (in seconds) since the start of a motion.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedState {
+    pub time: f64,
+    pub state: KinematicState,
+    /// The distance between the end-effector in the state and the requested tool position.
+    pub position_deviation: f64,
+}
+
+/// Convert timed tool positions into joint states, by solving the position of the end-effector
+///  of every sample starting from the state of the previous one.
+pub fn solve_timed_positions(
+    fk: &dyn ForwardKinematicAlgorithm,
+    ik: &dyn InverseKinematicAlgorithm,
+    solver: &IterativeSolver,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    samples: &[TimedPosition],
+) -> Result<Vec<TimedState>, MotionError> {
+    let mut state: KinematicState = state.clone();
+
+    samples
+        .iter()
+        .map(|sample| {
+            let (next, _) =
+                solver.solve_limb4_position(fk, ik, params, &state, &sample.position)?;
+            state = next.clone();
+
+            Ok(TimedState {
+                time: sample.time,
+                position_deviation: (fk.limb4_position_vector(params, &next) - sample.position)
+                    .magnitude(),
+                state: next,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,448 @@
+use nalgebra::Vector5;
+use serde::{Deserialize, Serialize};
+
+use crate::model::KinematicState;
+use crate::motion::MotionError;
+
+/// The number of bisection steps used to find the peak velocity of a short S-curve move, which
+///  determines it far below the precision of a double.
+const PEAK_VELOCITY_ITERATIONS: usize = 100_usize;
+
+/// The per-joint limits of a joint-space move, in radians per second (squared, cubed).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointDynamicsLimits {
+    pub velocity: Vector5<f64>,
+    pub acceleration: Vector5<f64>,
+    /// The jerk limits, which are only used by S-curve profiles.
+    pub jerk: Vector5<f64>,
+}
+
+impl JointDynamicsLimits {
+    pub fn new(velocity: Vector5<f64>, acceleration: Vector5<f64>, jerk: Vector5<f64>) -> Self {
+        Self {
+            velocity,
+            acceleration,
+            jerk,
+        }
+    }
+
+    /// Create limits that are the same for every joint.
+    pub fn uniform(velocity: f64, acceleration: f64, jerk: f64) -> Self {
+        Self::new(
+            Vector5::<f64>::repeat(velocity),
+            Vector5::<f64>::repeat(acceleration),
+            Vector5::<f64>::repeat(jerk),
+        )
+    }
+}
+
+/// The shape of the velocity of a move.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProfileShape {
+    /// Constant acceleration up to the cruise velocity, the acceleration jumps between phases.
+    Trapezoidal,
+    /// Constant jerk up to the maximum acceleration, so the acceleration changes continuously.
+    SCurve,
+}
+
+/// A phase of a profile, in which the jerk is constant.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct ProfileSegment {
+    pub duration: f64,
+    /// The acceleration at the start of the phase.
+    pub acceleration: f64,
+    pub jerk: f64,
+}
+
+/// The position, velocity and acceleration of all the joints at a point in time (in seconds)
+///  since the start of a move.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointSample {
+    pub time: f64,
+    pub state: KinematicState,
+    pub velocity: Vector5<f64>,
+    pub acceleration: Vector5<f64>,
+}
+
+/// A joint-space move in which all the joints start and finish together.
+///
+/// The joints are synchronized by moving them along the same normalized profile, which runs from
+///  zero to one. Its limits are the tightest of the joint limits divided by the distances the
+///  joints travel, so the joint with the longest move (relative to its limits) moves as fast as it
+///  may and the others follow it proportionally.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointProfile {
+    pub start: KinematicState,
+    pub end: KinematicState,
+    pub shape: ProfileShape,
+    /// The phases of the normalized profile.
+    pub segments: Vec<ProfileSegment>,
+}
+
+impl JointProfile {
+    /// Create the fastest synchronized move between the given states within the given limits,
+    ///  which must all be positive.
+    pub fn new(
+        start: &KinematicState,
+        end: &KinematicState,
+        limits: &JointDynamicsLimits,
+        shape: ProfileShape,
+    ) -> Result<Self, MotionError> {
+        let mut checked: Vec<&Vector5<f64>> = vec![&limits.velocity, &limits.acceleration];
+
+        if shape == ProfileShape::SCurve {
+            checked.push(&limits.jerk);
+        }
+
+        if let Some(limit) = checked
+            .into_iter()
+            .flat_map(|limits| limits.iter())
+            .find(|limit| limit.is_nan() || **limit <= 0_f64)
+        {
+            return Err(MotionError::InvalidDynamicsLimit(*limit));
+        }
+
+        let delta: Vector5<f64> = Vector5::<f64>::from(end) - Vector5::<f64>::from(start);
+
+        // Scale the limits of every moving joint onto the normalized profile.
+        let normalized = |limits: &Vector5<f64>| -> f64 {
+            limits
+                .iter()
+                .zip(delta.iter())
+                .filter(|(_, distance)| **distance != 0_f64)
+                .map(|(limit, distance)| limit / distance.abs())
+                .fold(f64::INFINITY, f64::min)
+        };
+
+        let segments: Vec<ProfileSegment> = if delta.iter().all(|distance| *distance == 0_f64) {
+            Vec::new()
+        } else {
+            match shape {
+                ProfileShape::Trapezoidal => Self::trapezoidal_segments(
+                    normalized(&limits.velocity),
+                    normalized(&limits.acceleration),
+                ),
+                ProfileShape::SCurve => Self::s_curve_segments(
+                    normalized(&limits.velocity),
+                    normalized(&limits.acceleration),
+                    normalized(&limits.jerk),
+                ),
+            }
+        };
+
+        Ok(Self {
+            start: start.clone(),
+            end: end.clone(),
+            shape,
+            segments,
+        })
+    }
+
+    /// Get the duration of the move, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Get the state at the given time, which is clamped into the duration of the move.
+    pub fn state_at(&self, time: f64) -> KinematicState {
+        if time >= self.duration() {
+            return self.end.clone();
+        }
+
+        let (position, _, _) = self.normalized_at(time);
+
+        KinematicState::from(Vector5::<f64>::from(&self.start) + self.delta() * position)
+    }
+
+    /// Get the joint velocities at the given time.
+    pub fn velocity_at(&self, time: f64) -> Vector5<f64> {
+        self.delta() * self.normalized_at(time).1
+    }
+
+    /// Get the joint accelerations at the given time.
+    pub fn acceleration_at(&self, time: f64) -> Vector5<f64> {
+        self.delta() * self.normalized_at(time).2
+    }
+
+    /// Sample the move at intervals of at most the given period, the first and last samples lie
+    ///  on the start and end of the move.
+    pub fn sample(&self, period: f64) -> Result<Vec<JointSample>, MotionError> {
+        if period.is_nan() || period <= 0_f64 {
+            return Err(MotionError::InvalidPeriod(period));
+        }
+
+        let duration: f64 = self.duration();
+        let intervals: usize = (duration / period).ceil().max(1_f64) as usize;
+
+        Ok((0..=intervals)
+            .map(|i| {
+                let time: f64 = duration * i as f64 / intervals as f64;
+
+                JointSample {
+                    time,
+                    state: self.state_at(time),
+                    velocity: self.velocity_at(time),
+                    acceleration: self.acceleration_at(time),
+                }
+            })
+            .collect())
+    }
+
+    fn delta(&self) -> Vector5<f64> {
+        Vector5::<f64>::from(&self.end) - Vector5::<f64>::from(&self.start)
+    }
+
+    /// Get the position, velocity and acceleration of the normalized profile at the given time,
+    ///  by integrating its phases.
+    fn normalized_at(&self, time: f64) -> (f64, f64, f64) {
+        let mut remaining: f64 = time.max(0_f64);
+        let (mut position, mut velocity) = (0_f64, 0_f64);
+
+        for segment in self.segments.iter() {
+            let dt: f64 = remaining.min(segment.duration);
+            let (a, j) = (segment.acceleration, segment.jerk);
+
+            if remaining <= segment.duration {
+                return (
+                    position + velocity * dt + a * dt.powi(2) / 2_f64 + j * dt.powi(3) / 6_f64,
+                    velocity + a * dt + j * dt.powi(2) / 2_f64,
+                    a + j * dt,
+                );
+            }
+
+            position += velocity * dt + a * dt.powi(2) / 2_f64 + j * dt.powi(3) / 6_f64;
+            velocity += a * dt + j * dt.powi(2) / 2_f64;
+            remaining -= dt;
+        }
+
+        (1_f64, 0_f64, 0_f64)
+    }
+
+    /// Compute the phases of a trapezoidal profile over a unit distance, which becomes a triangle
+    ///  when the distance is too short to reach the velocity limit.
+    fn trapezoidal_segments(velocity: f64, acceleration: f64) -> Vec<ProfileSegment> {
+        let peak: f64 = velocity.min(acceleration.sqrt());
+        let accelerating: f64 = peak / acceleration;
+        let cruising: f64 = (1_f64 - peak.powi(2) / acceleration) / peak;
+
+        vec![
+            ProfileSegment {
+                duration: accelerating,
+                acceleration,
+                jerk: 0_f64,
+            },
+            ProfileSegment {
+                duration: cruising.max(0_f64),
+                acceleration: 0_f64,
+                jerk: 0_f64,
+            },
+            ProfileSegment {
+                duration: accelerating,
+                acceleration: -acceleration,
+                jerk: 0_f64,
+            },
+        ]
+    }
+
+    /// Compute the phases of an S-curve profile over a unit distance, in which the velocity is
+    ///  lowered when the distance is too short to reach it.
+    fn s_curve_segments(velocity: f64, acceleration: f64, jerk: f64) -> Vec<ProfileSegment> {
+        // Get the times of the jerk and constant acceleration phases of speeding up to the given
+        //  velocity, and the distance that takes.
+        let ramp = |velocity: f64| -> (f64, f64, f64) {
+            let jerking: f64 = (acceleration / jerk).min((velocity / jerk).sqrt());
+            let constant: f64 = velocity / (jerk * jerking) - jerking;
+
+            (
+                jerking,
+                constant,
+                velocity * (2_f64 * jerking + constant) / 2_f64,
+            )
+        };
+
+        // When speeding up and down covers more than the distance, find the peak velocity for
+        //  which it covers exactly the distance.
+        let peak: f64 = if 2_f64 * ramp(velocity).2 <= 1_f64 {
+            velocity
+        } else {
+            let (mut low, mut high) = (0_f64, velocity);
+
+            for _ in 0..PEAK_VELOCITY_ITERATIONS {
+                let middle: f64 = (low + high) / 2_f64;
+
+                if 2_f64 * ramp(middle).2 <= 1_f64 {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+
+            low
+        };
+
+        let (jerking, constant, distance) = ramp(peak);
+        let reached: f64 = jerk * jerking;
+        let phase = |duration: f64, acceleration: f64, jerk: f64| -> ProfileSegment {
+            ProfileSegment {
+                duration,
+                acceleration,
+                jerk,
+            }
+        };
+
+        vec![
+            phase(jerking, 0_f64, jerk),
+            phase(constant, reached, 0_f64),
+            phase(jerking, reached, -jerk),
+            phase((1_f64 - 2_f64 * distance) / peak, 0_f64, 0_f64),
+            phase(jerking, 0_f64, -jerk),
+            phase(constant, -reached, 0_f64),
+            phase(jerking, -reached, jerk),
+        ]
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector5;
+
+    use crate::model::KinematicState;
+    use crate::motion::profile::{JointDynamicsLimits, JointProfile, JointSample, ProfileShape};
+    use crate::motion::MotionError;
+
+    /// Check that the sampled move stays within the limits, and that all the joints travel the
+    ///  same fraction of their distance at every point in time.
+    fn assert_within_limits(profile: &JointProfile, limits: &JointDynamicsLimits) {
+        let delta: Vector5<f64> =
+            Vector5::<f64>::from(&profile.end) - Vector5::<f64>::from(&profile.start);
+        let samples: Vec<JointSample> = profile.sample(0.001_f64).unwrap();
+
+        for sample in samples.iter() {
+            assert!(sample
+                .velocity
+                .iter()
+                .zip(limits.velocity.iter())
+                .all(|(velocity, limit)| velocity.abs() <= limit + 10_f64.powf(-9_f64)));
+            assert!(sample
+                .acceleration
+                .iter()
+                .zip(limits.acceleration.iter())
+                .all(|(acceleration, limit)| acceleration.abs() <= limit + 10_f64.powf(-9_f64)));
+
+            let travelled: Vector5<f64> =
+                Vector5::<f64>::from(&sample.state) - Vector5::<f64>::from(&profile.start);
+            let fraction: f64 = travelled.x / delta.x;
+            assert!((travelled - delta * fraction).amax() < 10_f64.powf(-9_f64));
+        }
+
+        assert_eq!(samples.last().unwrap().state, profile.end);
+    }
+
+    #[test]
+    pub fn trapezoidal_moves() {
+        let limits: JointDynamicsLimits = JointDynamicsLimits::uniform(1_f64, 1_f64, 1_f64);
+        let start: KinematicState = KinematicState::default();
+
+        // Two seconds of speeding up and down, and a second of cruising.
+        let end: KinematicState = KinematicState {
+            theta_0: 2_f64,
+            theta_1: -1_f64,
+            theta_3: 0.5_f64,
+            ..KinematicState::default()
+        };
+        let profile: JointProfile =
+            JointProfile::new(&start, &end, &limits, ProfileShape::Trapezoidal).unwrap();
+
+        assert!((profile.duration() - 3_f64).abs() < 10_f64.powf(-12_f64));
+        assert!((profile.state_at(1.5_f64).theta_0 - 1_f64).abs() < 10_f64.powf(-12_f64));
+        assert!((profile.state_at(1.5_f64).theta_1 + 0.5_f64).abs() < 10_f64.powf(-12_f64));
+        assert!((profile.velocity_at(1.5_f64).x - 1_f64).abs() < 10_f64.powf(-12_f64));
+        assert_within_limits(&profile, &limits);
+
+        // Too short to reach the velocity limit, the profile becomes a triangle.
+        let short: KinematicState = KinematicState {
+            theta_2: 0.5_f64,
+            ..KinematicState::default()
+        };
+        let profile: JointProfile =
+            JointProfile::new(&start, &short, &limits, ProfileShape::Trapezoidal).unwrap();
+
+        assert!((profile.duration() - 2_f64 * 0.5_f64.sqrt()).abs() < 10_f64.powf(-12_f64));
+
+        // Staying in place takes no time.
+        assert_eq!(
+            JointProfile::new(&start, &start, &limits, ProfileShape::Trapezoidal)
+                .unwrap()
+                .duration(),
+            0_f64
+        );
+        assert_eq!(
+            JointProfile::new(
+                &start,
+                &end,
+                &JointDynamicsLimits::uniform(1_f64, 0_f64, 1_f64),
+                ProfileShape::Trapezoidal
+            ),
+            Err(MotionError::InvalidDynamicsLimit(0_f64))
+        );
+    }
+
+    #[test]
+    pub fn s_curve_moves() {
+        let limits: JointDynamicsLimits = JointDynamicsLimits::new(
+            Vector5::<f64>::repeat(1_f64),
+            Vector5::<f64>::repeat(1_f64),
+            Vector5::<f64>::new(1_f64, 1_f64, 1_f64, 1_f64, 0.5_f64),
+        );
+        let start: KinematicState = KinematicState::default();
+
+        // Speeding up and down takes four seconds and two radians, so eight seconds are cruised.
+        let end: KinematicState = KinematicState {
+            theta_1: 10_f64,
+            theta_2: 5_f64,
+            ..KinematicState::default()
+        };
+        let profile: JointProfile =
+            JointProfile::new(&start, &end, &limits, ProfileShape::SCurve).unwrap();
+
+        assert!((profile.duration() - 12_f64).abs() < 10_f64.powf(-12_f64));
+        assert!((profile.state_at(6_f64).theta_1 - 5_f64).abs() < 10_f64.powf(-9_f64));
+        assert_within_limits(
+            &JointProfile::new(
+                &start,
+                &KinematicState {
+                    theta_0: 10_f64,
+                    ..end.clone()
+                },
+                &limits,
+                ProfileShape::SCurve,
+            )
+            .unwrap(),
+            &limits,
+        );
+
+        // The acceleration changes continuously, within the jerk limit of the slowest joint.
+        let short: KinematicState = KinematicState {
+            theta_0: 0.3_f64,
+            theta_4: -0.2_f64,
+            ..KinematicState::default()
+        };
+        let profile: JointProfile =
+            JointProfile::new(&start, &short, &limits, ProfileShape::SCurve).unwrap();
+        let samples: Vec<JointSample> = profile.sample(0.001_f64).unwrap();
+
+        assert_within_limits(&profile, &limits);
+        assert!(samples.windows(2_usize).all(|pair| {
+            ((pair[1].acceleration - pair[0].acceleration) / (pair[1].time - pair[0].time))
+                .iter()
+                .zip(limits.jerk.iter())
+                .all(|(jerk, limit)| jerk.abs() <= limit + 10_f64.powf(-6_f64))
+        }));
+        assert!(
+            JointProfile::new(&start, &short, &limits, ProfileShape::Trapezoidal)
+                .unwrap()
+                .duration()
+                < profile.duration()
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use nalgebra::{DMatrix, Vector5};
+use serde::{Deserialize, Serialize};
+
+use crate::model::KinematicState;
+use crate::motion::profile::JointSample;
+use crate::motion::MotionError;
+
+/// A joint state that a spline passes through at the given time (in seconds).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointWaypoint {
+    pub time: f64,
+    pub state: KinematicState,
+}
+
+impl JointWaypoint {
+    pub fn new(time: f64, state: KinematicState) -> Self {
+        Self { time, state }
+    }
+}
+
+/// The kind of polynomials a spline is built from.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SplineKind {
+    /// Cubic polynomials, of which the accelerations are continuous.
+    Cubic,
+    /// Quintic polynomials, of which the jerks and snaps are continuous as well, which makes the
+    ///  spline the one with the minimal integrated squared jerk.
+    Quintic,
+}
+
+impl SplineKind {
+    /// Get the number of derivatives (including the position) that are fixed at both ends of
+    ///  every segment.
+    fn node_derivatives(&self) -> usize {
+        match self {
+            SplineKind::Cubic => 2_usize,
+            SplineKind::Quintic => 3_usize,
+        }
+    }
+}
+
+/// The joint velocities and accelerations at the start and end of a spline, the accelerations
+///  are only used by quintic splines.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SplineBoundary {
+    pub start_velocity: Vector5<f64>,
+    pub end_velocity: Vector5<f64>,
+    pub start_acceleration: Vector5<f64>,
+    pub end_acceleration: Vector5<f64>,
+}
+
+impl Default for SplineBoundary {
+    /// Start and end at rest.
+    fn default() -> Self {
+        Self {
+            start_velocity: Vector5::<f64>::zeros(),
+            end_velocity: Vector5::<f64>::zeros(),
+            start_acceleration: Vector5::<f64>::zeros(),
+            end_acceleration: Vector5::<f64>::zeros(),
+        }
+    }
+}
+
+/// A joint-space spline through timed waypoints, with one polynomial per pair of consecutive
+///  waypoints.
+///
+/// Every polynomial is the Hermite interpolant of the positions (and the derivatives below the
+///  continuity order) at its ends, the derivatives in the inner waypoints are solved for such that
+///  the higher derivatives are continuous as well.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointSpline {
+    pub kind: SplineKind,
+    /// The times of the waypoints.
+    pub times: Vec<f64>,
+    /// The coefficients of the polynomial of every segment, in the time since its start and
+    ///  ordered by increasing power.
+    pub coefficients: Vec<Vec<Vector5<f64>>>,
+}
+
+impl JointSpline {
+    pub fn new(
+        waypoints: &[JointWaypoint],
+        kind: SplineKind,
+        boundary: &SplineBoundary,
+    ) -> Result<Self, MotionError> {
+        if waypoints.len() < 2_usize {
+            return Err(MotionError::TooFewWaypoints(waypoints.len()));
+        }
+
+        if let Some(i) = waypoints
+            .iter()
+            .position(|waypoint| !waypoint.time.is_finite())
+        {
+            return Err(MotionError::InvalidWaypointTime(i));
+        }
+
+        if let Some(i) =
+            (1_usize..waypoints.len()).find(|i| waypoints[*i].time <= waypoints[*i - 1_usize].time)
+        {
+            return Err(MotionError::NonIncreasingTimes(i));
+        }
+
+        let m: usize = kind.node_derivatives();
+        let nodes: usize = waypoints.len();
+        let hermite: Vec<DMatrix<f64>> = waypoints
+            .windows(2_usize)
+            .map(|pair| hermite_matrix(m, pair[1].time - pair[0].time))
+            .collect::<Option<Vec<DMatrix<f64>>>>()
+            .ok_or(MotionError::UnsolvableSpline)?;
+
+        // The derivative of the given order in the given node, when it is known up front.
+        let known = |node: usize, order: usize| -> Option<Vector5<f64>> {
+            match (node, order) {
+                (node, 0_usize) => Some(Vector5::<f64>::from(&waypoints[node].state)),
+                (0_usize, 1_usize) => Some(boundary.start_velocity),
+                (0_usize, 2_usize) => Some(boundary.start_acceleration),
+                (node, 1_usize) if node == nodes - 1_usize => Some(boundary.end_velocity),
+                (node, 2_usize) if node == nodes - 1_usize => Some(boundary.end_acceleration),
+                _ => None,
+            }
+        };
+        let unknown = |node: usize, order: usize| -> usize {
+            (node - 1_usize) * (m - 1_usize) + order - 1_usize
+        };
+
+        // Require the derivatives from the order m up to 2m - 2 to be continuous in every inner
+        //  node, which gives as many equations as there are unknown derivatives.
+        let size: usize = (nodes - 2_usize) * (m - 1_usize);
+        let mut matrix: DMatrix<f64> = DMatrix::<f64>::zeros(size, size);
+        let mut rhs: DMatrix<f64> = DMatrix::<f64>::zeros(size, 5_usize);
+
+        for node in 1_usize..nodes - 1_usize {
+            for order in m..=2_usize * m - 2_usize {
+                let row: usize = unknown(node, order - m + 1_usize);
+                let before: f64 = waypoints[node].time - waypoints[node - 1_usize].time;
+
+                // The derivative at the end of the segment before the node, minus the one at the
+                //  start of the segment after it.
+                let terms = derivative_weights(&hermite[node - 1_usize], order, before)
+                    .into_iter()
+                    .map(|(index, weight)| (node - 1_usize + index / m, index % m, weight))
+                    .chain(
+                        derivative_weights(&hermite[node], order, 0_f64)
+                            .into_iter()
+                            .map(|(index, weight)| (node + index / m, index % m, -weight)),
+                    );
+
+                for (other, derivative, weight) in terms {
+                    match known(other, derivative) {
+                        Some(value) => {
+                            for joint in 0..5_usize {
+                                rhs[(row, joint)] -= weight * value[joint];
+                            }
+                        }
+                        None => matrix[(row, unknown(other, derivative))] += weight,
+                    }
+                }
+            }
+        }
+
+        let solution: DMatrix<f64> = if size == 0_usize {
+            rhs
+        } else {
+            matrix
+                .lu()
+                .solve(&rhs)
+                .ok_or(MotionError::UnsolvableSpline)?
+        };
+
+        // Gather the derivatives of all the nodes, and build the polynomials of the segments.
+        let derivatives: Vec<Vec<Vector5<f64>>> = (0..nodes)
+            .map(|node| {
+                (0..m)
+                    .map(|order| {
+                        known(node, order).unwrap_or_else(|| {
+                            solution
+                                .row(unknown(node, order))
+                                .transpose()
+                                .fixed_rows::<5>(0)
+                                .into_owned()
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let coefficients: Vec<Vec<Vector5<f64>>> = hermite
+            .iter()
+            .enumerate()
+            .map(|(segment, hermite)| {
+                let values: Vec<&Vector5<f64>> = derivatives[segment]
+                    .iter()
+                    .chain(derivatives[segment + 1_usize].iter())
+                    .collect();
+
+                (0..2_usize * m)
+                    .map(|power| {
+                        values
+                            .iter()
+                            .enumerate()
+                            .map(|(i, value)| *value * hermite[(power, i)])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            kind,
+            times: waypoints.iter().map(|waypoint| waypoint.time).collect(),
+            coefficients,
+        })
+    }
+
+    /// Get the time of the first waypoint.
+    pub fn start_time(&self) -> f64 {
+        self.times[0]
+    }
+
+    /// Get the time of the last waypoint.
+    pub fn end_time(&self) -> f64 {
+        self.times[self.times.len() - 1_usize]
+    }
+
+    /// Get the duration of the spline, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.end_time() - self.start_time()
+    }
+
+    /// Get the state at the given time, which is clamped into the times of the waypoints.
+    pub fn state_at(&self, time: f64) -> KinematicState {
+        KinematicState::from(self.derivative_at(time, 0_usize))
+    }
+
+    /// Get the joint velocities at the given time.
+    pub fn velocity_at(&self, time: f64) -> Vector5<f64> {
+        self.derivative_at(time, 1_usize)
+    }
+
+    /// Get the joint accelerations at the given time.
+    pub fn acceleration_at(&self, time: f64) -> Vector5<f64> {
+        self.derivative_at(time, 2_usize)
+    }
+
+    /// Sample the spline at intervals of at most the given period, the first and last samples lie
+    ///  on the first and last waypoints.
+    pub fn sample(&self, period: f64) -> Result<Vec<JointSample>, MotionError> {
+        if period.is_nan() || period <= 0_f64 {
+            return Err(MotionError::InvalidPeriod(period));
+        }
+
+        let duration: f64 = self.duration();
+        let intervals: usize = (duration / period).ceil().max(1_f64) as usize;
+
+        Ok((0..=intervals)
+            .map(|i| {
+                let time: f64 = self.start_time() + duration * i as f64 / intervals as f64;
+
+                JointSample {
+                    time,
+                    state: self.state_at(time),
+                    velocity: self.velocity_at(time),
+                    acceleration: self.acceleration_at(time),
+                }
+            })
+            .collect())
+    }
+
+    /// Evaluate the derivative of the given order at the given time.
+    fn derivative_at(&self, time: f64, order: usize) -> Vector5<f64> {
+        let time: f64 = time.clamp(self.start_time(), self.end_time());
+        let segment: usize = self.times[1_usize..]
+            .iter()
+            .position(|end| time <= *end)
+            .unwrap_or(self.coefficients.len() - 1_usize);
+        let tau: f64 = time - self.times[segment];
+
+        self.coefficients[segment]
+            .iter()
+            .enumerate()
+            .skip(order)
+            .map(|(power, coefficient)| {
+                coefficient * falling_factorial(power, order) * tau.powi((power - order) as i32)
+            })
+            .sum()
+    }
+}
+
+/// Compute `power! / (power - order)!`, the factor by which differentiating a monomial `order`
+///  times scales it.
+fn falling_factorial(power: usize, order: usize) -> f64 {
+    ((power - order + 1_usize)..=power).product::<usize>() as f64
+}
+
+/// Compute the matrix that maps the derivatives (up to order m - 1) at the start and end of a
+///  segment of the given duration onto the coefficients of its Hermite polynomial.
+fn hermite_matrix(m: usize, duration: f64) -> Option<DMatrix<f64>> {
+    let size: usize = 2_usize * m;
+
+    DMatrix::<f64>::from_fn(size, size, |row, power| {
+        let (order, tau) = if row < m {
+            (row, 0_f64)
+        } else {
+            (row - m, duration)
+        };
+
+        if power < order {
+            0_f64
+        } else {
+            falling_factorial(power, order) * tau.powi((power - order) as i32)
+        }
+    })
+    .try_inverse()
+}
+
+/// Compute the weights with which the derivatives at the ends of a segment (indexed like the
+///  columns of its Hermite matrix) contribute to the derivative of the given order at the given
+///  time within it.
+fn derivative_weights(hermite: &DMatrix<f64>, order: usize, tau: f64) -> Vec<(usize, f64)> {
+    (0..hermite.ncols())
+        .map(|index| {
+            let weight: f64 = (order..hermite.nrows())
+                .map(|power| {
+                    falling_factorial(power, order)
+                        * tau.powi((power - order) as i32)
+                        * hermite[(power, index)]
+                })
+                .sum();
+
+            (index, weight)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector5;
+
+    use crate::model::KinematicState;
+    use crate::motion::spline::{
+        falling_factorial, JointSpline, JointWaypoint, SplineBoundary, SplineKind,
+    };
+    use crate::motion::MotionError;
+
+    fn waypoints() -> Vec<JointWaypoint> {
+        [
+            (0_f64, [0_f64, 0_f64, 0_f64, 0_f64, 0_f64]),
+            (1_f64, [1_f64, 0.5_f64, -0.2_f64, 0_f64, 0.3_f64]),
+            (2.5_f64, [0.5_f64, 1_f64, 0.4_f64, -0.5_f64, 0.3_f64]),
+            (3_f64, [0.2_f64, 1.2_f64, 0.1_f64, -0.2_f64, 0_f64]),
+            (4.5_f64, [0_f64, 0_f64, 0_f64, 0_f64, 0_f64]),
+        ]
+        .into_iter()
+        .map(|(time, state)| {
+            JointWaypoint::new(time, KinematicState::from(Vector5::<f64>::from(state)))
+        })
+        .collect()
+    }
+
+    /// Check that the spline passes through the waypoints, and that the derivative of the given
+    ///  order is continuous in them.
+    fn assert_smooth(spline: &JointSpline, waypoints: &[JointWaypoint], order: usize) {
+        for waypoint in waypoints {
+            assert!(
+                (Vector5::<f64>::from(&spline.state_at(waypoint.time))
+                    - Vector5::<f64>::from(&waypoint.state))
+                .amax()
+                    < 10_f64.powf(-9_f64)
+            );
+        }
+
+        for waypoint in &waypoints[1..waypoints.len() - 1] {
+            let segment: usize = spline
+                .times
+                .iter()
+                .position(|time| *time == waypoint.time)
+                .unwrap();
+            let derivative = |segment: usize, tau: f64| -> Vector5<f64> {
+                spline.coefficients[segment]
+                    .iter()
+                    .enumerate()
+                    .skip(order)
+                    .map(|(power, coefficient)| {
+                        coefficient
+                            * falling_factorial(power, order)
+                            * tau.powi((power - order) as i32)
+                    })
+                    .sum()
+            };
+            let before: f64 = waypoint.time - spline.times[segment - 1_usize];
+
+            assert!(
+                (derivative(segment - 1_usize, before) - derivative(segment, 0_f64)).amax()
+                    < 10_f64.powf(-6_f64)
+            );
+        }
+    }
+
+    #[test]
+    pub fn cubic_spline() {
+        let waypoints: Vec<JointWaypoint> = waypoints();
+        let boundary: SplineBoundary = SplineBoundary {
+            start_velocity: Vector5::<f64>::repeat(0.5_f64),
+            ..SplineBoundary::default()
+        };
+        let spline: JointSpline =
+            JointSpline::new(&waypoints, SplineKind::Cubic, &boundary).unwrap();
+
+        assert_smooth(&spline, &waypoints, 2_usize);
+        assert!((spline.duration() - 4.5_f64).abs() < 10_f64.powf(-12_f64));
+        assert!((spline.velocity_at(0_f64) - boundary.start_velocity).amax() < 10_f64.powf(-9_f64));
+        assert!(spline.velocity_at(4.5_f64).amax() < 10_f64.powf(-9_f64));
+
+        // Waypoints on a line, with matching boundary velocities, give the line itself.
+        let line: Vec<JointWaypoint> = [0_f64, 0.5_f64, 2_f64, 3_f64]
+            .into_iter()
+            .map(|time| {
+                JointWaypoint::new(
+                    time,
+                    KinematicState::from(Vector5::<f64>::repeat(2_f64 * time)),
+                )
+            })
+            .collect();
+        let spline: JointSpline = JointSpline::new(
+            &line,
+            SplineKind::Cubic,
+            &SplineBoundary {
+                start_velocity: Vector5::<f64>::repeat(2_f64),
+                end_velocity: Vector5::<f64>::repeat(2_f64),
+                ..SplineBoundary::default()
+            },
+        )
+        .unwrap();
+
+        assert!((spline.state_at(1.3_f64).theta_2 - 2.6_f64).abs() < 10_f64.powf(-9_f64));
+        assert!(
+            (spline.velocity_at(2.7_f64) - Vector5::<f64>::repeat(2_f64)).amax()
+                < 10_f64.powf(-9_f64)
+        );
+        assert!(spline.acceleration_at(1_f64).amax() < 10_f64.powf(-9_f64));
+    }
+
+    #[test]
+    pub fn quintic_spline() {
+        // A single segment from rest to rest is the minimum-jerk move.
+        let spline: JointSpline = JointSpline::new(
+            &[
+                JointWaypoint::new(0_f64, KinematicState::default()),
+                JointWaypoint::new(2_f64, KinematicState::from(Vector5::<f64>::repeat(1_f64))),
+            ],
+            SplineKind::Quintic,
+            &SplineBoundary::default(),
+        )
+        .unwrap();
+
+        for time in [0.3_f64, 1_f64, 1.7_f64] {
+            let s: f64 = time / 2_f64;
+            let expected: f64 = 10_f64 * s.powi(3) - 15_f64 * s.powi(4) + 6_f64 * s.powi(5);
+
+            assert!((spline.state_at(time).theta_0 - expected).abs() < 10_f64.powf(-9_f64));
+        }
+
+        // Through several waypoints the jerk and snap are continuous as well.
+        let waypoints: Vec<JointWaypoint> = waypoints();
+        let boundary: SplineBoundary = SplineBoundary {
+            end_acceleration: Vector5::<f64>::repeat(-1_f64),
+            ..SplineBoundary::default()
+        };
+        let spline: JointSpline =
+            JointSpline::new(&waypoints, SplineKind::Quintic, &boundary).unwrap();
+
+        for order in 2_usize..=4_usize {
+            assert_smooth(&spline, &waypoints, order);
+        }
+
+        assert!(
+            (spline.acceleration_at(4.5_f64) - boundary.end_acceleration).amax()
+                < 10_f64.powf(-9_f64)
+        );
+        assert_eq!(spline.sample(0.1_f64).unwrap().len(), 46_usize);
+    }
+
+    #[test]
+    pub fn rejects_invalid_waypoints() {
+        let mut waypoints: Vec<JointWaypoint> = waypoints();
+
+        assert_eq!(
+            JointSpline::new(
+                &waypoints[..1],
+                SplineKind::Cubic,
+                &SplineBoundary::default()
+            ),
+            Err(MotionError::TooFewWaypoints(1_usize))
+        );
+
+        waypoints[3].time = 2.5_f64;
+        assert_eq!(
+            JointSpline::new(&waypoints, SplineKind::Quintic, &SplineBoundary::default()),
+            Err(MotionError::NonIncreasingTimes(3_usize))
+        );
+
+        // Times that are not finite are refused, also for the first waypoint, which has no
+        //  earlier time to compare with.
+        waypoints[3].time = 3_f64;
+
+        for (i, time) in [
+            (0_usize, f64::NAN),
+            (0_usize, f64::NEG_INFINITY),
+            (4_usize, f64::INFINITY),
+        ] {
+            let mut invalid: Vec<JointWaypoint> = waypoints.clone();
+            invalid[i].time = time;
+
+            assert_eq!(
+                JointSpline::new(&invalid, SplineKind::Cubic, &SplineBoundary::default()),
+                Err(MotionError::InvalidWaypointTime(i))
+            );
+        }
+    }
+}
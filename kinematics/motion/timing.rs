@@ -0,0 +1,436 @@
+use nalgebra::Vector5;
+use serde::{Deserialize, Serialize};
+
+use crate::model::KinematicState;
+use crate::motion::profile::{JointDynamicsLimits, JointSample};
+use crate::motion::MotionError;
+
+/// The number of bisection steps used to find the largest feasible path velocity in a grid
+///  point.
+const BISECTION_ITERATIONS: usize = 60_usize;
+
+/// Computes the fastest timing along a geometric path of joint states that respects the joint
+///  velocity and acceleration limits, and starts and ends at rest.
+///
+/// The path is the polyline through the states, parameterized by its joint-space arc length `s`
+///  and discretized into a grid of at most the resolution. In every grid point the joint
+///  velocities `q' ṡ` and accelerations `q' s̈ + q'' ṡ²` are bounded, with the path derivatives
+///  taken by finite differences. Like TOPP-RA, a backward pass computes the largest `ṡ²` from
+///  which the end can still be reached at rest, and a forward pass then accelerates as hard as
+///  that allows.
+///
+/// Sharp corners of the polyline make the arm slow down in them, paths from a planner should be
+///  smoothed (for example by sampling a spline through them) to move through them quickly.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimeOptimalParameterization {
+    /// The joint limits, of which the jerk limits are ignored.
+    pub limits: JointDynamicsLimits,
+    /// The maximum joint-space distance between two grid points.
+    pub resolution: f64,
+}
+
+impl TimeOptimalParameterization {
+    pub fn new(limits: JointDynamicsLimits, resolution: f64) -> Self {
+        Self { limits, resolution }
+    }
+
+    /// Compute the timing of the given path, which must consist of at least two states.
+    ///
+    /// Fails when a velocity or acceleration limit is not positive and finite, since the largest
+    ///  feasible path velocity would then not be bounded.
+    pub fn parameterize(&self, path: &[KinematicState]) -> Result<TimedPath, MotionError> {
+        if self.resolution.is_nan() || self.resolution <= 0_f64 {
+            return Err(MotionError::InvalidResolution(self.resolution));
+        }
+
+        if let Some(limit) = self
+            .limits
+            .velocity
+            .iter()
+            .chain(self.limits.acceleration.iter())
+            .find(|limit| !limit.is_finite() || **limit <= 0_f64)
+        {
+            return Err(MotionError::InvalidDynamicsLimit(*limit));
+        }
+
+        if path.len() < 2_usize {
+            return Err(MotionError::TooFewWaypoints(path.len()));
+        }
+
+        let grid: Vec<Vector5<f64>> = self.grid(path);
+        let n: usize = grid.len();
+
+        // Staying in place takes no time.
+        if n < 2_usize {
+            return Ok(TimedPath {
+                samples: vec![JointSample {
+                    time: 0_f64,
+                    state: path[0].clone(),
+                    velocity: Vector5::<f64>::zeros(),
+                    acceleration: Vector5::<f64>::zeros(),
+                }],
+                speeds: vec![0_f64],
+            });
+        }
+
+        // The lengths and (unit) directions of the intervals, and the path derivatives in the
+        //  grid points. The last grid point has no interval leaving it, so it reuses the
+        //  direction of the one entering it.
+        let lengths: Vec<f64> = grid
+            .windows(2_usize)
+            .map(|pair| (pair[1] - pair[0]).magnitude())
+            .collect();
+        let directions: Vec<Vector5<f64>> = grid
+            .windows(2_usize)
+            .zip(lengths.iter())
+            .map(|(pair, length)| (pair[1] - pair[0]) / *length)
+            .collect();
+        let first: Vec<Vector5<f64>> = (0..n).map(|k| directions[k.min(n - 2_usize)]).collect();
+        let second: Vec<Vector5<f64>> = (0..n)
+            .map(|k| {
+                if k == 0_usize || k == n - 1_usize {
+                    Vector5::<f64>::zeros()
+                } else {
+                    (directions[k] - directions[k - 1_usize])
+                        / ((lengths[k - 1_usize] + lengths[k]) / 2_f64)
+                }
+            })
+            .collect();
+
+        // The largest squared path velocity in every grid point, limited by the joint velocities
+        //  on both sides and by the existence of a feasible path acceleration.
+        let maximum: Vec<f64> = (0..n)
+            .map(|k| {
+                let incoming: Vector5<f64> = directions[k.saturating_sub(1_usize)];
+                let velocity: f64 = [first[k], incoming]
+                    .iter()
+                    .flat_map(|direction| {
+                        direction
+                            .iter()
+                            .zip(self.limits.velocity.iter())
+                            .map(|(d, limit)| (limit / d.abs()).powi(2))
+                    })
+                    .fold(f64::INFINITY, f64::min);
+
+                self.largest(velocity, |x| {
+                    let (low, high) = self.acceleration_bounds(&first[k], &second[k], x);
+
+                    low <= high
+                })
+            })
+            .collect();
+
+        // The backward pass, in which the end is reached at rest.
+        let mut controllable: Vec<f64> = maximum.clone();
+        controllable[n - 1_usize] = 0_f64;
+
+        for k in (0..n - 1_usize).rev() {
+            let next: f64 = controllable[k + 1_usize];
+
+            controllable[k] = self.largest(maximum[k], |x| {
+                let (low, high) = self.acceleration_bounds(&first[k], &second[k], x);
+
+                low <= high && x + 2_f64 * low * lengths[k] <= next
+            });
+        }
+
+        // The forward pass, which starts at rest and accelerates as hard as possible.
+        let mut squared: Vec<f64> = vec![0_f64; n];
+
+        for k in 0..n - 1_usize {
+            let (_, high) = self.acceleration_bounds(&first[k], &second[k], squared[k]);
+
+            squared[k + 1_usize] = (squared[k] + 2_f64 * high * lengths[k])
+                .min(controllable[k + 1_usize])
+                .max(0_f64);
+        }
+
+        let speeds: Vec<f64> = squared.iter().map(|x| x.sqrt()).collect();
+        let mut time: f64 = 0_f64;
+        let samples: Vec<JointSample> = (0..n)
+            .map(|k| {
+                if k > 0_usize {
+                    time += 2_f64 * lengths[k - 1_usize] / (speeds[k - 1_usize] + speeds[k]);
+                }
+
+                // The path acceleration of the interval leaving the grid point.
+                let acceleration: f64 = if k < n - 1_usize {
+                    (squared[k + 1_usize] - squared[k]) / (2_f64 * lengths[k])
+                } else {
+                    0_f64
+                };
+
+                JointSample {
+                    time,
+                    state: KinematicState::from(grid[k]),
+                    velocity: first[k] * speeds[k],
+                    acceleration: first[k] * acceleration + second[k] * squared[k],
+                }
+            })
+            .collect();
+
+        Ok(TimedPath { samples, speeds })
+    }
+
+    /// Subdivide the intervals between the states of the path to the resolution, leaving out
+    ///  repeated states.
+    fn grid(&self, path: &[KinematicState]) -> Vec<Vector5<f64>> {
+        let mut grid: Vec<Vector5<f64>> = vec![Vector5::<f64>::from(&path[0])];
+
+        for state in path[1..].iter() {
+            let from: Vector5<f64> = grid[grid.len() - 1_usize];
+            let to: Vector5<f64> = Vector5::<f64>::from(state);
+            let length: f64 = (to - from).magnitude();
+
+            if length == 0_f64 {
+                continue;
+            }
+
+            let steps: usize = (length / self.resolution).ceil().max(1_f64) as usize;
+            grid.extend(
+                (1_usize..=steps).map(|step| from + (to - from) * (step as f64 / steps as f64)),
+            );
+        }
+
+        grid
+    }
+
+    /// Compute the range of path accelerations that keeps the joint accelerations within their
+    ///  limits at the given squared path velocity, which is empty when the low bound exceeds the
+    ///  high one.
+    fn acceleration_bounds(
+        &self,
+        first: &Vector5<f64>,
+        second: &Vector5<f64>,
+        squared: f64,
+    ) -> (f64, f64) {
+        let mut bounds: (f64, f64) = (f64::NEG_INFINITY, f64::INFINITY);
+
+        for i in 0..5_usize {
+            let limit: f64 = self.limits.acceleration[i];
+            let offset: f64 = second[i] * squared;
+
+            if first[i] == 0_f64 {
+                // The joint does not move along the path here, so only the curvature counts.
+                if offset.abs() > limit {
+                    return (f64::INFINITY, f64::NEG_INFINITY);
+                }
+            } else {
+                let a: f64 = (-limit - offset) / first[i];
+                let b: f64 = (limit - offset) / first[i];
+
+                bounds = (bounds.0.max(a.min(b)), bounds.1.min(a.max(b)));
+            }
+        }
+
+        bounds
+    }
+
+    /// Find the largest squared path velocity below the given maximum for which the condition
+    ///  holds, assuming that the condition holds at rest and on an interval starting there.
+    fn largest<F: Fn(f64) -> bool>(&self, maximum: f64, condition: F) -> f64 {
+        if condition(maximum) {
+            return maximum;
+        }
+
+        let (mut low, mut high) = (0_f64, maximum);
+
+        for _ in 0..BISECTION_ITERATIONS {
+            let middle: f64 = (low + high) / 2_f64;
+
+            if condition(middle) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        low
+    }
+}
+
+/// A path of joint states together with its timing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedPath {
+    /// The grid points of the path, with their times, velocities and accelerations.
+    pub samples: Vec<JointSample>,
+    /// The path velocities in the grid points.
+    speeds: Vec<f64>,
+}
+
+impl TimedPath {
+    /// Get the duration of the path, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.samples[self.samples.len() - 1_usize].time
+    }
+
+    /// Get the state at the given time, which is clamped into the duration of the path.
+    pub fn state_at(&self, time: f64) -> KinematicState {
+        let last: usize = self.samples.len() - 1_usize;
+        let k: usize = match self.samples[1_usize..]
+            .iter()
+            .position(|sample| time <= sample.time)
+        {
+            Some(k) => k,
+            None => return self.samples[last].state.clone(),
+        };
+
+        // The path acceleration is constant between two grid points.
+        let from: Vector5<f64> = Vector5::<f64>::from(&self.samples[k].state);
+        let to: Vector5<f64> = Vector5::<f64>::from(&self.samples[k + 1_usize].state);
+        let length: f64 = (to - from).magnitude();
+        let acceleration: f64 =
+            (self.speeds[k + 1_usize].powi(2) - self.speeds[k].powi(2)) / (2_f64 * length);
+        let tau: f64 = (time - self.samples[k].time).max(0_f64);
+        let travelled: f64 = self.speeds[k] * tau + acceleration * tau.powi(2) / 2_f64;
+
+        KinematicState::from(from + (to - from) * (travelled / length).clamp(0_f64, 1_f64))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector5;
+
+    use crate::model::KinematicState;
+    use crate::motion::profile::{JointDynamicsLimits, JointProfile, JointSample, ProfileShape};
+    use crate::motion::timing::{TimeOptimalParameterization, TimedPath};
+    use crate::motion::MotionError;
+
+    /// Check that the timed path starts and ends at rest, and that the joint velocities and
+    ///  accelerations in between stay within the limits.
+    fn assert_within_limits(path: &TimedPath, limits: &JointDynamicsLimits) {
+        let samples: &[JointSample] = &path.samples;
+
+        assert_eq!(samples[0].velocity, Vector5::<f64>::zeros());
+        assert_eq!(samples[samples.len() - 1].velocity, Vector5::<f64>::zeros());
+
+        for sample in samples {
+            for i in 0..5_usize {
+                assert!(sample.velocity[i].abs() <= limits.velocity[i] + 10_f64.powf(-9_f64));
+                assert!(
+                    sample.acceleration[i].abs() <= limits.acceleration[i] + 10_f64.powf(-6_f64)
+                );
+            }
+        }
+
+        assert!(samples
+            .windows(2_usize)
+            .all(|pair| pair[1].time > pair[0].time));
+    }
+
+    #[test]
+    pub fn matches_trapezoid_on_line() {
+        let limits: JointDynamicsLimits = JointDynamicsLimits::new(
+            Vector5::<f64>::new(1_f64, 2_f64, 1_f64, 1_f64, 1_f64),
+            Vector5::<f64>::repeat(1_f64),
+            Vector5::<f64>::repeat(1_f64),
+        );
+        let start: KinematicState = KinematicState::default();
+        let end: KinematicState = KinematicState {
+            theta_0: 2_f64,
+            theta_1: 1_f64,
+            theta_4: -0.5_f64,
+            ..KinematicState::default()
+        };
+
+        let timing: TimeOptimalParameterization =
+            TimeOptimalParameterization::new(limits.clone(), 0.01_f64);
+        let path: TimedPath = timing.parameterize(&[start.clone(), end.clone()]).unwrap();
+        let profile: JointProfile =
+            JointProfile::new(&start, &end, &limits, ProfileShape::Trapezoidal).unwrap();
+
+        // On a straight line the fastest timing is the synchronized trapezoid, up to the switch
+        //  from accelerating to braking falling between two grid points.
+        assert!((path.duration() - profile.duration()).abs() < 10_f64.powf(-4_f64));
+        assert_within_limits(&path, &limits);
+
+        for time in [0.5_f64, 1.5_f64, 2.7_f64] {
+            assert!(
+                (Vector5::<f64>::from(&path.state_at(time))
+                    - Vector5::<f64>::from(&profile.state_at(time)))
+                .amax()
+                    < 10_f64.powf(-4_f64)
+            );
+        }
+
+        assert_eq!(path.state_at(10_f64), end);
+    }
+
+    #[test]
+    pub fn slows_down_in_corners() {
+        let limits: JointDynamicsLimits = JointDynamicsLimits::uniform(1_f64, 2_f64, 1_f64);
+        let timing: TimeOptimalParameterization =
+            TimeOptimalParameterization::new(limits.clone(), 0.02_f64);
+
+        // A sampled quarter circle in the plane of the first two joints, and a sharp corner.
+        let arc: Vec<KinematicState> = (0..=50)
+            .map(|i| {
+                let angle: f64 = std::f64::consts::FRAC_PI_2 * i as f64 / 50_f64;
+
+                KinematicState {
+                    theta_0: 2_f64 * angle.cos(),
+                    theta_1: 2_f64 * angle.sin(),
+                    ..KinematicState::default()
+                }
+            })
+            .collect();
+        let corner: Vec<KinematicState> = vec![
+            arc[0].clone(),
+            KinematicState {
+                theta_0: 2_f64,
+                theta_1: 2_f64,
+                ..KinematicState::default()
+            },
+            arc[50].clone(),
+        ];
+
+        let smooth: TimedPath = timing.parameterize(&arc).unwrap();
+        let sharp: TimedPath = timing.parameterize(&corner).unwrap();
+
+        assert_within_limits(&smooth, &limits);
+        assert_within_limits(&sharp, &limits);
+
+        // The arc is curved everywhere, so the arm can not reach the velocity limits on it.
+        let middle: &JointSample = &smooth.samples[smooth.samples.len() / 2];
+        assert!(middle.velocity.amax() < 1_f64);
+        assert!(middle.velocity.amax() > 0.5_f64);
+
+        // In the corner the direction flips within a single grid interval, so the arm almost
+        //  stops there.
+        let apex: &JointSample = sharp
+            .samples
+            .iter()
+            .find(|sample| sample.state.theta_1 == 2_f64 && sample.state.theta_0 == 2_f64)
+            .unwrap();
+        assert!(apex.velocity.amax() < 0.25_f64);
+
+        assert_eq!(
+            timing.parameterize(&corner[..1]),
+            Err(MotionError::TooFewWaypoints(1_usize))
+        );
+    }
+
+    #[test]
+    pub fn refuses_invalid_limits() {
+        let path: [KinematicState; 2] = [
+            KinematicState::default(),
+            KinematicState {
+                theta_0: 1_f64,
+                ..KinematicState::default()
+            },
+        ];
+
+        for limit in [0_f64, -1_f64, f64::NAN, f64::INFINITY] {
+            for limits in [
+                JointDynamicsLimits::uniform(limit, 1_f64, 1_f64),
+                JointDynamicsLimits::uniform(1_f64, limit, 1_f64),
+            ] {
+                assert!(matches!(
+                    TimeOptimalParameterization::new(limits, 0.01_f64).parameterize(&path),
+                    Err(MotionError::InvalidDynamicsLimit(_))
+                ));
+            }
+        }
+    }
+}
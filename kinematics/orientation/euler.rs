@@ -0,0 +1,207 @@
+use nalgebra::{Matrix3, Rotation3, Unit, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// The tolerance on the cosine (Tait-Bryan) or sine (proper Euler) of the second angle below
+///  which an orientation is considered to be in gimbal lock.
+const GIMBAL_LOCK_EPS: f64 = 1e-9_f64;
+
+/// The convention used to decompose an orientation matrix into three angles, the convention is
+///  always intrinsic, so `Zyx` means `R = Rz(a) * Ry(b) * Rx(c)`.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EulerConvention {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+    Xyx,
+    Xzx,
+    Yxy,
+    Yzy,
+    Zxz,
+    Zyz,
+}
+
+impl EulerConvention {
+    /// Get the indices of the three rotation axes of the convention.
+    pub fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            EulerConvention::Xyz => (0, 1, 2),
+            EulerConvention::Xzy => (0, 2, 1),
+            EulerConvention::Yxz => (1, 0, 2),
+            EulerConvention::Yzx => (1, 2, 0),
+            EulerConvention::Zxy => (2, 0, 1),
+            EulerConvention::Zyx => (2, 1, 0),
+            EulerConvention::Xyx => (0, 1, 0),
+            EulerConvention::Xzx => (0, 2, 0),
+            EulerConvention::Yxy => (1, 0, 1),
+            EulerConvention::Yzy => (1, 2, 1),
+            EulerConvention::Zxz => (2, 0, 2),
+            EulerConvention::Zyz => (2, 1, 2),
+        }
+    }
+
+    /// Check if the convention is a proper euler convention (first and last axis are equal).
+    pub fn is_proper_euler(&self) -> bool {
+        let (i, _, k) = self.axes();
+
+        i == k
+    }
+
+    /// Check if the convention is a Tait-Bryan convention (all axes are different).
+    pub fn is_tait_bryan(&self) -> bool {
+        !self.is_proper_euler()
+    }
+
+    /// Decompose the given orientation matrix into the angles of this convention.
+    ///
+    /// The second angle lies in `[-pi/2, pi/2]` for Tait-Bryan conventions and in `[0, pi]` for
+    ///  proper euler conventions. When the orientation is in gimbal lock the third angle is set to
+    ///  zero, and the first angle absorbs the full rotation around the locked axis.
+    pub fn euler_angles(&self, matrix: &Matrix3<f64>) -> Vector3<f64> {
+        let (i, j, k) = self.axes();
+
+        // Get the axis that does not occur in the convention (for Tait-Bryan this is the third
+        //  axis itself), and the sign of the permutation (i, j, k).
+        let k: usize = if i == k { 3 - i - j } else { k };
+        let e: f64 = if (j + 3 - i) % 3 == 1 { 1_f64 } else { -1_f64 };
+
+        if self.is_tait_bryan() {
+            let b: f64 = (e * matrix[(i, k)])
+                .atan2((matrix[(i, i)].powi(2) + matrix[(i, j)].powi(2)).sqrt());
+
+            if b.cos() < GIMBAL_LOCK_EPS {
+                return Vector3::<f64>::new((e * matrix[(k, j)]).atan2(matrix[(j, j)]), b, 0_f64);
+            }
+
+            Vector3::<f64>::new(
+                (-e * matrix[(j, k)]).atan2(matrix[(k, k)]),
+                b,
+                (-e * matrix[(i, j)]).atan2(matrix[(i, i)]),
+            )
+        } else {
+            let b: f64 = (matrix[(i, j)].powi(2) + matrix[(i, k)].powi(2))
+                .sqrt()
+                .atan2(matrix[(i, i)]);
+
+            if b.sin() < GIMBAL_LOCK_EPS {
+                return Vector3::<f64>::new((e * matrix[(k, j)]).atan2(matrix[(j, j)]), b, 0_f64);
+            }
+
+            Vector3::<f64>::new(
+                matrix[(j, i)].atan2(-e * matrix[(k, i)]),
+                b,
+                matrix[(i, j)].atan2(e * matrix[(i, k)]),
+            )
+        }
+    }
+
+    /// Compose the orientation matrix from the given angles of this convention.
+    pub fn rotation_matrix(&self, angles: &Vector3<f64>) -> Matrix3<f64> {
+        let (i, j, k) = self.axes();
+
+        (Self::axis_rotation(i, angles.x)
+            * Self::axis_rotation(j, angles.y)
+            * Self::axis_rotation(k, angles.z))
+        .into_inner()
+    }
+
+    /// Check if the given orientation matrix is in gimbal lock for this convention.
+    pub fn is_gimbal_locked(&self, matrix: &Matrix3<f64>) -> bool {
+        let b: f64 = self.euler_angles(matrix).y;
+
+        if self.is_tait_bryan() {
+            b.cos() < GIMBAL_LOCK_EPS
+        } else {
+            b.sin() < GIMBAL_LOCK_EPS
+        }
+    }
+
+    /// Create the rotation around the principal axis with the given index.
+    fn axis_rotation(axis: usize, angle: f64) -> Rotation3<f64> {
+        let mut vector: Vector3<f64> = Vector3::<f64>::zeros();
+        vector[axis] = 1_f64;
+
+        Rotation3::<f64>::from_axis_angle(&Unit::new_unchecked(vector), angle)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use nalgebra::{Matrix3, Vector3};
+
+    use crate::orientation::euler::EulerConvention;
+
+    const CONVENTIONS: [EulerConvention; 12] = [
+        EulerConvention::Xyz,
+        EulerConvention::Xzy,
+        EulerConvention::Yxz,
+        EulerConvention::Yzx,
+        EulerConvention::Zxy,
+        EulerConvention::Zyx,
+        EulerConvention::Xyx,
+        EulerConvention::Xzx,
+        EulerConvention::Yxy,
+        EulerConvention::Yzy,
+        EulerConvention::Zxz,
+        EulerConvention::Zyz,
+    ];
+
+    #[test]
+    pub fn round_trip() {
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        for convention in CONVENTIONS {
+            for angles in [
+                Vector3::<f64>::new(0.3_f64, 0.2_f64, -0.7_f64),
+                Vector3::<f64>::new(-2.5_f64, 1.1_f64, 3_f64),
+                Vector3::<f64>::new(1.4_f64, -0.4_f64, 0.1_f64),
+            ] {
+                // Compose the matrix, decompose it and compose it again.
+                let matrix: Matrix3<f64> = convention.rotation_matrix(&angles);
+                let decomposed: Vector3<f64> = convention.euler_angles(&matrix);
+
+                assert!(
+                    (convention.rotation_matrix(&decomposed) - matrix).norm() < thresh,
+                    "{:?} failed for {}",
+                    convention,
+                    angles
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn gimbal_lock() {
+        let thresh: f64 = 10_f64.powf(-9_f64);
+
+        for convention in CONVENTIONS {
+            // Put the second angle on both singularities of the convention, in which the first
+            //  and third axes align in the same or in the opposite direction.
+            let singularities: [f64; 2] = if convention.is_tait_bryan() {
+                [FRAC_PI_2, -FRAC_PI_2]
+            } else {
+                [0_f64, PI]
+            };
+
+            for b in singularities {
+                let matrix: Matrix3<f64> =
+                    convention.rotation_matrix(&Vector3::<f64>::new(0.4_f64, b, 0.3_f64));
+                let decomposed: Vector3<f64> = convention.euler_angles(&matrix);
+
+                // The third angle must be zero, and the matrix must still be reproduced.
+                assert!(convention.is_gimbal_locked(&matrix));
+                assert_eq!(decomposed.z, 0_f64);
+                assert!(
+                    (convention.rotation_matrix(&decomposed) - matrix).norm() < thresh,
+                    "{:?} failed for {}",
+                    convention,
+                    b
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use nalgebra::Vector5;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::collision::scene::Scene;
+use crate::collision::SelfCollisionChecker;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::model::{KinematicParameters, KinematicState};
+
+/// The maximum number of states that are checked along a single segment, which bounds how fine
+///  the collision resolution can be compared to the length of the segment.
+const MAX_SEGMENT_STEPS: f64 = 1e6_f64;
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum PlanningError {
+    #[error("The start state lies outside of the joint limits or is in collision")]
+    InvalidStart,
+    #[error("The goal state lies outside of the joint limits or is in collision")]
+    InvalidGoal,
+    #[error("No collision-free path was found within {0} iterations")]
+    NoPathFound(usize),
+    #[error("Invalid step size {0}, it must be positive and finite")]
+    InvalidStepSize(f64),
+    #[error(
+        "Invalid collision resolution {0}, it must be positive and finite, and split a segment \
+         into at most a million checks"
+    )]
+    InvalidCollisionResolution(f64),
+}
+
+/// The result of growing a tree towards a state.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Extension {
+    /// The tree grew up to the state, into the given node.
+    Reached(usize),
+    /// The tree grew a single step towards the state, into the given node.
+    Advanced(usize),
+    /// The tree could not grow towards the state without colliding.
+    Trapped,
+}
+
+/// A tree of collision-free states, rooted in either the start or the goal.
+struct Tree {
+    nodes: Vec<Vector5<f64>>,
+    parents: Vec<Option<usize>>,
+}
+
+impl Tree {
+    fn new(root: Vector5<f64>) -> Self {
+        Self {
+            nodes: vec![root],
+            parents: vec![None],
+        }
+    }
+
+    fn push(&mut self, node: Vector5<f64>, parent: usize) -> usize {
+        self.nodes.push(node);
+        self.parents.push(Some(parent));
+
+        self.nodes.len() - 1_usize
+    }
+
+    /// Find the node that lies closest to the given state.
+    fn nearest(&self, state: &Vector5<f64>) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| (node - state).magnitude_squared())
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+            .unwrap_or(0_usize)
+    }
+
+    /// Get the states from the root of the tree up to the given node.
+    fn path(&self, mut node: usize) -> Vec<Vector5<f64>> {
+        let mut path: Vec<Vector5<f64>> = vec![self.nodes[node]];
+
+        while let Some(parent) = self.parents[node] {
+            path.push(self.nodes[parent]);
+            node = parent;
+        }
+
+        path.reverse();
+
+        path
+    }
+}
+
+/// Plans collision-free paths through joint space with RRT-Connect, which grows a random tree
+///  from both the start and the goal and tries to connect them after every step, and shortens the
+///  found path by replacing random parts of it with straight segments.
+///
+/// A state is valid when it lies within the joint limits and neither collides with itself nor with
+///  the scene. The returned path is piecewise linear in joint space, and every segment of it has
+///  been checked at the collision resolution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PathPlanner {
+    /// The maximum number of random states the trees are grown towards.
+    pub max_iterations: usize,
+    /// The maximum distance (in joint space) a tree grows in a single step.
+    pub step_size: f64,
+    /// The maximum change of a single joint between two states that are checked along a segment.
+    pub collision_resolution: f64,
+    /// The number of attempts to shortcut the found path.
+    pub shortcut_iterations: usize,
+    /// The seed of the random states.
+    pub seed: u64,
+    pub self_collision: SelfCollisionChecker,
+}
+
+impl Default for PathPlanner {
+    fn default() -> Self {
+        Self {
+            max_iterations: 5_000_usize,
+            step_size: 0.2_f64,
+            collision_resolution: 0.02_f64,
+            shortcut_iterations: 100_usize,
+            seed: 0_u64,
+            self_collision: SelfCollisionChecker::default(),
+        }
+    }
+}
+
+impl PathPlanner {
+    pub fn new(max_iterations: usize, step_size: f64, seed: u64) -> Self {
+        Self {
+            max_iterations,
+            step_size,
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Plan a collision-free path from the start to the goal state, both included.
+    ///
+    /// Fails when the step size or collision resolution is not positive and finite, since the
+    ///  trees would never grow or the segments would never finish being checked.
+    pub fn plan(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        start: &KinematicState,
+        goal: &KinematicState,
+    ) -> Result<Vec<KinematicState>, PlanningError> {
+        if !self.step_size.is_finite() || self.step_size <= 0_f64 {
+            return Err(PlanningError::InvalidStepSize(self.step_size));
+        }
+
+        if !self.collision_resolution.is_finite() || self.collision_resolution <= 0_f64 {
+            return Err(PlanningError::InvalidCollisionResolution(
+                self.collision_resolution,
+            ));
+        }
+
+        let start: Vector5<f64> = Vector5::<f64>::from(start);
+        let goal: Vector5<f64> = Vector5::<f64>::from(goal);
+
+        if !self.is_state_valid(fk, params, scene, &start) {
+            return Err(PlanningError::InvalidStart);
+        }
+
+        if !self.is_state_valid(fk, params, scene, &goal) {
+            return Err(PlanningError::InvalidGoal);
+        }
+
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed);
+        let path: Vec<Vector5<f64>> = if self.is_segment_valid(fk, params, scene, &start, &goal)? {
+            vec![start, goal]
+        } else {
+            self.connect_trees(fk, params, scene, start, goal, &mut rng)?
+        };
+
+        Ok(self
+            .shortcut(fk, params, scene, path, &mut rng)?
+            .into_iter()
+            .map(KinematicState::from)
+            .collect())
+    }
+
+    /// Check if the given state lies within the limits and is free of collisions.
+    pub fn is_state_valid(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        state: &Vector5<f64>,
+    ) -> bool {
+        let state: KinematicState = KinematicState::from(*state);
+
+        params.limits.contains(&state)
+            && !self.self_collision.is_colliding(fk, params, &state)
+            && !scene.is_colliding(fk, params, &state)
+    }
+
+    /// Check if the straight segment between the given states is free of collisions, the start of
+    ///  the segment is assumed to be valid already.
+    ///
+    /// Fails when the collision resolution is not positive and finite, or so fine compared to the
+    ///  segment that checking it would never finish.
+    pub fn is_segment_valid(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        from: &Vector5<f64>,
+        to: &Vector5<f64>,
+    ) -> Result<bool, PlanningError> {
+        if !self.collision_resolution.is_finite() || self.collision_resolution <= 0_f64 {
+            return Err(PlanningError::InvalidCollisionResolution(
+                self.collision_resolution,
+            ));
+        }
+
+        let delta: Vector5<f64> = to - from;
+        let steps: f64 = (delta.amax() / self.collision_resolution).ceil().max(1_f64);
+
+        if !steps.is_finite() || steps > MAX_SEGMENT_STEPS {
+            return Err(PlanningError::InvalidCollisionResolution(
+                self.collision_resolution,
+            ));
+        }
+
+        let steps: usize = steps as usize;
+
+        Ok((1_usize..=steps).all(|step| {
+            let state: Vector5<f64> = from + delta * (step as f64 / steps as f64);
+
+            self.is_state_valid(fk, params, scene, &state)
+        }))
+    }
+
+    /// Grow the trees of the start and the goal towards random states, and towards each other,
+    ///  until they connect.
+    fn connect_trees(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        start: Vector5<f64>,
+        goal: Vector5<f64>,
+        rng: &mut StdRng,
+    ) -> Result<Vec<Vector5<f64>>, PlanningError> {
+        let mut trees: [Tree; 2] = [Tree::new(start), Tree::new(goal)];
+
+        for iteration in 0..self.max_iterations {
+            // Alternate the tree that grows towards the random state, the other one follows it.
+            let (a, b) = (iteration % 2_usize, 1_usize - iteration % 2_usize);
+            let sample: Vector5<f64> = Vector5::<f64>::from(&params.limits.sample(rng));
+
+            let grown: usize = match self.extend(fk, params, scene, &mut trees[a], &sample)? {
+                Extension::Reached(node) | Extension::Advanced(node) => node,
+                Extension::Trapped => continue,
+            };
+            let target: Vector5<f64> = trees[a].nodes[grown];
+
+            let connected: Option<usize> = loop {
+                match self.extend(fk, params, scene, &mut trees[b], &target)? {
+                    Extension::Advanced(_) => continue,
+                    Extension::Reached(node) => break Some(node),
+                    Extension::Trapped => break None,
+                }
+            };
+            let Some(connected) = connected else {
+                continue;
+            };
+
+            // Join the paths of both trees in the node they share.
+            let mut first: Vec<Vector5<f64>> = trees[a].path(grown);
+            let mut second: Vec<Vector5<f64>> = trees[b].path(connected);
+
+            if a == 1_usize {
+                std::mem::swap(&mut first, &mut second);
+            }
+
+            second.pop();
+            first.extend(second.into_iter().rev());
+
+            return Ok(first);
+        }
+
+        Err(PlanningError::NoPathFound(self.max_iterations))
+    }
+
+    /// Grow the tree a single step from its nearest node towards the given state.
+    fn extend(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        tree: &mut Tree,
+        target: &Vector5<f64>,
+    ) -> Result<Extension, PlanningError> {
+        let nearest: usize = tree.nearest(target);
+        let from: Vector5<f64> = tree.nodes[nearest];
+        let delta: Vector5<f64> = target - from;
+        let distance: f64 = delta.magnitude();
+
+        let (to, reached) = if distance <= self.step_size {
+            (*target, true)
+        } else {
+            (from + delta * (self.step_size / distance), false)
+        };
+
+        if !self.is_segment_valid(fk, params, scene, &from, &to)? {
+            return Ok(Extension::Trapped);
+        }
+
+        let node: usize = tree.push(to, nearest);
+
+        if reached {
+            Ok(Extension::Reached(node))
+        } else {
+            Ok(Extension::Advanced(node))
+        }
+    }
+
+    /// Shorten the path by replacing the part between two random states with a straight segment,
+    ///  whenever that segment is free of collisions.
+    fn shortcut(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        scene: &Scene,
+        mut path: Vec<Vector5<f64>>,
+        rng: &mut StdRng,
+    ) -> Result<Vec<Vector5<f64>>, PlanningError> {
+        for _ in 0..self.shortcut_iterations {
+            if path.len() < 3_usize {
+                break;
+            }
+
+            let i: usize = rng.gen_range(0_usize..path.len() - 2_usize);
+            let j: usize = rng.gen_range(i + 2_usize..path.len());
+
+            if self.is_segment_valid(fk, params, scene, &path[i], &path[j])? {
+                path.drain(i + 1_usize..j);
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::{Vector3, Vector5};
+
+    use crate::collision::scene::{Obstacle, Scene};
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::model::{KinematicLimits, KinematicParameters, KinematicState};
+    use crate::planning::{PathPlanner, PlanningError};
+
+    #[test]
+    pub fn plans_around_obstacle() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let planner: PathPlanner = PathPlanner {
+            collision_resolution: 0.05_f64,
+            ..PathPlanner::default()
+        };
+
+        // Swing the leaning arm around the base, through a sphere placed in the middle of the
+        //  swing, above a table.
+        let leaning = |theta_0: f64| -> KinematicState {
+            KinematicState {
+                theta_0,
+                theta_1: 0.9_f64,
+                theta_2: 0.3_f64,
+                ..KinematicState::default()
+            }
+        };
+        let scene: Scene = Scene::new(
+            vec![
+                Obstacle::Sphere {
+                    center: fk_solver.limb4_position_vector(&params, &leaning(0_f64)),
+                    radius: 3_f64,
+                },
+                Obstacle::floor(0_f64),
+            ],
+            0_f64,
+        )
+        .unwrap();
+        let start: KinematicState = leaning(-1.2_f64);
+        let goal: KinematicState = leaning(1.2_f64);
+
+        assert_eq!(
+            planner.is_segment_valid(
+                &fk_solver,
+                &params,
+                &scene,
+                &Vector5::<f64>::from(&start),
+                &Vector5::<f64>::from(&goal)
+            ),
+            Ok(false)
+        );
+
+        let path: Vec<KinematicState> = planner
+            .plan(&fk_solver, &params, &scene, &start, &goal)
+            .unwrap();
+
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path
+            .windows(2_usize)
+            .all(|segment| planner.is_segment_valid(
+                &fk_solver,
+                &params,
+                &scene,
+                &Vector5::<f64>::from(&segment[0]),
+                &Vector5::<f64>::from(&segment[1])
+            ) == Ok(true)));
+
+        // Planning is deterministic for a given seed.
+        assert_eq!(
+            planner.plan(&fk_solver, &params, &scene, &start, &goal),
+            Ok(path)
+        );
+    }
+
+    #[test]
+    pub fn refuses_invalid_states() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let planner: PathPlanner = PathPlanner::default();
+
+        let start: KinematicState = KinematicState::default();
+        let goal: KinematicState = KinematicState {
+            theta_1: 1_f64,
+            ..KinematicState::default()
+        };
+
+        // A box around the goal position of the end-effector.
+        let scene: Scene = Scene::new(
+            vec![Obstacle::aligned_box(
+                fk_solver.limb4_position_vector(&params, &goal),
+                Vector3::<f64>::repeat(2_f64),
+            )],
+            0_f64,
+        )
+        .unwrap();
+
+        assert_eq!(
+            planner.plan(&fk_solver, &params, &scene, &start, &goal),
+            Err(PlanningError::InvalidGoal)
+        );
+        assert_eq!(
+            planner.plan(&fk_solver, &params, &scene, &goal, &start),
+            Err(PlanningError::InvalidStart)
+        );
+
+        // Without obstacles, the straight segment is the path.
+        assert_eq!(
+            planner.plan(&fk_solver, &params, &Scene::default(), &start, &goal),
+            Ok(vec![start.clone(), goal.clone()])
+        );
+
+        // Planners that would never grow or never finish checking a segment are refused.
+        for step_size in [0_f64, -0.2_f64, f64::NAN] {
+            assert!(matches!(
+                PathPlanner::new(100_usize, step_size, 0_u64)
+                    .plan(&fk_solver, &params, &scene, &start, &goal),
+                Err(PlanningError::InvalidStepSize(_))
+            ));
+        }
+        assert_eq!(
+            PathPlanner {
+                collision_resolution: 0_f64,
+                ..PathPlanner::default()
+            }
+            .plan(&fk_solver, &params, &scene, &start, &goal),
+            Err(PlanningError::InvalidCollisionResolution(0_f64))
+        );
+
+        // A resolution that would split a segment into too many checks is refused as well.
+        for collision_resolution in [0_f64, 10_f64.powf(-310_f64), 10_f64.powf(-12_f64)] {
+            assert_eq!(
+                PathPlanner {
+                    collision_resolution,
+                    ..PathPlanner::default()
+                }
+                .is_segment_valid(
+                    &fk_solver,
+                    &params,
+                    &Scene::default(),
+                    &Vector5::<f64>::from(&start),
+                    &Vector5::<f64>::from(&goal)
+                ),
+                Err(PlanningError::InvalidCollisionResolution(
+                    collision_resolution
+                ))
+            );
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use nalgebra::{Vector3, Vector5};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+use crate::inverse::algorithms::{InverseKinematicAlgorithm, InverseKinematicError};
+use crate::inverse::solver::IterativeSolver;
+use crate::model::{KinematicParameters, KinematicState};
+use crate::verification::numerical::NumericalJacobian;
+
+pub mod numerical;
+
+/// The quantity in which a discrepancy was found.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerifiedQuantity {
+    /// The end position of a limb.
+    Position(Limb),
+    /// The orientation of the end-effector of the fourth limb.
+    Orientation,
+    /// The linear part of the jacobian of a limb.
+    LinearJacobian(Limb),
+    /// The angular part of the jacobian of the end-effector of the fourth limb.
+    AngularJacobian,
+    /// The distance between the target and the end-effector after solving for the target.
+    RoundTrip,
+    /// The solve for the target failed, the error is the distance between the target and the
+    ///  end-effector at the start of the solve.
+    FailedSolve,
+}
+
+/// A difference between two results that should have been equal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub quantity: VerifiedQuantity,
+    /// The state in which the difference was found.
+    pub state: KinematicState,
+    /// The norm of the difference.
+    pub error: f64,
+}
+
+/// The result of a verification over random states.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VerificationReport {
+    /// The number of states that have been verified.
+    pub samples: usize,
+    /// The number of states for which a solve failed.
+    pub failures: usize,
+    /// The largest discrepancies, ordered from the largest to the smallest.
+    pub worst: Vec<Discrepancy>,
+}
+
+impl VerificationReport {
+    fn new(samples: usize) -> Self {
+        Self {
+            samples,
+            failures: 0_usize,
+            worst: Vec::new(),
+        }
+    }
+
+    /// Get the largest error found during the verification.
+    pub fn max_error(&self) -> f64 {
+        self.worst
+            .first()
+            .map(|discrepancy| discrepancy.error)
+            .unwrap_or(0_f64)
+    }
+
+    /// Record a discrepancy, keeping only the given number of largest ones.
+    fn record(&mut self, discrepancy: Discrepancy, keep: usize) {
+        let index: usize = self
+            .worst
+            .partition_point(|other| other.error >= discrepancy.error);
+
+        if index < keep {
+            self.worst.insert(index, discrepancy);
+            self.worst.truncate(keep);
+        }
+    }
+}
+
+/// Cross-verifies kinematic algorithms against each other, and against numerical derivatives,
+///  over reproducible random states within the joint limits.
+pub struct CrossVerifier {
+    /// The number of random states to verify.
+    pub samples: usize,
+    /// The seed of the random states.
+    pub seed: u64,
+    /// The number of largest discrepancies that are reported.
+    pub worst_count: usize,
+    /// The largest distance (per joint) between a random state and the start of its inverse
+    ///  kinematic solve.
+    pub start_distance: f64,
+    pub numerical: NumericalJacobian,
+}
+
+impl Default for CrossVerifier {
+    fn default() -> Self {
+        Self {
+            samples: 100_usize,
+            seed: 0_u64,
+            worst_count: 5_usize,
+            start_distance: 0.2_f64,
+            numerical: NumericalJacobian::default(),
+        }
+    }
+}
+
+impl CrossVerifier {
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self {
+            samples,
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Compare the jacobians of the given algorithm with numerical ones, the linear part is
+    ///  compared for every limb and the angular part for the end-effector.
+    pub fn verify_jacobian(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+    ) -> VerificationReport {
+        let mut report: VerificationReport = VerificationReport::new(self.samples);
+
+        for state in self.random_states(params) {
+            for limb in Limb::ALL {
+                let error: f64 = (fk.limb_jacobian(params, &state, limb).fixed_rows::<3>(0)
+                    - self
+                        .numerical
+                        .limb_position_jacobian(fk, params, &state, limb))
+                .norm();
+
+                self.record(
+                    &mut report,
+                    VerifiedQuantity::LinearJacobian(limb),
+                    &state,
+                    error,
+                );
+            }
+
+            let error: f64 = (fk.limb4_jacobian(params, &state).fixed_rows::<3>(3)
+                - self
+                    .numerical
+                    .limb4_orientation_jacobian(fk, params, &state))
+            .norm();
+
+            self.record(
+                &mut report,
+                VerifiedQuantity::AngularJacobian,
+                &state,
+                error,
+            );
+        }
+
+        report
+    }
+
+    /// Compare the limb positions and end-effector orientation of two forward kinematic
+    ///  algorithms.
+    pub fn verify_forward(
+        &self,
+        reference: &dyn ForwardKinematicAlgorithm,
+        candidate: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+    ) -> VerificationReport {
+        let mut report: VerificationReport = VerificationReport::new(self.samples);
+
+        for state in self.random_states(params) {
+            for limb in Limb::ALL {
+                let error: f64 = (reference.limb_position_vector(params, &state, limb)
+                    - candidate.limb_position_vector(params, &state, limb))
+                .magnitude();
+
+                self.record(&mut report, VerifiedQuantity::Position(limb), &state, error);
+            }
+
+            let error: f64 = (reference.limb4_orientation_matrix(params, &state)
+                - candidate.limb4_orientation_matrix(params, &state))
+            .norm();
+
+            self.record(&mut report, VerifiedQuantity::Orientation, &state, error);
+        }
+
+        report
+    }
+
+    /// Solve for the end-effector position of random states, starting from a nearby state, and
+    ///  compare the position that is reached with the target.
+    ///
+    /// A failed solve is recorded as a discrepancy, only errors that would fail every solve (the
+    ///  algorithms not fitting each other) end the verification.
+    pub fn verify_inverse(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        ik: &dyn InverseKinematicAlgorithm,
+        solver: &IterativeSolver,
+        params: &KinematicParameters,
+    ) -> Result<VerificationReport, InverseKinematicError> {
+        let mut report: VerificationReport = VerificationReport::new(self.samples);
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed.wrapping_add(1_u64));
+
+        for state in self.random_states(params) {
+            let target: Vector3<f64> = fk.limb4_position_vector(params, &state);
+
+            // Start a small random distance away from the state, within the limits.
+            let start: KinematicState = params.limits.clamp(&KinematicState::from(
+                Vector5::<f64>::from(&state)
+                    + Vector5::<f64>::from_fn(|_, _| {
+                        rng.gen_range(-self.start_distance..=self.start_distance)
+                    }),
+            ));
+
+            match solver.solve_limb4_position(fk, ik, params, &start, &target) {
+                Ok((solved, _)) => {
+                    let error: f64 =
+                        (fk.limb4_position_vector(params, &solved) - target).magnitude();
+
+                    self.record(&mut report, VerifiedQuantity::RoundTrip, &state, error);
+                }
+                Err(
+                    error
+                    @ (InverseKinematicError::ModelMismatch | InverseKinematicError::Chain(_)),
+                ) => return Err(error),
+                Err(_) => {
+                    let error: f64 =
+                        (fk.limb4_position_vector(params, &start) - target).magnitude();
+
+                    report.failures += 1_usize;
+                    self.record(&mut report, VerifiedQuantity::FailedSolve, &state, error);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Generate the random states, uniformly within the joint limits (or within a full turn for
+    ///  unlimited joints).
+    pub fn random_states(&self, params: &KinematicParameters) -> Vec<KinematicState> {
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed);
+
+        (0..self.samples)
+            .map(|_| params.limits.sample(&mut rng))
+            .collect()
+    }
+
+    /// Record a discrepancy in the report.
+    fn record(
+        &self,
+        report: &mut VerificationReport,
+        quantity: VerifiedQuantity,
+        state: &KinematicState,
+        error: f64,
+    ) {
+        report.record(
+            Discrepancy {
+                quantity,
+                state: state.clone(),
+                error,
+            },
+            self.worst_count,
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::chain::ChainForwardKinematicAlgorithm;
+    use crate::forward::algorithms::denavit_hartenberg::DenavitHartenbergForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::analytical::AnalyticalInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::damped_least_squares::DampedLeastSquaresInverseKinematicAlgorithm;
+    use crate::inverse::algorithms::InverseKinematicError;
+    use crate::inverse::singularity::{SingularityGuard, SingularityPolicy};
+    use crate::inverse::solver::IterativeSolver;
+    use crate::model::{KinematicLimits, KinematicParameters};
+    use crate::verification::{CrossVerifier, VerificationReport, VerifiedQuantity};
+
+    #[test]
+    pub fn analytical_jacobian_is_consistent() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let verifier: CrossVerifier = CrossVerifier::new(50_usize, 7_u64);
+
+        let report: VerificationReport =
+            verifier.verify_jacobian(&AnalyticalForwardKinematicAlgorithm::default(), &params);
+
+        assert_eq!(report.worst.len(), verifier.worst_count);
+        assert!(report.max_error() < 10_f64.powf(-5_f64));
+        assert!(report
+            .worst
+            .windows(2)
+            .all(|pair| pair[0].error >= pair[1].error));
+    }
+
+    #[test]
+    pub fn forward_algorithms_agree() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let verifier: CrossVerifier = CrossVerifier::new(50_usize, 7_u64);
+
+        let analytical: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+
+        assert!(
+            verifier
+                .verify_forward(
+                    &analytical,
+                    &ChainForwardKinematicAlgorithm::default(),
+                    &params
+                )
+                .max_error()
+                < 10_f64.powf(-9_f64)
+        );
+
+        // A table built for other link lengths must be caught.
+        let other: DenavitHartenbergForwardKinematicAlgorithm =
+            DenavitHartenbergForwardKinematicAlgorithm::from_parameters(&KinematicParameters {
+                l_3: 12_f64,
+                ..KinematicParameters::default()
+            });
+
+        assert!(
+            verifier
+                .verify_forward(&analytical, &other, &params)
+                .max_error()
+                > 1_f64
+        );
+    }
+
+    #[test]
+    pub fn inverse_round_trip() {
+        let params: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits::servo(),
+            ..KinematicParameters::default()
+        };
+        let verifier: CrossVerifier = CrossVerifier::new(20_usize, 3_u64);
+
+        let report: VerificationReport = verifier
+            .verify_inverse(
+                &AnalyticalForwardKinematicAlgorithm::default(),
+                &DampedLeastSquaresInverseKinematicAlgorithm::default(),
+                &IterativeSolver::new(10_f64.powf(-6_f64), 200_usize, 1_f64),
+                &params,
+            )
+            .unwrap();
+
+        assert_eq!(report.samples, 20_usize);
+        assert_eq!(report.failures, 0_usize);
+        // Joint limits slow the solve down close to them, so the round trip is checked coarsely.
+        assert!(report.max_error() < 10_f64.powf(-2_f64));
+    }
+
+    #[test]
+    pub fn records_failed_solves() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let verifier: CrossVerifier = CrossVerifier::new(10_usize, 3_u64);
+        let solver: IterativeSolver = IterativeSolver::new(10_f64.powf(-6_f64), 200_usize, 1_f64);
+
+        // Every jacobian has a condition number of at least one, so every step is refused.
+        let report: VerificationReport = verifier
+            .verify_inverse(
+                &AnalyticalForwardKinematicAlgorithm::default(),
+                &DampedLeastSquaresInverseKinematicAlgorithm::default().with_singularity_guard(
+                    SingularityGuard::new(1_f64, SingularityPolicy::Refuse),
+                ),
+                &solver,
+                &params,
+            )
+            .unwrap();
+
+        assert_eq!(report.failures, 10_usize);
+        assert!(report
+            .worst
+            .iter()
+            .all(|discrepancy| discrepancy.quantity == VerifiedQuantity::FailedSolve));
+
+        // Algorithms that don't fit each other fail every solve, which ends the verification.
+        assert_eq!(
+            verifier.verify_inverse(
+                &DenavitHartenbergForwardKinematicAlgorithm::from_parameters(
+                    &KinematicParameters {
+                        l_3: 12_f64,
+                        ..KinematicParameters::default()
+                    }
+                ),
+                &AnalyticalInverseKinematicAlgorithm::default(),
+                &solver,
+                &params,
+            ),
+            Err(InverseKinematicError::ModelMismatch)
+        );
+    }
+}
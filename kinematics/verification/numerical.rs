@@ -0,0 +1,151 @@
+use nalgebra::{Matrix3, Matrix3x5, Matrix6x5, Vector3, Vector5};
+
+use crate::forward::algorithms::{ForwardKinematicAlgorithm, Limb};
+use crate::model::{KinematicParameters, KinematicState};
+
+/// Numerical jacobian, computed with central finite differences of the forward kinematics, which
+///  serves as an independent check of the analytical jacobians.
+pub struct NumericalJacobian {
+    /// The joint step used for the finite differences.
+    step: f64,
+}
+
+impl Default for NumericalJacobian {
+    fn default() -> Self {
+        Self {
+            step: 10_f64.powf(-6_f64),
+        }
+    }
+}
+
+impl NumericalJacobian {
+    pub fn new(step: f64) -> Self {
+        Self { step }
+    }
+
+    /// Compute the jacobian that maps joint velocities onto the linear velocity of the end of the
+    ///  given limb (in the base frame).
+    pub fn limb_position_jacobian(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        limb: Limb,
+    ) -> Matrix3x5<f64> {
+        let mut jacobian: Matrix3x5<f64> = Matrix3x5::<f64>::zeros();
+
+        for i in 0..5 {
+            let (forward, backward) = self.perturbed_states(state, i);
+
+            let derivative: Vector3<f64> = (fk.limb_position_vector(params, &forward, limb)
+                - fk.limb_position_vector(params, &backward, limb))
+                / (2_f64 * self.step);
+
+            jacobian.set_column(i, &derivative);
+        }
+
+        jacobian
+    }
+
+    /// Compute the jacobian that maps joint velocities onto the angular velocity of the
+    ///  end-effector of the fourth limb (in the base frame), through `skew(w) = dR/dt * R^T`.
+    pub fn limb4_orientation_jacobian(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix3x5<f64> {
+        let orientation: Matrix3<f64> = fk.limb4_orientation_matrix(params, state);
+        let mut jacobian: Matrix3x5<f64> = Matrix3x5::<f64>::zeros();
+
+        for i in 0..5 {
+            let (forward, backward) = self.perturbed_states(state, i);
+
+            let skew: Matrix3<f64> = (fk.limb4_orientation_matrix(params, &forward)
+                - fk.limb4_orientation_matrix(params, &backward))
+                / (2_f64 * self.step)
+                * orientation.transpose();
+
+            // Average the two halves of the (almost) skew-symmetric matrix.
+            jacobian.set_column(
+                i,
+                &(Vector3::<f64>::new(
+                    skew[(2, 1)] - skew[(1, 2)],
+                    skew[(0, 2)] - skew[(2, 0)],
+                    skew[(1, 0)] - skew[(0, 1)],
+                ) / 2_f64),
+            );
+        }
+
+        jacobian
+    }
+
+    /// Compute the geometric jacobian of the end-effector of the fourth limb, laid out like
+    ///  `ForwardKinematicAlgorithm::limb4_jacobian`.
+    pub fn limb4_jacobian(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix6x5<f64> {
+        let mut jacobian: Matrix6x5<f64> = Matrix6x5::<f64>::zeros();
+
+        jacobian
+            .fixed_rows_mut::<3>(0)
+            .copy_from(&self.limb_position_jacobian(fk, params, state, Limb::Limb4));
+        jacobian
+            .fixed_rows_mut::<3>(3)
+            .copy_from(&self.limb4_orientation_jacobian(fk, params, state));
+
+        jacobian
+    }
+
+    /// Create the states in which the given joint is moved a step forward and backward.
+    fn perturbed_states(
+        &self,
+        state: &KinematicState,
+        joint: usize,
+    ) -> (KinematicState, KinematicState) {
+        let offset: Vector5<f64> =
+            Vector5::<f64>::from_fn(|i, _| if i == joint { self.step } else { 0_f64 });
+
+        (
+            KinematicState::from(Vector5::<f64>::from(state) + offset),
+            KinematicState::from(Vector5::<f64>::from(state) - offset),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Matrix6x5;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::model::{KinematicParameters, KinematicState};
+    use crate::verification::numerical::NumericalJacobian;
+
+    #[test]
+    pub fn matches_analytical_jacobian() {
+        let params: KinematicParameters = KinematicParameters::default();
+
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let numerical: NumericalJacobian = NumericalJacobian::default();
+
+        let state: KinematicState = KinematicState {
+            theta_0: -1.3_f64,
+            theta_1: 0.7_f64,
+            theta_2: -0.2_f64,
+            theta_3: 1.1_f64,
+            theta_4: 0.5_f64,
+        };
+
+        let analytical: Matrix6x5<f64> = fk_solver.limb4_jacobian(&params, &state);
+
+        assert!(
+            (numerical.limb4_jacobian(&fk_solver, &params, &state) - analytical).norm()
+                < 10_f64.powf(-5_f64)
+        );
+    }
+}
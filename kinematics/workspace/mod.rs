@@ -0,0 +1,423 @@
+use std::path::Path;
+
+use nalgebra::{Matrix3, Vector3};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::model::{KinematicParameters, KinematicState};
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("Failed to access reachability map file, error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to (de)serialize reachability map, error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("The voxel size must be positive and finite, and leave a countable grid, got {0}")]
+    InvalidVoxelSize(f64),
+    #[error("A grid of dimensions {dimensions:?} does not hold {voxels} voxels")]
+    InvalidDimensions {
+        dimensions: [usize; 3],
+        voxels: usize,
+    },
+}
+
+/// The number of tool direction bins, one for every neighbour of a cell in a cubic grid.
+pub const DIRECTION_COUNT: usize = 26_usize;
+
+/// Get the (unit) center direction of every tool direction bin.
+pub fn tool_direction_bins() -> Vec<Vector3<f64>> {
+    let mut directions: Vec<Vector3<f64>> = Vec::with_capacity(DIRECTION_COUNT);
+
+    for x in -1_i32..=1_i32 {
+        for y in -1_i32..=1_i32 {
+            for z in -1_i32..=1_i32 {
+                if (x, y, z) != (0_i32, 0_i32, 0_i32) {
+                    directions.push(Vector3::<f64>::new(x as f64, y as f64, z as f64).normalize());
+                }
+            }
+        }
+    }
+
+    directions
+}
+
+/// Get the index of the tool direction bin (out of the given bin centers) whose center is closest
+///  to the given direction.
+fn tool_direction_bin(bins: &[Vector3<f64>], direction: &Vector3<f64>) -> usize {
+    bins.iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.dot(direction).total_cmp(&b.dot(direction)))
+        .map(|(i, _)| i)
+        .unwrap_or(0_usize)
+}
+
+/// The reachability of a single voxel.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Voxel {
+    /// The number of sampled states in which the end-effector ended up in the voxel.
+    pub samples: u32,
+    /// The tool direction bins (see `tool_direction_bins`) reached in the voxel, one bit per bin.
+    pub directions: u32,
+}
+
+impl Voxel {
+    /// Check if the end-effector reached the voxel.
+    pub fn is_reachable(&self) -> bool {
+        self.samples > 0_u32
+    }
+
+    /// Get the fraction of the tool direction bins reached in the voxel, from zero (unreachable)
+    ///  to one (reachable with the tool pointing in every direction).
+    pub fn dexterity(&self) -> f64 {
+        self.directions.count_ones() as f64 / DIRECTION_COUNT as f64
+    }
+
+    /// Get the center directions of the tool direction bins reached in the voxel.
+    pub fn tool_directions(&self) -> Vec<Vector3<f64>> {
+        tool_direction_bins()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.directions & (1_u32 << i) != 0_u32)
+            .map(|(_, direction)| direction)
+            .collect()
+    }
+}
+
+/// A voxel map of the positions the end-effector of the fourth limb can reach, and of the tool
+///  directions (the y-axis of the end-effector) it can reach them with.
+///
+/// The grid is a cube around the origin of the base frame, large enough to contain every position
+///  within the sum of the link lengths.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReachabilityMap {
+    /// The length of the edges of a voxel.
+    pub voxel_size: f64,
+    /// The position of the lowest corner of the grid.
+    pub origin: Vector3<f64>,
+    /// The number of voxels along each of the axes.
+    pub dimensions: [usize; 3],
+    /// The voxels, with the x index changing fastest and the z index slowest.
+    voxels: Vec<Voxel>,
+}
+
+impl ReachabilityMap {
+    /// Create an empty map that covers the reach of the arm with the given parameters.
+    ///
+    /// Fails when the voxel size is not positive and finite, or so small compared to the reach
+    ///  that the number of voxels can not be counted.
+    pub fn new(params: &KinematicParameters, voxel_size: f64) -> Result<Self, WorkspaceError> {
+        if !voxel_size.is_finite() || voxel_size <= 0_f64 {
+            return Err(WorkspaceError::InvalidVoxelSize(voxel_size));
+        }
+
+        let reach: f64 = params.sum_of_link_lengths();
+        let cells: f64 = (2_f64 * reach / voxel_size).ceil();
+
+        if !cells.is_finite() || cells >= usize::MAX as f64 {
+            return Err(WorkspaceError::InvalidVoxelSize(voxel_size));
+        }
+
+        let count: usize = cells as usize + 1_usize;
+        let voxels: usize = [count; 3]
+            .iter()
+            .try_fold(1_usize, |voxels, dimension| voxels.checked_mul(*dimension))
+            .ok_or(WorkspaceError::InvalidVoxelSize(voxel_size))?;
+
+        Ok(Self {
+            voxel_size,
+            origin: Vector3::<f64>::repeat(-(count as f64) * voxel_size / 2_f64),
+            dimensions: [count; 3],
+            voxels: vec![Voxel::default(); voxels],
+        })
+    }
+
+    /// Load a map from the given file, as written by `save`.
+    ///
+    /// Fails when the file does not describe a valid grid, like a truncated file in which the
+    ///  number of voxels does not match the dimensions.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WorkspaceError> {
+        let map: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        if !map.voxel_size.is_finite() || map.voxel_size <= 0_f64 {
+            return Err(WorkspaceError::InvalidVoxelSize(map.voxel_size));
+        }
+
+        let count: Option<usize> = map
+            .dimensions
+            .iter()
+            .try_fold(1_usize, |count, dimension| count.checked_mul(*dimension));
+
+        if count != Some(map.voxels.len()) {
+            return Err(WorkspaceError::InvalidDimensions {
+                dimensions: map.dimensions,
+                voxels: map.voxels.len(),
+            });
+        }
+
+        Ok(map)
+    }
+
+    /// Save the map to the given file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WorkspaceError> {
+        Ok(std::fs::write(path, serde_json::to_string(self)?)?)
+    }
+
+    /// Get the index of the voxel that contains the given position, if it lies within the grid.
+    fn index(&self, position: &Vector3<f64>) -> Option<usize> {
+        let mut index: usize = 0_usize;
+
+        for axis in (0..3).rev() {
+            let cell: f64 = ((position[axis] - self.origin[axis]) / self.voxel_size).floor();
+
+            if !(0_f64..self.dimensions[axis] as f64).contains(&cell) {
+                return None;
+            }
+
+            index = index * self.dimensions[axis] + cell as usize;
+        }
+
+        Some(index)
+    }
+
+    /// Get the voxel that contains the given position, if it lies within the grid.
+    pub fn voxel(&self, position: &Vector3<f64>) -> Option<&Voxel> {
+        self.index(position).map(|index| &self.voxels[index])
+    }
+
+    /// Check if the end-effector can reach the given position.
+    pub fn is_reachable(&self, position: &Vector3<f64>) -> bool {
+        self.voxel(position)
+            .map(|voxel| voxel.is_reachable())
+            .unwrap_or(false)
+    }
+
+    /// Get the dexterity (see `Voxel::dexterity`) at the given position.
+    pub fn dexterity(&self, position: &Vector3<f64>) -> f64 {
+        self.voxel(position)
+            .map(|voxel| voxel.dexterity())
+            .unwrap_or(0_f64)
+    }
+
+    /// Get the tool directions the end-effector can reach the given position with.
+    pub fn tool_directions(&self, position: &Vector3<f64>) -> Vec<Vector3<f64>> {
+        self.voxel(position)
+            .map(|voxel| voxel.tool_directions())
+            .unwrap_or_default()
+    }
+
+    /// Check if the end-effector can reach the given position with the tool pointing roughly in
+    ///  the given direction (within the same direction bin).
+    pub fn is_reachable_with(&self, position: &Vector3<f64>, direction: &Vector3<f64>) -> bool {
+        self.voxel(position)
+            .map(|voxel| {
+                voxel.directions & (1_u32 << tool_direction_bin(&tool_direction_bins(), direction))
+                    != 0_u32
+            })
+            .unwrap_or(false)
+    }
+
+    /// Get the number of voxels the end-effector can reach.
+    pub fn reachable_count(&self) -> usize {
+        self.voxels
+            .iter()
+            .filter(|voxel| voxel.is_reachable())
+            .count()
+    }
+
+    /// Record the end-effector position and tool direction bin of a sampled state.
+    fn record(&mut self, position: &Vector3<f64>, direction_bin: usize) {
+        if let Some(index) = self.index(position) {
+            let voxel: &mut Voxel = &mut self.voxels[index];
+
+            voxel.samples = voxel.samples.saturating_add(1_u32);
+            voxel.directions |= 1_u32 << direction_bin;
+        }
+    }
+}
+
+/// Samples the workspace of the arm by running the forward kinematics on random states within
+///  the joint limits.
+pub struct WorkspaceSampler {
+    /// The number of random states to sample.
+    pub samples: usize,
+    /// The length of the edges of the voxels of the map.
+    pub voxel_size: f64,
+    /// The seed of the random states.
+    pub seed: u64,
+}
+
+impl Default for WorkspaceSampler {
+    fn default() -> Self {
+        Self {
+            samples: 1_000_000_usize,
+            voxel_size: 2_f64,
+            seed: 0_u64,
+        }
+    }
+}
+
+impl WorkspaceSampler {
+    pub fn new(samples: usize, voxel_size: f64, seed: u64) -> Self {
+        Self {
+            samples,
+            voxel_size,
+            seed,
+        }
+    }
+
+    /// Build the reachability map of the arm with the given parameters.
+    pub fn sample(
+        &self,
+        fk: &dyn ForwardKinematicAlgorithm,
+        params: &KinematicParameters,
+    ) -> Result<ReachabilityMap, WorkspaceError> {
+        let mut map: ReachabilityMap = ReachabilityMap::new(params, self.voxel_size)?;
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed);
+        let bins: Vec<Vector3<f64>> = tool_direction_bins();
+
+        for _ in 0..self.samples {
+            let state: KinematicState = params.limits.sample(&mut rng);
+            let orientation: Matrix3<f64> = fk.limb4_orientation_matrix(params, &state);
+
+            map.record(
+                &fk.limb4_position_vector(params, &state),
+                tool_direction_bin(&bins, &orientation.column(1).into_owned()),
+            );
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::forward::algorithms::analytical::AnalyticalForwardKinematicAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::model::{JointLimit, KinematicLimits, KinematicParameters, KinematicState};
+    use crate::workspace::{ReachabilityMap, WorkspaceError, WorkspaceSampler};
+
+    #[test]
+    pub fn reachability_respects_limits() {
+        let fk_solver: AnalyticalForwardKinematicAlgorithm =
+            AnalyticalForwardKinematicAlgorithm::default();
+        let sampler: WorkspaceSampler = WorkspaceSampler::new(100_000_usize, 5_f64, 0_u64);
+
+        let unlimited: KinematicParameters = KinematicParameters::default();
+        // Keep the pitch joints close to upright, so the arm cannot reach down.
+        let limited: KinematicParameters = KinematicParameters {
+            limits: KinematicLimits {
+                theta_1: JointLimit::new(-0.3_f64, 0.3_f64),
+                theta_2: JointLimit::new(-0.3_f64, 0.3_f64),
+                theta_3: JointLimit::new(-0.3_f64, 0.3_f64),
+                ..KinematicLimits::unlimited()
+            },
+            ..KinematicParameters::default()
+        };
+
+        let unlimited_map: ReachabilityMap = sampler.sample(&fk_solver, &unlimited).unwrap();
+        let limited_map: ReachabilityMap = sampler.sample(&fk_solver, &limited).unwrap();
+
+        // Positions reached by states within the limits are in the map.
+        let mut rng: StdRng = StdRng::seed_from_u64(42_u64);
+
+        for _ in 0..20 {
+            let state: KinematicState = limited.limits.sample(&mut rng);
+            let position: Vector3<f64> = fk_solver.limb4_position_vector(&limited, &state);
+
+            assert!(limited_map.is_reachable(&position));
+        }
+
+        // Positions beyond the reach of the arm are not.
+        assert!(!unlimited_map.is_reachable(&Vector3::<f64>::new(0_f64, 0_f64, 60_f64)));
+        assert!(!unlimited_map.is_reachable(&Vector3::<f64>::new(500_f64, 0_f64, 0_f64)));
+
+        // Straight below the base can only be reached without limits.
+        let below: Vector3<f64> = Vector3::<f64>::new(0_f64, -20_f64, 0_f64);
+
+        assert!(unlimited_map.is_reachable(&below));
+        assert!(!limited_map.is_reachable(&below));
+        assert!(limited_map.reachable_count() < unlimited_map.reachable_count());
+
+        // At the top of the reach the tool can only point up, closer to the shoulder it is
+        //  far more dexterous.
+        let top: Vector3<f64> = Vector3::<f64>::new(0_f64, 49_f64, 0_f64);
+        let middle: Vector3<f64> = Vector3::<f64>::new(0_f64, 20_f64, 15_f64);
+
+        assert!(unlimited_map
+            .tool_directions(&top)
+            .iter()
+            .all(|direction| direction.y > 0_f64));
+        assert!(unlimited_map.is_reachable_with(&top, &Vector3::<f64>::y()));
+        assert!(!unlimited_map.is_reachable_with(&top, &-Vector3::<f64>::y()));
+        assert!(unlimited_map.dexterity(&middle) > unlimited_map.dexterity(&top));
+    }
+
+    #[test]
+    pub fn save_and_load() {
+        let map: ReachabilityMap = WorkspaceSampler::new(1_000_usize, 10_f64, 1_u64)
+            .sample(
+                &AnalyticalForwardKinematicAlgorithm::default(),
+                &KinematicParameters::default(),
+            )
+            .unwrap();
+
+        let path: PathBuf = std::env::temp_dir().join("rustydog_reachability_map.json");
+
+        map.save(&path).unwrap();
+        let loaded: ReachabilityMap = ReachabilityMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, map);
+        // Voxel sizes that can not be divided by, or leave more voxels than can be counted.
+        for voxel_size in [
+            0_f64,
+            f64::NAN,
+            f64::INFINITY,
+            10_f64.powf(-6_f64),
+            10_f64.powf(-300_f64),
+        ] {
+            assert!(matches!(
+                ReachabilityMap::new(&KinematicParameters::default(), voxel_size),
+                Err(WorkspaceError::InvalidVoxelSize(_))
+            ));
+        }
+    }
+
+    #[test]
+    pub fn refuses_invalid_file() {
+        let map: ReachabilityMap =
+            ReachabilityMap::new(&KinematicParameters::default(), 10_f64).unwrap();
+        let path: PathBuf = std::env::temp_dir().join("rustydog_invalid_reachability_map.json");
+
+        // A map whose dimensions do not match its voxels, like a truncated file.
+        let mut mismatched: ReachabilityMap = map.clone();
+        mismatched.dimensions[2] += 1_usize;
+        mismatched.save(&path).unwrap();
+
+        assert!(matches!(
+            ReachabilityMap::load(&path),
+            Err(WorkspaceError::InvalidDimensions { .. })
+        ));
+
+        // A map with a voxel size that can not be divided by.
+        let mut flat: ReachabilityMap = map;
+        flat.voxel_size = 0_f64;
+        flat.save(&path).unwrap();
+
+        assert!(matches!(
+            ReachabilityMap::load(&path),
+            Err(WorkspaceError::InvalidVoxelSize(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}